@@ -1,5 +1,5 @@
 use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
-use lattice_db::{GraphBuilder, LatticeDb, QueryBuilder};
+use lattice_db::{DbOptions, GraphBuilder, LatticeDb, QueryBuilder};
 
 // test lookup time of nodes all connected to one node
 fn bench_supernode(c: &mut Criterion) {
@@ -46,6 +46,55 @@ fn bench_supernode(c: &mut Criterion) {
     }
 }
 
+// same as supernode, only counting the followers
+fn bench_supernode_count(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Query");
+    group.sample_size(100);
+
+    let sizes: Vec<_> = (1..5).map(|v| 10usize.pow(v)).collect();
+    for size in sizes.iter() {
+        group.throughput(criterion::Throughput::Elements(*size as u64));
+        group.bench_with_input(
+            BenchmarkId::new("Supernode Count", size),
+            size,
+            |b, &size| {
+                let (db, _) = LatticeDb::create_temporary().unwrap();
+                let mut wr = db.begin_write().unwrap();
+                let prop_follows = wr.register_property(None, &()).unwrap();
+                let prop_type = wr.register_property(None, &()).unwrap();
+
+                // populate graph
+                let mut graph = GraphBuilder::new();
+                let celebrity = graph
+                    .new_vertex()
+                    .new_attribute(prop_type, "celebrity")
+                    .unwrap()
+                    .handle();
+                for _ in 0..size {
+                    let fan = graph.new_vertex().handle();
+                    graph.new_edge(fan, prop_follows, celebrity).unwrap();
+                }
+
+                wr.save_graphs_parallel(vec![graph]).unwrap();
+                wr.commit().unwrap();
+
+                // count who follows the celebrity
+                let mut query = QueryBuilder::new();
+                let celeb_node = query.match_attr(prop_type, "celebrity").unwrap();
+                let followers = query.match_incoming(prop_follows, celeb_node).unwrap();
+                query.set_root(followers);
+                let query = query.compile().unwrap();
+
+                let reader = db.begin_read().unwrap();
+                b.iter(|| {
+                    let count = reader.count(black_box(&query)).unwrap();
+                    assert_eq!(count, size as u64);
+                });
+            },
+        );
+    }
+}
+
 // intersections perform searches from the smaller set to resolve instantly
 fn bench_intersection(c: &mut Criterion) {
     let mut group = c.benchmark_group("Query");
@@ -135,7 +184,64 @@ fn bench_union(c: &mut Criterion) {
     }
 }
 
+// a range scan against a union of every exact value in the range
+fn bench_range(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Query");
+    group.sample_size(100);
+
+    let sizes: Vec<_> = (1..4).map(|v| 10u64.pow(v)).collect();
+    for size in sizes.iter() {
+        group.throughput(criterion::Throughput::Elements(*size));
+
+        let (db, _) = LatticeDb::create_temporary().unwrap();
+        let mut wr = db.begin_write().unwrap();
+        let prop_age = wr.register_property(None, &()).unwrap();
+
+        // ten vertices for every value
+        let mut graph = GraphBuilder::new();
+        for i in 0..size * 10 {
+            graph.new_vertex().new_attribute(prop_age, i / 10).unwrap();
+        }
+        wr.save_graphs_parallel(vec![graph]).unwrap();
+        wr.commit().unwrap();
+        let reader = db.begin_read().unwrap();
+
+        group.bench_with_input(BenchmarkId::new("Range", size), size, |b, &size| {
+            let mut query = QueryBuilder::new();
+            let root = query.match_range(prop_age, 0..size).unwrap();
+            query.set_root(root);
+            let query = query.compile().unwrap();
+
+            b.iter(|| {
+                let res = reader.search(black_box(&query)).unwrap();
+                assert_eq!(res.len() as u64, size * 10);
+            });
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("Range as Union", size),
+            size,
+            |b, &size| {
+                let mut query = QueryBuilder::new();
+                let mut terms = vec![];
+                for n in 0..size {
+                    terms.push(query.match_attr(prop_age, n).unwrap());
+                }
+                let root = query.group_or(terms).unwrap();
+                query.set_root(root);
+                let query = query.compile().unwrap();
+
+                b.iter(|| {
+                    let res = reader.search(black_box(&query)).unwrap();
+                    assert_eq!(res.len() as u64, size * 10);
+                });
+            },
+        );
+    }
+}
+
 // lookup a node based on its linking with another node's X amount of links
+#[allow(clippy::needless_range_loop)]
 fn bench_query_chain(c: &mut Criterion) {
     let mut group = c.benchmark_group("Query");
     group.sample_size(100);
@@ -182,11 +288,51 @@ fn bench_query_chain(c: &mut Criterion) {
     }
 }
 
+// commit cost of optimizing index bitmaps over contiguous vertex ids
+fn bench_run_optimize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Commit");
+    group.sample_size(20);
+
+    let size = 100_000;
+    for run_optimize in [false, true] {
+        group.bench_with_input(
+            BenchmarkId::new("Run Optimize", run_optimize),
+            &run_optimize,
+            |b, &run_optimize| {
+                b.iter(|| {
+                    let file = tempfile::NamedTempFile::new().unwrap();
+                    let options = DbOptions {
+                        run_optimize,
+                        ..Default::default()
+                    };
+                    let db = LatticeDb::create_with_options(file.path(), options).unwrap();
+                    let mut wr = db.begin_write().unwrap();
+                    let prop = wr.register_property(None, &()).unwrap();
+
+                    // every vertex shares the value, so the bitmap is one long run
+                    let mut graph = GraphBuilder::new();
+                    for _ in 0..size {
+                        graph.new_vertex().new_attribute(prop, 1u8).unwrap();
+                    }
+                    wr.set_metrics_hook(|metrics| {
+                        black_box(metrics.scalar_bytes);
+                    });
+                    wr.save_graphs_parallel(vec![graph]).unwrap();
+                    wr.commit().unwrap();
+                });
+            },
+        );
+    }
+}
+
 criterion_group!(
     benches,
     bench_query_chain,
     bench_supernode,
+    bench_supernode_count,
     bench_union,
+    bench_range,
     bench_intersection,
+    bench_run_optimize,
 );
 criterion_main!(benches);
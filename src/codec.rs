@@ -0,0 +1,61 @@
+use bincode::{
+    Decode, Encode, config,
+    error::{DecodeError, EncodeError},
+};
+
+use crate::errors::LatticeError;
+
+/// Integer encoding used for every value the database serializes.
+/// * `Variable` keeps small integers small (bincode's standard config).
+/// * `Fixed` writes every integer at full width, which decodes faster for large random ids.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntEncoding {
+    #[default]
+    Variable,
+    Fixed,
+}
+
+impl IntEncoding {
+    pub(crate) fn to_u64(self) -> u64 {
+        match self {
+            IntEncoding::Variable => 0,
+            IntEncoding::Fixed => 1,
+        }
+    }
+
+    // unknown values come from a corrupt or newer database
+    pub(crate) fn from_u64(value: u64) -> Result<Self, LatticeError> {
+        match value {
+            0 => Ok(IntEncoding::Variable),
+            1 => Ok(IntEncoding::Fixed),
+            _ => Err(LatticeError::UnknownEncoding(value)),
+        }
+    }
+}
+
+/// Encode a value with the selected integer encoding.
+pub(crate) fn encode_to_vec<E: Encode>(
+    value: E,
+    encoding: IntEncoding,
+) -> Result<Vec<u8>, EncodeError> {
+    match encoding {
+        IntEncoding::Variable => bincode::encode_to_vec(value, config::standard()),
+        IntEncoding::Fixed => {
+            bincode::encode_to_vec(value, config::standard().with_fixed_int_encoding())
+        }
+    }
+}
+
+/// Decode a value with the selected integer encoding.
+pub(crate) fn decode_from_slice<D: Decode<()>>(
+    bytes: &[u8],
+    encoding: IntEncoding,
+) -> Result<D, DecodeError> {
+    let (value, _) = match encoding {
+        IntEncoding::Variable => bincode::decode_from_slice(bytes, config::standard())?,
+        IntEncoding::Fixed => {
+            bincode::decode_from_slice(bytes, config::standard().with_fixed_int_encoding())?
+        }
+    };
+    Ok(value)
+}
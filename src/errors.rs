@@ -10,8 +10,12 @@ pub enum LatticeError {
     VertexNotFound,
     #[error("Edge does not exist")]
     EdgeNotFound,
-    #[error("Numeric overflow: {0}")]
-    NumberTooBig(String),
+    #[error("Numeric overflow: {value}{}", property_context(.property, .alias))]
+    NumberTooBig {
+        value: String,
+        property: Option<u64>,
+        alias: Option<String>,
+    },
     #[error("Query root not assigned")]
     RootNotFound,
     #[error("Serialization error: {0}")]
@@ -32,6 +36,10 @@ pub enum LatticeError {
     CommitError(#[from] CommitError),
     #[error("Graph does not exist")]
     GraphNotFound,
+    #[error("Graph ID already exists")]
+    GraphIdTaken,
+    #[error("Graph was loaded from the database")]
+    GraphAlreadySaved,
     #[error("Error decoding data")]
     DecodeError(#[from] DecodeError),
     #[error("Alias already exists")]
@@ -42,4 +50,43 @@ pub enum LatticeError {
     QueryNodeNotFound,
     #[error("Query not found")]
     QueryNotFound,
+    #[error("Saved query references itself")]
+    QueryCycle,
+    #[error("Edge endpoint does not exist")]
+    DanglingEdge,
+    #[error("Property ID already exists")]
+    PropertyIdTaken,
+    #[error("Search deadline exceeded")]
+    Timeout,
+    #[error("Search cancelled")]
+    Cancelled,
+    #[error("Preloaded entries are from another reader")]
+    SnapshotMismatch,
+    #[error("Another writer is open")]
+    WriterBusy,
+    #[error("Databases use different integer encodings")]
+    EncodingMismatch,
+    #[error("Property options do not match: {0}")]
+    PropertyOptionsMismatch(String),
+    #[error("Unknown integer encoding: {0}")]
+    UnknownEncoding(u64),
+    #[error("No ids left in the sequence")]
+    IdSpaceExhausted,
+    #[error("Query nodes do not form a DAG")]
+    InvalidQueryPlan,
+    #[error("Range bounds must both be UInt or both be Int")]
+    InvalidRange,
+    #[error("Float value is NaN")]
+    InvalidFloat,
+    #[error("Unsupported format version: {0}")]
+    UnsupportedVersion(u64),
+}
+
+// helper fn to name the property of an error, by alias when it is known
+fn property_context(property: &Option<u64>, alias: &Option<String>) -> String {
+    match (alias, property) {
+        (Some(alias), _) => format!(" for property {alias}"),
+        (None, Some(id)) => format!(" for property {id}"),
+        (None, None) => String::new(),
+    }
 }
@@ -1,24 +1,60 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+use bincode::{Decode, Encode};
 
 use crate::{
     errors::LatticeError,
     graph::graph_prepared::PreparedGraph,
     properties::PropertyHandle,
+    query::query_builder::EdgeDirection,
     utils::{
         generational_vector::{GenVec, Handle},
-        values::{Primitive, Value},
+        values::{OnOverflow, Primitive, Value},
     },
 };
 
 pub(crate) type GlobalVertexId = u64;
 pub(crate) type GraphId = u64;
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+/// Reference to a vertex inside a GraphBuilder.
+/// * Only valid against the same builder it came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VertexHandle(pub(crate) Handle);
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+/// Reference to an edge inside a GraphBuilder.
+/// * Only valid against the same builder it came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EdgeHandle(Handle);
 
+impl VertexHandle {
+    /// Recreate a vertex handle from a raw handle.
+    pub fn from_raw(handle: Handle) -> Self {
+        Self(handle)
+    }
+
+    /// Returns the raw handle.
+    pub fn raw(&self) -> Handle {
+        self.0
+    }
+}
+
+impl EdgeHandle {
+    /// Recreate an edge handle from a raw handle.
+    pub fn from_raw(handle: Handle) -> Self {
+        Self(handle)
+    }
+
+    /// Returns the raw handle.
+    pub fn raw(&self) -> Handle {
+        self.0
+    }
+}
+
 pub struct VertexData {
     pub global_id: Option<GlobalVertexId>,
     pub attributes: Vec<(PropertyHandle, Primitive)>,
@@ -26,10 +62,39 @@ pub struct VertexData {
     pub outgoing_edges: Vec<EdgeHandle>,
 }
 
+impl VertexData {
+    /// Return the first value of the attribute.
+    ///
+    /// ```
+    /// # use lattice_db::{GraphBuilder, LatticeDb, values::Primitive};
+    /// # let (db, _file) = LatticeDb::create_temporary().unwrap();
+    /// # let mut wr = db.begin_write().unwrap();
+    /// let age = wr.register_property("age", &()).unwrap();
+    /// let mut graph = GraphBuilder::new();
+    /// let handle = graph.new_vertex().new_attribute(age, 30u64).unwrap().handle();
+    ///
+    /// let vertex = graph.get_vertex(handle).unwrap();
+    /// assert_eq!(vertex.get_attribute(age), Some(&Primitive::UInt(30)));
+    /// assert_eq!(vertex.get_attributes(age).count(), 1);
+    /// ```
+    pub fn get_attribute(&self, attr: PropertyHandle) -> Option<&Primitive> {
+        self.get_attributes(attr).next()
+    }
+
+    /// Return every value of the attribute.
+    pub fn get_attributes(&self, attr: PropertyHandle) -> impl Iterator<Item = &Primitive> {
+        self.attributes
+            .iter()
+            .filter(move |(a, _)| *a == attr)
+            .map(|(_, v)| v)
+    }
+}
+
 pub struct EdgeData {
     pub from: VertexHandle,
     pub to: VertexHandle,
     pub label: PropertyHandle,
+    pub attributes: Vec<(PropertyHandle, Primitive)>,
 }
 
 pub(crate) struct OldGraphData {
@@ -39,6 +104,8 @@ pub(crate) struct OldGraphData {
 
 pub struct GraphBuilder {
     new_vertex_count: u64,
+    pub(crate) inline_attributes: bool,
+    on_overflow: OnOverflow,
     pub(crate) old_graph_data: Option<OldGraphData>,
     pub(crate) vertices: GenVec<VertexData>,
     pub(crate) edges: GenVec<EdgeData>,
@@ -62,8 +129,9 @@ impl GraphBuilder {
         VertexBuilder::new(self, handle)
     }
 
-    /// Removes a vertex and all attatched edges.
-    pub fn remove_vertex(&mut self, handle: VertexHandle) -> Result<(), LatticeError> {
+    /// Removes a vertex and all attatched edges, returning the removed vertex.
+    /// * The edge handles of the returned vertex refer to edges that were removed.
+    pub fn remove_vertex(&mut self, handle: VertexHandle) -> Result<VertexData, LatticeError> {
         let removed = self
             .vertices
             .remove(handle.0)
@@ -73,11 +141,11 @@ impl GraphBuilder {
         } // removing a new vertex decreases new vertex count
 
         // remove all edges, ignore errors from self loop edges
-        let edges = [removed.incoming_edges, removed.outgoing_edges].concat();
+        let edges = [&removed.incoming_edges[..], &removed.outgoing_edges[..]].concat();
         for edge in edges {
             let _ = self.remove_edge(edge);
         }
-        Ok(())
+        Ok(removed)
     }
 
     /// Return a VertexBuilder to edit the vertex.
@@ -96,6 +164,52 @@ impl GraphBuilder {
         self.vertices.get(handle.0)
     }
 
+    /// Get the number of edges leaving or entering a vertex.
+    pub fn degree(&self, handle: VertexHandle, dir: EdgeDirection) -> Result<usize, LatticeError> {
+        let vertex = self
+            .vertices
+            .get(handle.0)
+            .ok_or(LatticeError::VertexNotFound)?;
+        Ok(match dir {
+            EdgeDirection::Outgoing => vertex.outgoing_edges.len(),
+            EdgeDirection::Incoming => vertex.incoming_edges.len(),
+        })
+    }
+
+    /// Get the vertices at the other end of the edges leaving or entering a vertex.
+    /// * A vertex linked by several edges is listed once per edge.
+    /// * Returns an error if an edge or its endpoint no longer exists.
+    pub fn neighbors(
+        &self,
+        handle: VertexHandle,
+        dir: EdgeDirection,
+    ) -> Result<Vec<VertexHandle>, LatticeError> {
+        let vertex = self
+            .vertices
+            .get(handle.0)
+            .ok_or(LatticeError::VertexNotFound)?;
+        let edges = match dir {
+            EdgeDirection::Outgoing => &vertex.outgoing_edges,
+            EdgeDirection::Incoming => &vertex.incoming_edges,
+        };
+        let mut neighbors = Vec::with_capacity(edges.len());
+        for edge_handle in edges {
+            let edge = self
+                .edges
+                .get(edge_handle.0)
+                .ok_or(LatticeError::DanglingEdge)?;
+            let other = match dir {
+                EdgeDirection::Outgoing => edge.to,
+                EdgeDirection::Incoming => edge.from,
+            };
+            self.vertices
+                .get(other.0)
+                .ok_or(LatticeError::DanglingEdge)?;
+            neighbors.push(other);
+        }
+        Ok(neighbors)
+    }
+
     /// Create a new edge to link vertices.
     pub fn new_edge(
         &mut self,
@@ -103,6 +217,52 @@ impl GraphBuilder {
         label: PropertyHandle,
         to: VertexHandle,
     ) -> Result<&mut Self, LatticeError> {
+        self.add_edge(from, label, to)?;
+        Ok(self)
+    }
+
+    /// Create a new edge, unless one with the same source, label, and destination exists.
+    /// * Returns the handle of the existing edge or the new one.
+    pub fn new_edge_unique(
+        &mut self,
+        from: VertexHandle,
+        label: PropertyHandle,
+        to: VertexHandle,
+    ) -> Result<EdgeHandle, LatticeError> {
+        let from_vertex = self
+            .vertices
+            .get(from.0)
+            .ok_or(LatticeError::VertexNotFound)?;
+        let existing = from_vertex.outgoing_edges.iter().find(|h| {
+            self.edges
+                .get(h.0)
+                .is_some_and(|e| e.label == label && e.to == to)
+        });
+        match existing {
+            Some(handle) => Ok(*handle),
+            None => self.add_edge(from, label, to),
+        }
+    }
+
+    /// Create a new edge and return an EdgeBuilder to edit it.
+    /// * Use this to give the edge attributes, such as a weight.
+    pub fn build_edge<'a>(
+        &'a mut self,
+        from: VertexHandle,
+        label: PropertyHandle,
+        to: VertexHandle,
+    ) -> Result<EdgeBuilder<'a>, LatticeError> {
+        let handle = self.add_edge(from, label, to)?;
+        Ok(EdgeBuilder::new(self, handle.0))
+    }
+
+    // helper fn to create an edge and link it to its vertices
+    fn add_edge(
+        &mut self,
+        from: VertexHandle,
+        label: PropertyHandle,
+        to: VertexHandle,
+    ) -> Result<EdgeHandle, LatticeError> {
         self.vertices
             .get(from.0)
             .ok_or(LatticeError::VertexNotFound)?; // confirm 'from' exists
@@ -112,13 +272,18 @@ impl GraphBuilder {
             .ok_or(LatticeError::VertexNotFound)?;
 
         // create edge
-        let edge_handle = EdgeHandle(self.edges.add(EdgeData { from, label, to }));
+        let edge_handle = EdgeHandle(self.edges.add(EdgeData {
+            from,
+            label,
+            to,
+            attributes: vec![],
+        }));
 
         // add edge
         to_vertex.incoming_edges.push(edge_handle);
         let from_vertex = self.vertices.get_mut(from.0).unwrap();
         from_vertex.outgoing_edges.push(edge_handle);
-        Ok(self)
+        Ok(edge_handle)
     }
 
     /// Removes an edge from the graph.
@@ -145,6 +310,91 @@ impl GraphBuilder {
         Ok(())
     }
 
+    /// Return the edges from `a` to `b`, with any label.
+    /// * Returns nothing if either vertex doesn't exist.
+    pub fn edges_between(&self, a: VertexHandle, b: VertexHandle) -> Vec<EdgeHandle> {
+        let Some(vertex) = self.vertices.get(a.0) else {
+            return vec![];
+        };
+        vertex
+            .outgoing_edges
+            .iter()
+            .filter(|h| self.edges.get(h.0).is_some_and(|e| e.to == b))
+            .copied()
+            .collect()
+    }
+
+    /// Return the edges between `a` and `b` in either direction, with any label.
+    /// * Self-loops are returned once.
+    pub fn edges_connecting(&self, a: VertexHandle, b: VertexHandle) -> Vec<EdgeHandle> {
+        let mut edges = self.edges_between(a, b);
+        if a != b {
+            edges.extend(self.edges_between(b, a));
+        }
+        edges
+    }
+
+    /// Removes edges that have the same source, label, and destination as an earlier edge.
+    /// * Returns the number of edges removed.
+    pub fn dedup_edges(&mut self) -> usize {
+        let mut seen = HashSet::new();
+        let duplicates: Vec<EdgeHandle> = self
+            .edges
+            .iter()
+            .filter(|(_, e)| !seen.insert((e.from, e.label, e.to)))
+            .map(|(h, _)| EdgeHandle(h))
+            .collect();
+        for edge in &duplicates {
+            let _ = self.remove_edge(*edge);
+        }
+        duplicates.len()
+    }
+
+    /// Removes every edge with the label.
+    /// * Returns the number of edges removed.
+    pub fn remove_edges_by_label(&mut self, label: PropertyHandle) -> usize {
+        let matching: Vec<EdgeHandle> = self
+            .edges
+            .iter()
+            .filter(|(_, e)| e.label == label)
+            .map(|(h, _)| EdgeHandle(h))
+            .collect();
+        for edge in &matching {
+            let _ = self.remove_edge(*edge);
+        }
+        matching.len()
+    }
+
+    /// Changes the label of every edge with `from_label` to `to_label`.
+    /// * Returns the number of edges relabeled.
+    pub fn relabel_edges(&mut self, from_label: PropertyHandle, to_label: PropertyHandle) -> usize {
+        let mut count = 0;
+        for (_, edge) in self.edges.iter_mut_from(0) {
+            if edge.label == from_label {
+                edge.label = to_label;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Confirm every edge points to live vertices, and every vertex only lists live edges.
+    /// * Returns an error on the first inconsistency found.
+    pub fn check_edge_integrity(&self) -> Result<(), LatticeError> {
+        for (_, edge) in self.edges.iter() {
+            if self.vertices.get(edge.from.0).is_none() || self.vertices.get(edge.to.0).is_none() {
+                return Err(LatticeError::DanglingEdge);
+            }
+        }
+        for (_, vertex) in self.vertices.iter() {
+            let listed = vertex.incoming_edges.iter().chain(&vertex.outgoing_edges);
+            for edge in listed {
+                self.edges.get(edge.0).ok_or(LatticeError::DanglingEdge)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Return an EdgeBuilder to edit the edge.
     pub fn edit_edge<'a>(
         &'a mut self,
@@ -199,6 +449,23 @@ impl<'a> EdgeBuilder<'a> {
         self.get_self().label = label;
         Ok(self)
     }
+
+    /// Create a new attribute with a value.
+    /// * Edge attributes are indexed per label, see `LatticeReader::edges_by_weight`.
+    pub fn new_attribute<V: Value>(
+        &mut self,
+        attr: PropertyHandle,
+        value: V,
+    ) -> Result<&mut Self, LatticeError> {
+        let v = self.graph.on_overflow.apply(attr.0, value.to_primitive())?;
+        self.get_self().attributes.push((attr, v));
+        Ok(self)
+    }
+
+    /// Returns the edge handle.
+    pub fn handle(&self) -> EdgeHandle {
+        EdgeHandle(self.handle)
+    }
 }
 
 pub struct VertexBuilder<'a> {
@@ -221,12 +488,62 @@ impl<'a> VertexBuilder<'a> {
         attr: PropertyHandle,
         value: V,
     ) -> Result<&mut Self, LatticeError> {
-        let v = value.to_primitive();
-        v.verify()?;
+        let v = self.graph.on_overflow.apply(attr.0, value.to_primitive())?;
         self.get_self().attributes.push((attr, v));
         Ok(self)
     }
 
+    /// Create an attribute value for each item, so each is indexed and matched separately.
+    /// * Nothing is added if any value is invalid.
+    pub fn new_multi_attribute<V: Value>(
+        &mut self,
+        attr: PropertyHandle,
+        values: impl IntoIterator<Item = V>,
+    ) -> Result<&mut Self, LatticeError> {
+        let mut primitives = vec![];
+        for value in values {
+            let v = self.graph.on_overflow.apply(attr.0, value.to_primitive())?;
+            primitives.push((attr, v));
+        }
+        self.get_self().attributes.extend(primitives);
+        Ok(self)
+    }
+
+    /// Replace every value of the attribute with a single value.
+    /// * Existing values are kept if the new value is invalid.
+    pub fn set_attribute<V: Value>(
+        &mut self,
+        attr: PropertyHandle,
+        value: V,
+    ) -> Result<&mut Self, LatticeError> {
+        let v = self.graph.on_overflow.apply(attr.0, value.to_primitive())?;
+        let attributes = &mut self.get_self().attributes;
+        attributes.retain(|(a, _)| *a != attr);
+        attributes.push((attr, v));
+        Ok(self)
+    }
+
+    /// Remove every value of the attribute.
+    /// * Removing an attribute the vertex doesn't have does nothing.
+    pub fn remove_attribute(&mut self, attr: PropertyHandle) -> Result<&mut Self, LatticeError> {
+        self.get_self().attributes.retain(|(a, _)| *a != attr);
+        Ok(self)
+    }
+
+    /// Remove one value of the attribute, keeping its other values.
+    /// * Values are compared with the property options applied, like matches in a query.
+    pub fn remove_attribute_value<V: Value>(
+        &mut self,
+        attr: PropertyHandle,
+        value: V,
+    ) -> Result<&mut Self, LatticeError> {
+        let value = self.graph.on_overflow.apply(attr.0, value.to_primitive())?;
+        self.get_self()
+            .attributes
+            .retain(|(a, v)| *a != attr || !attr.values_match(v, &value));
+        Ok(self)
+    }
+
     /// Create a new edge to link vertices.
     pub fn new_edge(
         &mut self,
@@ -244,6 +561,7 @@ impl<'a> VertexBuilder<'a> {
             from: VertexHandle(self.handle),
             label,
             to: handle,
+            attributes: vec![],
         }));
 
         // add edge
@@ -270,6 +588,18 @@ impl GraphBuilder {
             .global_id)
     }
 
+    /// Iterate through edges as `(from, label, to)` global vertex ids.
+    /// * Edges with an endpoint that has not been saved yet are skipped.
+    pub fn iter_committed_edges(
+        &self,
+    ) -> impl Iterator<Item = (GlobalVertexId, PropertyHandle, GlobalVertexId)> {
+        self.edges.iter().filter_map(|(_, edge)| {
+            let from = self.vertices.get(edge.from.0)?.global_id?;
+            let to = self.vertices.get(edge.to.0)?.global_id?;
+            Some((from, edge.label, to))
+        })
+    }
+
     pub fn get_mut_attributes(
         &mut self,
         handle: VertexHandle,
@@ -303,6 +633,28 @@ impl GraphBuilder {
             .outgoing_edges)
     }
 
+    /// Set what happens when a new attribute's number is too large to store.
+    /// * Defaults to `OnOverflow::Error`.
+    pub fn set_overflow_policy(&mut self, policy: OnOverflow) {
+        self.on_overflow = policy;
+    }
+
+    /// Store vertex attributes only inside the graph instead of the scalar index.
+    /// * Meant for tiny graphs, where index entries cost more than scanning the graph.
+    /// * `search` scans every inline graph for attribute matches, other index lookups skip them.
+    /// * Loaded graphs keep the mode they were saved with.
+    pub fn set_inline_attributes(&mut self, enabled: bool) {
+        self.inline_attributes = enabled;
+    }
+
+    /// Release the memory of removed vertices and edges past the last live ones.
+    /// * Meant for long-lived builders with many additions and removals.
+    /// * Existing handles stay valid, handles to removed items stay invalid.
+    pub fn shrink_to_fit(&mut self) {
+        self.vertices.shrink_to_fit();
+        self.edges.shrink_to_fit();
+    }
+
     /// Iterate through all graph vertices.
     pub fn iter_vertices(&self) -> impl Iterator<Item = (Handle, &VertexData)> {
         self.vertices.iter()
@@ -318,12 +670,112 @@ impl GraphBuilder {
     pub fn new() -> Self {
         Self {
             new_vertex_count: 0,
+            inline_attributes: false,
+            on_overflow: OnOverflow::Error,
             old_graph_data: None,
             vertices: GenVec::new(),
             edges: GenVec::new(),
         }
     }
 
+    /// Build a graph from raw vertex and edge data.
+    /// * Edges reference vertices by their index in `vertices`.
+    /// * Returns an error if an edge references a vertex that does not exist.
+    pub fn from_parts(
+        vertices: Vec<Vec<(PropertyHandle, Primitive)>>,
+        edges: Vec<(usize, PropertyHandle, usize)>,
+    ) -> Result<Self, LatticeError> {
+        let mut builder = Self::new();
+
+        // populate vertices
+        let mut handles = Vec::with_capacity(vertices.len());
+        for attributes in vertices {
+            for (attr, value) in &attributes {
+                value.verify_property(attr.0)?;
+            }
+            builder.new_vertex_count += 1;
+            let handle = builder.vertices.add(VertexData {
+                global_id: None,
+                attributes,
+                incoming_edges: vec![],
+                outgoing_edges: vec![],
+            });
+            handles.push(VertexHandle(handle));
+        }
+
+        // populate edges
+        for (from, label, to) in edges {
+            let from = *handles.get(from).ok_or(LatticeError::VertexNotFound)?;
+            let to = *handles.get(to).ok_or(LatticeError::VertexNotFound)?;
+            builder.new_edge(from, label, to)?;
+        }
+        Ok(builder)
+    }
+
+    /// Split the graph into one graph per connected component.
+    /// * Edge direction is ignored when finding components.
+    /// * Every returned graph is saved as a new graph with new vertex ids.
+    /// * Returns an error if this graph was loaded, its vertices would be saved twice.
+    pub fn split_components(mut self) -> Result<Vec<GraphBuilder>, LatticeError> {
+        if self.old_graph_data.is_some() {
+            return Err(LatticeError::GraphAlreadySaved);
+        }
+
+        let mut components: Vec<GraphBuilder> = vec![];
+        let mut placed: HashMap<VertexHandle, (usize, VertexHandle)> = HashMap::new(); // old handle -> (component, new handle)
+
+        let handles: Vec<VertexHandle> =
+            self.vertices.iter().map(|(h, _)| VertexHandle(h)).collect();
+        for start in handles {
+            if placed.contains_key(&start) {
+                continue;
+            }
+
+            // walk every vertex connected to start
+            let component_idx = components.len();
+            let mut component = GraphBuilder::new();
+            let mut stack = vec![start];
+            while let Some(handle) = stack.pop() {
+                if placed.contains_key(&handle) {
+                    continue;
+                }
+                let Some(vertex) = self.vertices.get_mut(handle.0) else {
+                    continue; // dangling edge endpoint
+                };
+                let attributes = mem::take(&mut vertex.attributes);
+                for edge in vertex.incoming_edges.iter().chain(&vertex.outgoing_edges) {
+                    if let Some(edge) = self.edges.get(edge.0) {
+                        stack.extend_from_slice(&[edge.from, edge.to]);
+                    }
+                }
+
+                component.new_vertex_count += 1;
+                let new_handle = component.vertices.add(VertexData {
+                    global_id: None,
+                    attributes,
+                    incoming_edges: vec![],
+                    outgoing_edges: vec![],
+                });
+                placed.insert(handle, (component_idx, VertexHandle(new_handle)));
+            }
+            components.push(component);
+        }
+
+        // both endpoints of an edge are always in the same component
+        for (_, edge) in self.edges.iter_mut_from(0) {
+            if let (Some((component_idx, from)), Some((_, to))) =
+                (placed.get(&edge.from), placed.get(&edge.to))
+            {
+                let component = &mut components[*component_idx];
+                if let Ok(handle) = component.add_edge(*from, edge.label, *to) {
+                    component.edges.get_mut(handle.0).unwrap().attributes =
+                        mem::take(&mut edge.attributes);
+                }
+            }
+        }
+        Ok(components)
+    }
+
     pub(crate) fn count_new_vertices(&self) -> u64 {
         self.new_vertex_count
     }
@@ -351,6 +803,7 @@ impl GraphBuilder {
                 from,
                 to,
                 label: e.label,
+                attributes: e.attrs.clone(),
             });
             builder
                 .vertices
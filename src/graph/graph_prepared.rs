@@ -1,13 +1,18 @@
-use std::{collections::HashMap, mem};
+use std::{
+    collections::{BTreeSet, HashMap},
+    mem,
+};
 
-use bincode::{Decode, Encode, config};
+use bincode::{Decode, Encode};
+use redb::ReadableTable;
 
 use crate::{
     PreparedQuery,
+    codec::{self, IntEncoding},
     errors::LatticeError,
     graph::graph_builder::GraphBuilder,
     graph_builder::{GlobalVertexId, GraphId},
-    properties::PropertyHandle,
+    properties::{INTERNED_VALUE, PropertyHandle},
     utils::{generational_vector::Handle, values::Primitive},
 };
 
@@ -22,8 +27,15 @@ pub(crate) struct PreparedEdge {
     pub(crate) from: GlobalVertexId,
     pub(crate) label: PropertyHandle,
     pub(crate) to: GlobalVertexId,
+    pub(crate) attrs: Vec<(PropertyHandle, Primitive)>,
 }
 
+// (vertex id, prop id, value hash, indexed text)
+pub(crate) type AttrEntry = (u64, u64, u64, Option<String>);
+
+// (label, prop id, value hash, from, to)
+pub(crate) type EdgeAttrEntry = (u64, u64, u64, u64, u64);
+
 #[derive(Encode, Decode)]
 pub(crate) struct PreparedGraph {
     pub(crate) id: GraphId,
@@ -35,10 +47,12 @@ pub(crate) struct PreparedGraph {
 pub(crate) struct GraphCommitData {
     pub(crate) graph_id: u64,
     pub(crate) prepared_graph: Vec<u8>, // serialized PreparedGraph
-    pub(crate) add_attrs: Vec<(u64, u64, u64)>, // (vertex id, prop id, value hash)
-    pub(crate) rem_attrs: Vec<(u64, u64, u64)>,
+    pub(crate) add_attrs: Vec<AttrEntry>,
+    pub(crate) rem_attrs: Vec<AttrEntry>,
     pub(crate) add_edges: Vec<(u64, u64, u64)>, // (from, label, to)
     pub(crate) rem_edges: Vec<(u64, u64, u64)>,
+    pub(crate) add_edge_attrs: Vec<EdgeAttrEntry>,
+    pub(crate) rem_edge_attrs: Vec<EdgeAttrEntry>,
     pub(crate) deleted_vertices: Vec<u64>, // vertex id
 }
 
@@ -48,6 +62,8 @@ impl PreparedGraph {
         graph: GraphBuilder,
         start_id: u64,
         graph_id: u64,
+        encoding: IntEncoding,
+        text_ids: &HashMap<String, u64>,
         _auto_queries: &[PreparedQuery], // future implementation to add a query check to an item automatically
     ) -> Result<GraphCommitData, LatticeError> {
         let mut global_id_cursor = start_id;
@@ -67,6 +83,8 @@ impl PreparedGraph {
         let mut rem_attrs = vec![];
         let mut add_edges = vec![];
         let mut rem_edges = vec![];
+        let mut add_edge_attrs = vec![];
+        let mut rem_edge_attrs = vec![];
         let mut deleted_vertices = vec![];
 
         let mut proc_vertices = vec![];
@@ -97,7 +115,8 @@ impl PreparedGraph {
                     } else {
                         // vertex was changed
                         let mut new_attrs = mem::take(&mut continued_vertex.attributes);
-                        new_attrs.sort_unstable_by_key(|(attr, val)| (attr.0, val.hash())); // sort by attribute id
+                        new_attrs
+                            .sort_unstable_by_key(|(attr, val)| (attr.0, attr.hash_value(val))); // sort by attribute id
 
                         let global_id = continued_vertex.global_id.unwrap();
 
@@ -110,18 +129,18 @@ impl PreparedGraph {
                                 (None, None) => break,
                                 // no more new attrs, so remaining old attrs were removed
                                 (Some((old_attr, old_val)), None) => {
-                                    rem_attrs.push((global_id, old_attr.0, old_val.hash()));
+                                    rem_attrs.push(Self::attr_entry(global_id, old_attr, old_val));
                                     old_iter.next();
                                 }
                                 // no more old attrs, so remaining new attrs were added
                                 (None, Some((new_attr, new_val))) => {
-                                    add_attrs.push((global_id, new_attr.0, new_val.hash()));
+                                    add_attrs.push(Self::attr_entry(global_id, new_attr, new_val));
                                     new_iter.next();
                                 }
                                 // compare attrs
                                 (Some((old_attr, old_val)), Some((new_attr, new_val))) => {
-                                    let old_key = (old_attr.0, old_val.hash());
-                                    let new_key = (new_attr.0, new_val.hash());
+                                    let old_key = (old_attr.0, old_attr.hash_value(old_val));
+                                    let new_key = (new_attr.0, new_attr.hash_value(new_val));
 
                                     if old_key == new_key {
                                         // old and new attrs are the same, so the attr was unchanged
@@ -129,11 +148,13 @@ impl PreparedGraph {
                                         new_iter.next();
                                     } else if old_key < new_key {
                                         // old attr doesn't have a match (new attr past match value, so no match value exists), so old value was removed
-                                        rem_attrs.push((global_id, old_attr.0, old_val.hash()));
+                                        rem_attrs
+                                            .push(Self::attr_entry(global_id, old_attr, old_val));
                                         old_iter.next();
                                     } else {
                                         // new attr doesn't have a match (old attr past match value, so no match value exists), so new value was added
-                                        add_attrs.push((global_id, new_attr.0, new_val.hash()));
+                                        add_attrs
+                                            .push(Self::attr_entry(global_id, new_attr, new_val));
                                         new_iter.next();
                                     }
                                 }
@@ -150,14 +171,15 @@ impl PreparedGraph {
                     // vertex was deleted
                     deleted_vertices.push(old_vertex.id);
                     for (attr, value) in &old_vertex.attrs {
-                        rem_attrs.push((old_vertex.id, attr.0, value.hash())); // remove all old attributes
+                        rem_attrs.push(Self::attr_entry(old_vertex.id, attr, value)); // remove all old attributes
                     }
                     if let Some(new_vertex) = vertices.get_mut_index(idx) {
                         // new vertex created in freed slot
                         let mut new_attrs = mem::take(&mut new_vertex.attributes);
-                        new_attrs.sort_unstable_by_key(|(attr, val)| (attr.0, val.hash()));
+                        new_attrs
+                            .sort_unstable_by_key(|(attr, val)| (attr.0, attr.hash_value(val)));
                         for (attr, value) in &new_attrs {
-                            add_attrs.push((global_id_cursor, attr.0, value.hash())); // add all new attributes
+                            add_attrs.push(Self::attr_entry(global_id_cursor, attr, value)); // add all new attributes
                         }
                         idx_to_global.insert(idx, global_id_cursor);
                         proc_vertices.push(PreparedVertex {
@@ -176,9 +198,9 @@ impl PreparedGraph {
             global_id_cursor += 1;
 
             let mut new_attrs = mem::take(&mut new_vertex.attributes);
-            new_attrs.sort_unstable_by_key(|(attr, val)| (attr.0, val.hash()));
+            new_attrs.sort_unstable_by_key(|(attr, val)| (attr.0, attr.hash_value(val)));
             for (attr, value) in &new_attrs {
-                add_attrs.push((global_id, attr.0, value.hash())); // add all new attributes
+                add_attrs.push(Self::attr_entry(global_id, attr, value)); // add all new attributes
             }
 
             idx_to_global.insert(h.index, global_id);
@@ -194,7 +216,7 @@ impl PreparedGraph {
             new_edges_start = old_edges.len();
 
             // iterate through the edges that might be continued from the old_graph
-            for (idx, old_edge) in old_edges.iter().enumerate() {
+            for (idx, old_edge) in old_edges.into_iter().enumerate() {
                 if let Some(continued_edge) = edges.get(Handle {
                     generation: 0,
                     index: idx,
@@ -209,30 +231,49 @@ impl PreparedGraph {
                     let current_label = continued_edge.label.0;
 
                     // compare global ids
-                    let unchanged = from == old_edge.from
-                        && current_label == old_edge.label.0
-                        && to == old_edge.to;
+                    let moved = from != old_edge.from
+                        || current_label != old_edge.label.0
+                        || to != old_edge.to;
 
-                    if unchanged {
+                    if !moved && continued_edge.attributes == old_edge.attrs {
                         // edge was unchanged
-                        proc_edges.push(PreparedEdge {
-                            from: old_edge.from,
-                            label: old_edge.label,
-                            to: old_edge.to,
-                        });
+                        proc_edges.push(old_edge);
                     } else {
-                        // edge was changed
-                        rem_edges.push((old_edge.from, old_edge.label.0, old_edge.to)); // remove old edge
-                        add_edges.push((from, current_label, to)); // add new edge
-                        proc_edges.push(PreparedEdge {
+                        let edge = PreparedEdge {
                             from,
                             label: continued_edge.label,
                             to,
-                        });
+                            attrs: continued_edge.attributes.clone(),
+                        };
+                        if moved {
+                            // edge was moved, reindex it with all of its attributes
+                            rem_edges.push((old_edge.from, old_edge.label.0, old_edge.to));
+                            Self::push_edge_attrs(&mut rem_edge_attrs, &old_edge);
+                            add_edges.push((from, current_label, to));
+                            Self::push_edge_attrs(&mut add_edge_attrs, &edge);
+                        } else {
+                            // only the attributes were changed
+                            let mut old_entries = vec![];
+                            let mut new_entries = vec![];
+                            Self::push_edge_attrs(&mut old_entries, &old_edge);
+                            Self::push_edge_attrs(&mut new_entries, &edge);
+                            rem_edge_attrs.extend(
+                                old_entries
+                                    .iter()
+                                    .filter(|entry| !new_entries.contains(entry)),
+                            );
+                            add_edge_attrs.extend(
+                                new_entries
+                                    .iter()
+                                    .filter(|entry| !old_entries.contains(entry)),
+                            );
+                        }
+                        proc_edges.push(edge);
                     }
                 } else {
                     // edge was delted
                     rem_edges.push((old_edge.from, old_edge.label.0, old_edge.to));
+                    Self::push_edge_attrs(&mut rem_edge_attrs, &old_edge);
 
                     if let Some(new_edge) = edges.get_index(idx) {
                         // new edge created in freed slot
@@ -243,11 +284,14 @@ impl PreparedGraph {
                             .get(&new_edge.to.0.index)
                             .expect("Destination vertex missing");
                         add_edges.push((from, new_edge.label.0, to));
-                        proc_edges.push(PreparedEdge {
+                        let edge = PreparedEdge {
                             from,
                             label: new_edge.label,
                             to,
-                        });
+                            attrs: new_edge.attributes.clone(),
+                        };
+                        Self::push_edge_attrs(&mut add_edge_attrs, &edge);
+                        proc_edges.push(edge);
                     }
                 }
             }
@@ -262,22 +306,24 @@ impl PreparedGraph {
                 .get(&new_edge.to.0.index)
                 .expect("Destination vertex missing");
             add_edges.push((from, new_edge.label.0, to));
-            proc_edges.push(PreparedEdge {
+            let edge = PreparedEdge {
                 from,
                 label: new_edge.label,
                 to,
-            });
+                attrs: new_edge.attributes.clone(),
+            };
+            Self::push_edge_attrs(&mut add_edge_attrs, &edge);
+            proc_edges.push(edge);
         }
 
         // return computed changes
-        let prepared_graph = bincode::encode_to_vec(
-            PreparedGraph {
-                id: graph_id,
-                vertices: proc_vertices,
-                edges: proc_edges,
-            },
-            config::standard(),
-        )?;
+        let mut graph = PreparedGraph {
+            id: graph_id,
+            vertices: proc_vertices,
+            edges: proc_edges,
+        };
+        graph.intern_text(text_ids);
+        let prepared_graph = codec::encode_to_vec(graph, encoding)?;
         Ok(GraphCommitData {
             graph_id,
             prepared_graph,
@@ -285,7 +331,158 @@ impl PreparedGraph {
             rem_attrs,
             add_edges,
             rem_edges,
+            add_edge_attrs,
+            rem_edge_attrs,
             deleted_vertices,
         })
     }
+
+    // decodes a stored graph, restoring its interned text from the dictionary
+    pub(crate) fn decode(
+        bytes: &[u8],
+        encoding: IntEncoding,
+        dictionary: &impl ReadableTable<u64, &'static str>,
+    ) -> Result<Self, LatticeError> {
+        let mut graph: PreparedGraph = codec::decode_from_slice(bytes, encoding)?;
+        graph.resolve_text(dictionary)?;
+        Ok(graph)
+    }
+
+    // encodes a decoded graph for storage, interning text already in the dictionary
+    pub(crate) fn encode(
+        mut self,
+        encoding: IntEncoding,
+        text_table: &impl ReadableTable<&'static str, u64>,
+    ) -> Result<Vec<u8>, LatticeError> {
+        let mut text_ids = HashMap::new();
+        for vertex in &self.vertices {
+            for (attr, value) in &vertex.attrs {
+                if let Primitive::Text(text) = value
+                    && attr.options().intern_text
+                    && let Some(id) = text_table.get(text.as_str())?
+                {
+                    text_ids.insert(text.clone(), id.value());
+                }
+            }
+        }
+        self.intern_text(&text_ids);
+        Ok(codec::encode_to_vec(self, encoding)?)
+    }
+
+    // replaces the text values of interned properties with their dictionary ids
+    fn intern_text(&mut self, text_ids: &HashMap<String, u64>) {
+        for vertex in &mut self.vertices {
+            for (attr, value) in &mut vertex.attrs {
+                if let Primitive::Text(text) = value
+                    && attr.options().intern_text
+                    && let Some(id) = text_ids.get(text)
+                {
+                    attr.0 |= INTERNED_VALUE;
+                    *value = Primitive::UInt(*id);
+                }
+            }
+        }
+    }
+
+    // restores interned text values, the inverse of intern_text
+    pub(crate) fn resolve_text(
+        &mut self,
+        dictionary: &impl ReadableTable<u64, &'static str>,
+    ) -> Result<(), LatticeError> {
+        for vertex in &mut self.vertices {
+            for (attr, value) in &mut vertex.attrs {
+                if attr.0 & INTERNED_VALUE == 0 {
+                    continue;
+                }
+                let Primitive::UInt(id) = value else {
+                    return Err(LatticeError::MissingValue);
+                };
+                let text = dictionary
+                    .get(*id)?
+                    .ok_or(LatticeError::MissingValue)?
+                    .value()
+                    .to_string();
+                attr.0 &= !INTERNED_VALUE;
+                *value = Primitive::Text(text);
+            }
+        }
+        Ok(())
+    }
+
+    // commit data that removes every index entry of the graph
+    pub(crate) fn removal_commit_data(&self, inline: bool) -> GraphCommitData {
+        let mut rem_edges = vec![];
+        let mut rem_edge_attrs = vec![];
+        for edge in &self.edges {
+            rem_edges.push((edge.from, edge.label.0, edge.to));
+            Self::push_edge_attrs(&mut rem_edge_attrs, edge);
+        }
+        GraphCommitData {
+            graph_id: self.id,
+            prepared_graph: vec![],
+            add_attrs: vec![],
+            rem_attrs: if inline { vec![] } else { self.attr_entries() }, // inline graphs are not indexed
+            add_edges: vec![],
+            rem_edges,
+            add_edge_attrs: vec![],
+            rem_edge_attrs,
+            deleted_vertices: self.vertices.iter().map(|v| v.id).collect(),
+        }
+    }
+
+    // lists the scalar index entries of every vertex attribute
+    pub(crate) fn attr_entries(&self) -> Vec<AttrEntry> {
+        let mut entries = vec![];
+        for vertex in &self.vertices {
+            for (attr, value) in &vertex.attrs {
+                entries.push(Self::attr_entry(vertex.id, attr, value));
+            }
+        }
+        entries
+    }
+
+    // the properties of every vertex attribute, without repeats, stored or decoded
+    pub(crate) fn properties(&self) -> BTreeSet<u64> {
+        self.vertices
+            .iter()
+            .flat_map(|vertex| {
+                vertex
+                    .attrs
+                    .iter()
+                    .map(|(attr, _)| attr.0 & !INTERNED_VALUE)
+            })
+            .collect()
+    }
+
+    // helper fn to build the index entry of a vertex attribute, text is also indexed in order
+    fn attr_entry(vertex: u64, attr: &PropertyHandle, value: &Primitive) -> AttrEntry {
+        (
+            vertex,
+            attr.0,
+            attr.hash_value(value),
+            attr.index_text(value),
+        )
+    }
+
+    // the index entries of every edge attribute
+    pub(crate) fn edge_attr_entries(&self) -> Vec<EdgeAttrEntry> {
+        let mut entries = vec![];
+        for edge in &self.edges {
+            Self::push_edge_attrs(&mut entries, edge);
+        }
+        entries
+    }
+
+    // helper fn to list the index entries of an edge's attributes
+    fn push_edge_attrs(list: &mut Vec<EdgeAttrEntry>, edge: &PreparedEdge) {
+        for (attr, value) in &edge.attrs {
+            list.push((
+                edge.label.0,
+                attr.0,
+                attr.hash_value(value),
+                edge.from,
+                edge.to,
+            ));
+        }
+    }
 }
@@ -1,4 +1,6 @@
 pub mod db;
+pub mod merge;
+mod migrate;
 pub mod reader;
 pub(crate) mod tables;
 pub mod writer;
@@ -1,79 +1,314 @@
-use std::path::Path;
+use std::{
+    path::Path,
+    sync::{Arc, Condvar, Mutex},
+};
 
-use redb::{Database, ReadableDatabase};
+use redb::{Database, ReadableDatabase, ReadableTable, ReadableTableMetadata, WriteTransaction};
 use tempfile::NamedTempFile;
 
 use crate::{
+    codec::IntEncoding,
     errors::LatticeError,
     lattice_db::{
+        migrate,
         reader::LatticeReader,
         tables::{
-            GRAPHS, INDEX_FORWARD, INDEX_REVERSE, INDEX_SCALAR, PROP_NAMES, PROPERTIES, QUERIES,
-            QUERY_METAS, QUERY_NAMES, SEQUENCES, VERTEX_GRAPH_MAP,
+            GRAPH_METAS, GRAPHS, INDEX_EDGE_ATTR_FWD, INDEX_EDGE_ATTR_REV, INDEX_FORWARD,
+            INDEX_REVERSE, INDEX_SCALAR, INDEX_TEXT, INLINE_GRAPHS, INLINE_PROPS, INTERNED_IDS,
+            INTERNED_TEXT, META_FORMAT_VERSION, META_INT_ENCODING, META_RUN_OPTIMIZE, METADATA,
+            PROP_IDS, PROP_NAMES, PROPERTIES, QUERIES, QUERY_METAS, QUERY_NAMES, SEQUENCES,
+            VERTEX_GRAPH_MAP,
         },
         writer::LatticeWriter,
     },
 };
 
+/// Settings chosen when a database is first created.
+/// * Stored inside the database, so reopening always uses the original settings.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DbOptions {
+    pub int_encoding: IntEncoding,
+    /// Optimize index bitmaps into run containers before writing them.
+    /// * Shrinks indexes over contiguous vertex ids, at a CPU cost on commit.
+    pub run_optimize: bool,
+}
+
+/// Storage format written by this version of the crate.
+/// * Databases with an older format are upgraded when opened.
+/// * Databases with a newer format can not be read.
+pub const FORMAT_VERSION: u32 = 2;
+
+// format of databases created before the version was stored
+const LEGACY_FORMAT_VERSION: u64 = 1;
+
 pub struct LatticeDb {
     db: Database,
+    encoding: IntEncoding,
+    run_optimize: bool,
+    format_version: u64,
+    write_lock: Arc<WriteLock>,
+}
+
+// tracks whether a writer is open, so callers can choose not to wait
+#[derive(Default)]
+pub(crate) struct WriteLock {
+    busy: Mutex<bool>,
+    released: Condvar,
+}
+
+// held by a writer, frees the write lock when dropped
+pub(crate) struct WriteGuard(Arc<WriteLock>);
+
+impl WriteLock {
+    fn acquire(self: &Arc<Self>) -> WriteGuard {
+        let mut busy = self.busy.lock().unwrap();
+        while *busy {
+            busy = self.released.wait(busy).unwrap();
+        }
+        *busy = true;
+        WriteGuard(self.clone())
+    }
+
+    fn try_acquire(self: &Arc<Self>) -> Option<WriteGuard> {
+        let mut busy = self.busy.lock().unwrap();
+        if *busy {
+            return None;
+        }
+        *busy = true;
+        Some(WriteGuard(self.clone()))
+    }
+}
+
+impl Drop for WriteGuard {
+    fn drop(&mut self) {
+        *self.0.busy.lock().unwrap() = false;
+        self.0.released.notify_one();
+    }
+}
+
+// helper fn to tell databases written before any metadata was stored apart from new ones
+fn holds_data(wt: &WriteTransaction) -> Result<bool, LatticeError> {
+    Ok(!wt.open_table(GRAPHS)?.is_empty()?
+        || !wt.open_table(PROPERTIES)?.is_empty()?
+        || !wt.open_table(QUERIES)?.is_empty()?
+        || !wt.open_table(SEQUENCES)?.is_empty()?)
 }
 
 impl LatticeDb {
     /// Creates or opens the specified file as a database.
     /// * Creates the file if it does not exist.
     /// * Returns an error if the existing file is an invalid db format.
-    pub fn create(path: impl AsRef<Path>) -> Result<Self, redb::Error> {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, LatticeError> {
+        Self::create_with_options(path, DbOptions::default())
+    }
+
+    /// Creates or opens the specified file as a database with the given options.
+    /// * Options are ignored if the database already exists.
+    /// * Returns an error if the existing database has a newer format version.
+    pub fn create_with_options(
+        path: impl AsRef<Path>,
+        options: DbOptions,
+    ) -> Result<Self, LatticeError> {
         let p = path.as_ref();
-        let db = Database::create(p)?;
-        let mut me = Self { db };
+        let db = Database::create(p).map_err(redb::Error::from)?;
+        let mut me = Self {
+            db,
+            encoding: options.int_encoding,
+            run_optimize: options.run_optimize,
+            format_version: FORMAT_VERSION.into(),
+            write_lock: Arc::default(),
+        };
         me.init_tables()?;
+        me.init_format_version()?;
+        me.init_metadata()?;
+        me.upgrade()?;
         Ok(me)
     }
 
     /// Opens the specified existing database.
-    pub fn open(path: impl AsRef<Path>) -> Result<Self, redb::Error> {
+    /// * Creates any tables missing from databases written by older versions.
+    /// * Upgrades databases with an older format version.
+    /// * Returns an error if the database has a newer format version.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, LatticeError> {
         let p = path.as_ref();
-        let db = Database::open(p)?;
-        Ok(Self { db })
+        let db = Database::open(p).map_err(redb::Error::from)?;
+        let mut me = Self {
+            db,
+            encoding: IntEncoding::default(),
+            run_optimize: false,
+            format_version: FORMAT_VERSION.into(),
+            write_lock: Arc::default(),
+        };
+        me.init_tables()?;
+        me.init_format_version()?;
+        me.load_metadata()?;
+        me.upgrade()?;
+        Ok(me)
     }
 
     /// Creates a temporary volatile database.
-    pub fn create_temporary() -> Result<(LatticeDb, NamedTempFile), redb::Error> {
+    pub fn create_temporary() -> Result<(LatticeDb, NamedTempFile), LatticeError> {
         let file = NamedTempFile::new()?;
         let db = LatticeDb::create(file.path())?;
         Ok((db, file))
     }
 
-    // helper fn to initialize tables on startup
+    // helper fn to initialize tables on startup, existing tables are left untouched
     fn init_tables(&mut self) -> Result<(), redb::Error> {
         let wt = self.db.begin_write()?;
         {
             let _ = wt.open_table(INDEX_SCALAR)?;
+            let _ = wt.open_table(INDEX_TEXT)?;
             let _ = wt.open_table(INDEX_FORWARD)?;
             let _ = wt.open_table(INDEX_REVERSE)?;
+            let _ = wt.open_table(INDEX_EDGE_ATTR_FWD)?;
+            let _ = wt.open_table(INDEX_EDGE_ATTR_REV)?;
             let _ = wt.open_table(SEQUENCES)?;
             let _ = wt.open_table(GRAPHS)?;
+            let _ = wt.open_table(GRAPH_METAS)?;
+            let _ = wt.open_table(INLINE_GRAPHS)?;
+            let _ = wt.open_table(INLINE_PROPS)?;
             let _ = wt.open_table(VERTEX_GRAPH_MAP)?;
+            let _ = wt.open_table(INTERNED_TEXT)?;
+            let _ = wt.open_table(INTERNED_IDS)?;
             let _ = wt.open_table(PROPERTIES)?;
             let _ = wt.open_table(PROP_NAMES)?;
+            let _ = wt.open_table(PROP_IDS)?;
             let _ = wt.open_table(QUERIES)?;
             let _ = wt.open_table(QUERY_NAMES)?;
             let _ = wt.open_table(QUERY_METAS)?;
+            let _ = wt.open_table(METADATA)?;
+        }
+        wt.commit()?;
+        Ok(())
+    }
+
+    // helper fn to load the stored format version, new databases are stamped with the current one
+    fn init_format_version(&mut self) -> Result<(), LatticeError> {
+        let wt = self.db.begin_write()?;
+        {
+            let mut table = wt.open_table(METADATA)?;
+            let stored = table.get(META_FORMAT_VERSION)?.map(|v| v.value());
+            self.format_version = match stored {
+                Some(version) => version,
+                // only databases written before the version was stored hold data without one
+                None if holds_data(&wt)? => LEGACY_FORMAT_VERSION,
+                None => {
+                    let current = u64::from(FORMAT_VERSION);
+                    table.insert(META_FORMAT_VERSION, current)?;
+                    current
+                }
+            };
         }
         wt.commit()?;
         Ok(())
     }
 
+    // helper fn to store options on first creation, or load the stored ones
+    fn init_metadata(&mut self) -> Result<(), LatticeError> {
+        let wt = self.db.begin_write()?;
+        {
+            let mut table = wt.open_table(METADATA)?;
+            let stored = table.get(META_INT_ENCODING)?.map(|v| v.value());
+            if let Some(encoding) = stored {
+                self.encoding = IntEncoding::from_u64(encoding)?;
+            } else {
+                // legacy data was written before the encoding was selectable
+                if self.format_version == LEGACY_FORMAT_VERSION {
+                    self.encoding = IntEncoding::Variable;
+                }
+                table.insert(META_INT_ENCODING, self.encoding.to_u64())?;
+            }
+            let stored = table.get(META_RUN_OPTIMIZE)?.map(|v| v.value());
+            if let Some(run_optimize) = stored {
+                self.run_optimize = run_optimize != 0;
+            } else {
+                table.insert(META_RUN_OPTIMIZE, self.run_optimize as u64)?;
+            }
+        }
+        wt.commit()?;
+        Ok(())
+    }
+
+    // helper fn to load stored options, databases without stored options use the defaults
+    fn load_metadata(&mut self) -> Result<(), LatticeError> {
+        let rt = self.db.begin_read()?;
+        let table = rt.open_table(METADATA)?;
+        if let Some(encoding) = table.get(META_INT_ENCODING)? {
+            self.encoding = IntEncoding::from_u64(encoding.value())?;
+        }
+        if let Some(run_optimize) = table.get(META_RUN_OPTIMIZE)? {
+            self.run_optimize = run_optimize.value() != 0;
+        }
+        Ok(())
+    }
+
+    // helper fn to upgrade older formats in place, newer formats are refused
+    fn upgrade(&mut self) -> Result<(), LatticeError> {
+        let current = u64::from(FORMAT_VERSION);
+        if self.format_version > current {
+            return Err(LatticeError::UnsupportedVersion(self.format_version));
+        }
+        if self.format_version == current {
+            return Ok(());
+        }
+        let wt = self.db.begin_write()?;
+        migrate::upgrade(&wt, self.encoding, self.format_version)?;
+        wt.open_table(METADATA)?
+            .insert(META_FORMAT_VERSION, current)?;
+        wt.commit()?;
+        self.format_version = current;
+        Ok(())
+    }
+
+    /// Returns the storage format version of the database.
+    /// * Older databases are upgraded when opened, so this is always `FORMAT_VERSION`.
+    pub fn format_version(&self) -> Result<u32, LatticeError> {
+        u32::try_from(self.format_version)
+            .map_err(|_| LatticeError::UnsupportedVersion(self.format_version))
+    }
+
+    /// Returns true if this version of the crate can read the database.
+    pub fn is_compatible(&self) -> bool {
+        self.format_version()
+            .is_ok_and(|version| version <= FORMAT_VERSION)
+    }
+
+    /// Returns the integer encoding used for stored data.
+    pub fn int_encoding(&self) -> IntEncoding {
+        self.encoding
+    }
+
     /// Begins a write transaction.
+    /// * Only one writer can be open at a time, blocks until any open writer is dropped.
     pub fn begin_write(&self) -> Result<LatticeWriter, LatticeError> {
+        let guard = self.write_lock.acquire();
         let wt = self.db.begin_write()?;
-        LatticeWriter::new(wt)
+        LatticeWriter::new(wt, self.encoding, self.run_optimize, guard)
+    }
+
+    /// Begins a write transaction without waiting.
+    /// * Returns an error if another writer is open.
+    pub fn try_begin_write(&self) -> Result<LatticeWriter, LatticeError> {
+        let guard = self
+            .write_lock
+            .try_acquire()
+            .ok_or(LatticeError::WriterBusy)?;
+        let wt = self.db.begin_write()?;
+        LatticeWriter::new(wt, self.encoding, self.run_optimize, guard)
+    }
+
+    /// Commits everything the writer saved so far, then continues writing in a new transaction.
+    /// * Index caches and id sequences are committed together, so ids are not reused after a crash.
+    /// * The writer keeps its settings, and other writers stay blocked until it is dropped.
+    /// * The metrics hook receives the metrics gathered since the last report, then they are reset.
+    pub fn checkpoint(&self, writer: LatticeWriter) -> Result<LatticeWriter, LatticeError> {
+        writer.checkpoint(|| Ok(self.db.begin_write()?))
     }
 
     /// Begins a read transaction.
-    pub fn begin_read(&self) -> Result<LatticeReader, redb::Error> {
+    pub fn begin_read(&self) -> Result<LatticeReader, LatticeError> {
         let rt = self.db.begin_read()?;
-        Ok(LatticeReader::new(rt))
+        Ok(LatticeReader::new(rt, self.encoding))
     }
 }
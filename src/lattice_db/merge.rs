@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet};
+
+use redb::ReadableTable;
+
+use crate::{
+    LatticeDb, LatticeReader, LatticeWriter, codec,
+    errors::LatticeError,
+    graph::{graph_builder::GraphBuilder, graph_prepared::PreparedGraph},
+    lattice_db::{
+        tables::{
+            GRAPH_METAS, GRAPHS, INTERNED_IDS, PROP_IDS, PROP_NAMES, PROPERTIES, QUERIES,
+            QUERY_METAS, QUERY_NAMES,
+        },
+        writer::take_ids,
+    },
+    properties::{PropertyHandle, PropertyOptions, PropertyRow, read_property_row},
+    query::query_prepared::PreparedQuery,
+};
+
+// number of imported graphs held in memory before saving
+const MERGE_BATCH: usize = 1024;
+
+/// Maps ids from a merged database to their new ids.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IdRemap {
+    pub graphs: HashMap<u64, u64>,
+    pub vertices: HashMap<u64, u64>,
+    pub properties: HashMap<PropertyHandle, PropertyHandle>,
+    pub queries: HashMap<u64, u64>,
+}
+
+impl IdRemap {
+    // properties missing from the map keep their id
+    fn property(&self, handle: PropertyHandle) -> PropertyHandle {
+        *self.properties.get(&handle).unwrap_or(&handle)
+    }
+}
+
+impl LatticeDb {
+    /// Import every graph, property, and query from another database.
+    /// * Properties with an alias already in this database are merged into the existing property.
+    /// * Merged properties must have the same options, otherwise returns an error.
+    /// * Imported graphs, vertices, and queries get new ids, returned in the remap.
+    /// * Both databases must use the same integer encoding.
+    pub fn merge_from(&mut self, other: &LatticeDb) -> Result<IdRemap, LatticeError> {
+        if self.int_encoding() != other.int_encoding() {
+            return Err(LatticeError::EncodingMismatch);
+        }
+        let rd = other.begin_read()?;
+        let mut wr = self.begin_write()?;
+        let mut remap = IdRemap::default();
+
+        Self::merge_properties(&rd, &mut wr, &mut remap)?;
+        Self::merge_graphs(&rd, &mut wr, &mut remap)?;
+        Self::merge_queries(&rd, &mut wr, &mut remap)?;
+
+        wr.commit()?;
+        Ok(remap)
+    }
+
+    // helper fn to copy property metadata and aliases
+    fn merge_properties(
+        rd: &LatticeReader,
+        wr: &mut LatticeWriter,
+        remap: &mut IdRemap,
+    ) -> Result<(), LatticeError> {
+        let mut names: HashMap<u64, Vec<String>> = HashMap::new();
+        for entry in rd.rt.open_table(PROP_NAMES)?.iter()? {
+            let (name, id) = entry?;
+            names
+                .entry(id.value())
+                .or_default()
+                .push(name.value().to_string());
+        }
+
+        let src = rd.rt.open_table(PROPERTIES)?;
+        let mut dst = wr.wt.open_table(PROPERTIES)?;
+        let mut dst_names = wr.wt.open_table(PROP_NAMES)?;
+        let mut dst_ids = wr.wt.open_table(PROP_IDS)?;
+        for entry in src.iter()? {
+            let (id, bytes) = entry?;
+            let id = id.value();
+            let row: PropertyRow = codec::decode_from_slice(&bytes.value(), rd.encoding)?;
+            let handle = PropertyHandle(id, PropertyOptions::from_bits(row.options));
+            let aliases = names.remove(&id).unwrap_or_default();
+
+            // same alias, same property
+            let mut existing = None;
+            for name in &aliases {
+                if let Some(found) = dst_names.get(name.as_str())? {
+                    existing = Some((found.value(), name));
+                    break;
+                }
+            }
+            if let Some((existing, name)) = existing {
+                let existing_row = read_property_row(&dst, existing, wr.encoding)?
+                    .ok_or(LatticeError::PropertyNotFound)?;
+                if existing_row.options != row.options {
+                    return Err(LatticeError::PropertyOptionsMismatch(name.clone()));
+                }
+                // keep the aliases the existing property is missing
+                for name in &aliases {
+                    if dst_names.get(name.as_str())?.is_none() {
+                        dst_names.insert(name.as_str(), existing)?;
+                        dst_ids.insert((existing, name.as_str()), ())?;
+                    }
+                }
+                remap
+                    .properties
+                    .insert(handle, PropertyHandle(existing, handle.1));
+                continue;
+            }
+
+            // keep the option flags, take a new id
+            let new_id = take_ids(&mut wr.property_id_cursor, 1)?;
+            dst.insert(new_id, bytes.value())?;
+            for name in &aliases {
+                dst_names.insert(name.as_str(), new_id)?;
+                dst_ids.insert((new_id, name.as_str()), ())?;
+            }
+            remap
+                .properties
+                .insert(handle, PropertyHandle(new_id, handle.1));
+        }
+        Ok(())
+    }
+
+    // helper fn to save every graph as a new graph, rebuilding the indexes
+    fn merge_graphs(
+        rd: &LatticeReader,
+        wr: &mut LatticeWriter,
+        remap: &mut IdRemap,
+    ) -> Result<(), LatticeError> {
+        let mut batch = vec![];
+        let inline: HashSet<u64> = rd.inline_graph_ids()?.into_iter().collect();
+
+        let table = rd.rt.open_table(GRAPHS)?;
+        let dictionary = rd.rt.open_table(INTERNED_IDS)?;
+        for entry in table.iter()? {
+            let (graph_id, bytes) = entry?;
+            let graph = PreparedGraph::decode(&bytes.value(), rd.encoding, &dictionary)?;
+
+            let mut vertex_idx = HashMap::new();
+            let mut vertices = Vec::with_capacity(graph.vertices.len());
+            for (idx, vertex) in graph.vertices.iter().enumerate() {
+                vertex_idx.insert(vertex.id, idx);
+                vertices.push(
+                    vertex
+                        .attrs
+                        .iter()
+                        .map(|(attr, value)| (remap.property(*attr), value.clone()))
+                        .collect(),
+                );
+            }
+            let mut edges = Vec::with_capacity(graph.edges.len());
+            for edge in &graph.edges {
+                let from = *vertex_idx
+                    .get(&edge.from)
+                    .ok_or(LatticeError::DanglingEdge)?;
+                let to = *vertex_idx.get(&edge.to).ok_or(LatticeError::DanglingEdge)?;
+                edges.push((from, remap.property(edge.label), to));
+            }
+
+            let mut builder = GraphBuilder::from_parts(vertices, edges)?;
+            for (idx, edge) in graph.edges.iter().enumerate() {
+                builder.edges.get_mut_index(idx).unwrap().attributes = edge
+                    .attrs
+                    .iter()
+                    .map(|(attr, value)| (remap.property(*attr), value.clone()))
+                    .collect();
+            }
+
+            builder.inline_attributes = inline.contains(&graph_id.value());
+            let old_vertices = graph.vertices.iter().map(|v| v.id).collect();
+            batch.push((graph_id.value(), old_vertices, builder));
+            if batch.len() >= MERGE_BATCH {
+                Self::save_batch(wr, std::mem::take(&mut batch), remap)?;
+            }
+        }
+        Self::save_batch(wr, batch, remap)?;
+
+        // graph metadata follows the graphs to their new ids
+        let mut dst_metas = wr.wt.open_table(GRAPH_METAS)?;
+        for entry in rd.rt.open_table(GRAPH_METAS)?.iter()? {
+            let (id, meta) = entry?;
+            if let Some(new_id) = remap.graphs.get(&id.value()) {
+                dst_metas.insert(*new_id, meta.value())?;
+            }
+        }
+        Ok(())
+    }
+
+    // helper fn to save imported graphs, remapping to the ids they were given
+    fn save_batch(
+        wr: &mut LatticeWriter,
+        batch: Vec<(u64, Vec<u64>, GraphBuilder)>,
+        remap: &mut IdRemap,
+    ) -> Result<(), LatticeError> {
+        let mut old_ids = Vec::with_capacity(batch.len());
+        let mut builders = Vec::with_capacity(batch.len());
+        for (graph_id, vertices, builder) in batch {
+            old_ids.push((graph_id, vertices));
+            builders.push(builder);
+        }
+        let new_graph_ids = wr.save_graphs(builders)?;
+
+        // saved vertices keep the order they were built in
+        let table = wr.wt.open_table(GRAPHS)?;
+        let dictionary = wr.wt.open_table(INTERNED_IDS)?;
+        for ((graph_id, vertices), new_graph_id) in old_ids.into_iter().zip(new_graph_ids) {
+            let bytes = table
+                .get(new_graph_id)?
+                .ok_or(LatticeError::GraphNotFound)?
+                .value();
+            let saved = PreparedGraph::decode(&bytes, wr.encoding, &dictionary)?;
+            for (old_id, vertex) in vertices.into_iter().zip(&saved.vertices) {
+                remap.vertices.insert(old_id, vertex.id);
+            }
+            remap.graphs.insert(graph_id, new_graph_id);
+        }
+        Ok(())
+    }
+
+    // helper fn to copy saved queries, pointing them at the new ids
+    fn merge_queries(
+        rd: &LatticeReader,
+        wr: &mut LatticeWriter,
+        remap: &mut IdRemap,
+    ) -> Result<(), LatticeError> {
+        let src = rd.rt.open_table(QUERIES)?;
+
+        // saved queries can reference each other, so assign every id first
+        for entry in src.iter()? {
+            let (id, _) = entry?;
+            let new_id = take_ids(&mut wr.query_id_cursor, 1)?;
+            remap.queries.insert(id.value(), new_id);
+        }
+
+        let mut dst = wr.wt.open_table(QUERIES)?;
+        for entry in src.iter()? {
+            let (id, bytes) = entry?;
+            let mut query: PreparedQuery = codec::decode_from_slice(&bytes.value(), rd.encoding)?;
+            query.remap_ids(&remap.properties, &remap.queries, &remap.graphs);
+            dst.insert(
+                remap.queries[&id.value()],
+                codec::encode_to_vec(query, wr.encoding)?,
+            )?;
+        }
+
+        let mut dst_metas = wr.wt.open_table(QUERY_METAS)?;
+        for entry in rd.rt.open_table(QUERY_METAS)?.iter()? {
+            let (id, meta) = entry?;
+            if let Some(new_id) = remap.queries.get(&id.value()) {
+                dst_metas.insert(*new_id, meta.value())?;
+            }
+        }
+
+        let mut dst_names = wr.wt.open_table(QUERY_NAMES)?;
+        for entry in rd.rt.open_table(QUERY_NAMES)?.iter()? {
+            let (name, id) = entry?;
+            if dst_names.get(name.value())?.is_some() {
+                return Err(LatticeError::AliasAlreadyExists);
+            }
+            if let Some(new_id) = remap.queries.get(&id.value()) {
+                dst_names.insert(name.value(), *new_id)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use bincode::{Decode, Encode};
+use redb::{ReadableTable, WriteTransaction};
+use roaring::RoaringTreemap;
+
+use crate::{
+    codec::{self, IntEncoding},
+    errors::LatticeError,
+    graph::graph_prepared::{PreparedEdge, PreparedGraph, PreparedVertex},
+    lattice_db::{
+        tables::{GRAPHS, INDEX_TEXT, INLINE_GRAPHS, PROP_IDS, PROP_NAMES, PROPERTIES, QUERIES},
+        writer::LatticeWriter,
+    },
+    properties::{PropertyHandle, PropertyRow},
+    query::{query_builder::EdgeDirection, query_prepared::Node},
+    values::Primitive,
+};
+
+// version 1 graphs, property ids had no options and edges had no attributes
+#[derive(Decode)]
+struct LegacyVertex {
+    id: u64,
+    attrs: Vec<(u64, Primitive)>,
+}
+
+#[derive(Decode)]
+struct LegacyEdge {
+    from: u64,
+    label: u64,
+    to: u64,
+}
+
+#[derive(Decode)]
+struct LegacyGraph {
+    id: u64,
+    vertices: Vec<LegacyVertex>,
+    edges: Vec<LegacyEdge>,
+}
+
+// version 1 query nodes, only the variants that existed then
+#[derive(Decode)]
+enum LegacyNode {
+    Union(Vec<usize>),
+    Intersect(Vec<usize>),
+    Difference(usize, usize),
+    Attribute {
+        attr: u64,
+        value: u64,
+    },
+    Edge {
+        dir: EdgeDirection,
+        label: u64,
+        target: usize,
+    },
+    SavedQuery(u64),
+}
+
+#[derive(Decode)]
+struct LegacyQuery {
+    nodes: Vec<LegacyNode>,
+    root: usize,
+}
+
+// the stored form of a PreparedQuery
+#[derive(Encode)]
+struct StoredQuery {
+    nodes: Vec<Node>,
+    root: usize,
+}
+
+impl From<LegacyNode> for Node {
+    fn from(node: LegacyNode) -> Self {
+        match node {
+            LegacyNode::Union(children) => Node::Union(children),
+            LegacyNode::Intersect(children) => Node::Intersect(children),
+            LegacyNode::Difference(a, b) => Node::Difference(a, b),
+            LegacyNode::Attribute { attr, value } => Node::Attribute {
+                attr: PropertyHandle::plain(attr),
+                value,
+            },
+            LegacyNode::Edge { dir, label, target } => Node::Edge {
+                dir,
+                label: PropertyHandle::plain(label),
+                target,
+            },
+            LegacyNode::SavedQuery(id) => Node::SavedQuery(id),
+        }
+    }
+}
+
+// upgrades the stored data from an older format version, in the given transaction
+pub(crate) fn upgrade(
+    wt: &WriteTransaction,
+    encoding: IntEncoding,
+    from_version: u64,
+) -> Result<(), LatticeError> {
+    if from_version < 2 {
+        upgrade_graphs(wt, encoding)?;
+        upgrade_queries(wt, encoding)?;
+        upgrade_properties(wt, encoding)?;
+    }
+    Ok(())
+}
+
+// helper fn to re-encode graphs with property options and edge attributes, and index their text in order
+fn upgrade_graphs(wt: &WriteTransaction, encoding: IntEncoding) -> Result<(), LatticeError> {
+    let inline = wt.open_table(INLINE_GRAPHS)?;
+    let mut table = wt.open_table(GRAPHS)?;
+    let mut upgraded = vec![];
+    let mut text_index: HashMap<(u64, String), RoaringTreemap> = HashMap::new();
+    for entry in table.iter()? {
+        let (id, bytes) = entry?;
+        let legacy: LegacyGraph = codec::decode_from_slice(&bytes.value(), encoding)?;
+        let graph = PreparedGraph {
+            id: legacy.id,
+            vertices: legacy
+                .vertices
+                .into_iter()
+                .map(|vertex| PreparedVertex {
+                    id: vertex.id,
+                    attrs: vertex
+                        .attrs
+                        .into_iter()
+                        .map(|(attr, value)| (PropertyHandle::plain(attr), value))
+                        .collect(),
+                })
+                .collect(),
+            edges: legacy
+                .edges
+                .into_iter()
+                .map(|edge| PreparedEdge {
+                    from: edge.from,
+                    label: PropertyHandle::plain(edge.label),
+                    to: edge.to,
+                    attrs: vec![],
+                })
+                .collect(),
+        };
+        if inline.get(id.value())?.is_none() {
+            for (vertex, property, _, text) in graph.attr_entries() {
+                if let Some(text) = text {
+                    text_index
+                        .entry((property, text))
+                        .or_default()
+                        .insert(vertex);
+                }
+            }
+        }
+        upgraded.push((id.value(), codec::encode_to_vec(graph, encoding)?));
+    }
+    for (id, bytes) in upgraded {
+        table.insert(id, bytes)?;
+    }
+    LatticeWriter::commit_cache(wt, text_index, INDEX_TEXT, false)?;
+    Ok(())
+}
+
+// helper fn to re-encode saved queries with property options
+fn upgrade_queries(wt: &WriteTransaction, encoding: IntEncoding) -> Result<(), LatticeError> {
+    let mut table = wt.open_table(QUERIES)?;
+    let mut upgraded = vec![];
+    for entry in table.iter()? {
+        let (id, bytes) = entry?;
+        let legacy: LegacyQuery = codec::decode_from_slice(&bytes.value(), encoding)?;
+        let query = StoredQuery {
+            nodes: legacy.nodes.into_iter().map(Node::from).collect(),
+            root: legacy.root,
+        };
+        upgraded.push((id.value(), codec::encode_to_vec(query, encoding)?));
+    }
+    for (id, bytes) in upgraded {
+        table.insert(id, bytes)?;
+    }
+    Ok(())
+}
+
+// helper fn to wrap property metadata into rows, and index aliases by id
+fn upgrade_properties(wt: &WriteTransaction, encoding: IntEncoding) -> Result<(), LatticeError> {
+    let mut table = wt.open_table(PROPERTIES)?;
+    let mut upgraded = vec![];
+    for entry in table.iter()? {
+        let (id, bytes) = entry?;
+        let row = PropertyRow {
+            options: 0,
+            meta: bytes.value(),
+        };
+        upgraded.push((id.value(), codec::encode_to_vec(&row, encoding)?));
+    }
+    for (id, bytes) in upgraded {
+        table.insert(id, bytes)?;
+    }
+
+    let names = wt.open_table(PROP_NAMES)?;
+    let mut ids = wt.open_table(PROP_IDS)?;
+    for entry in names.iter()? {
+        let (name, id) = entry?;
+        ids.insert((id.value(), name.value()), ())?;
+    }
+    Ok(())
+}
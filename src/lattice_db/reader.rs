@@ -1,25 +1,184 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet, hash_map::Entry},
+    mem,
+    ops::{Bound, RangeBounds},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Instant,
+};
 
-use bincode::config;
-use redb::ReadTransaction;
+use bincode::{Decode, error::DecodeError};
+use redb::{ReadOnlyTable, ReadTransaction, ReadableTable, TableError};
 use roaring::RoaringTreemap;
 
 use crate::{
     PreparedQuery,
+    codec::{self, IntEncoding},
     errors::LatticeError,
-    graph::graph_builder::GraphBuilder,
-    lattice_db::tables::{GRAPHS, INDEX_FORWARD, INDEX_REVERSE, INDEX_SCALAR, VERTEX_GRAPH_MAP},
-    properties::QUERY_MATCH,
-    query::{query_builder::EdgeDirection, query_prepared::Node},
+    graph::{graph_builder::GraphBuilder, graph_prepared::PreparedGraph},
+    lattice_db::{
+        db::FORMAT_VERSION,
+        tables::{
+            GRAPH_METAS, GRAPHS, INDEX_EDGE_ATTR_FWD, INDEX_EDGE_ATTR_REV, INDEX_FORWARD,
+            INDEX_REVERSE, INDEX_SCALAR, INDEX_TEXT, INLINE_GRAPHS, INLINE_PROPS, INTERNED_IDS,
+            META_FORMAT_VERSION, METADATA, SEQ_VERTEX_ID, SEQUENCES, VERTEX_GRAPH_MAP,
+        },
+    },
+    properties::{PropertyHandle, QUERY_MATCH},
+    query::{
+        query_builder::{DegreePredicate, EdgeDirection},
+        query_prepared::Node,
+        query_save::QueryHandle,
+    },
+    utils::sampling,
+    values::{Primitive, Value},
 };
 
+/// A decoded graph, intended for inspection and serialization.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphDump {
+    pub id: u64,
+    pub vertices: Vec<VertexDump>,
+    pub edges: Vec<EdgeDump>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VertexDump {
+    pub id: u64,
+    pub attributes: Vec<(PropertyHandle, Primitive)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EdgeDump {
+    pub from: u64,
+    pub label: PropertyHandle,
+    pub to: u64,
+}
+
+/// Scalar index entries loaded ahead of time, see `LatticeReader::preload`.
+#[derive(Debug, Clone)]
+pub struct PreloadedIndex {
+    reader: u64, // id of the reader whose snapshot the entries were read from
+    bitmaps: HashMap<(u64, u64), RoaringTreemap>,
+}
+
+impl PreloadedIndex {
+    /// Number of preloaded entries.
+    pub fn len(&self) -> usize {
+        self.bitmaps.len()
+    }
+
+    /// Returns true if nothing was preloaded.
+    pub fn is_empty(&self) -> bool {
+        self.bitmaps.is_empty()
+    }
+}
+
+// tracks information gathered while evaluating a query
+#[derive(Default)]
+pub(crate) struct SearchState<'a> {
+    pub(crate) truncated: bool,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) cancel: Option<&'a AtomicBool>,
+    pub(crate) node_sizes: Option<Vec<u64>>, // recorded in build order when set
+    pub(crate) preloaded: Option<&'a PreloadedIndex>,
+    pub(crate) saved_results: HashMap<u64, RoaringTreemap>, // saved queries evaluated inline
+    pub(crate) resolving: HashSet<u64>, // saved queries currently being evaluated
+}
+
+/// A scalar index key, a property id and a value hash, with its vertex ids.
+#[cfg(feature = "testing")]
+pub type ScalarIndexEntry = ((u64, u64), Vec<u64>);
+
+// number of loop iterations between interruption checks
+const CHECK_INTERVAL: usize = 1024;
+
+// number of target vertices read when estimating an edge expansion
+const ESTIMATE_SAMPLE: u64 = 256;
+
+// a node result while estimating, exact until an edge expansion is estimated
+enum CountEstimate {
+    Exact(RoaringTreemap),
+    Approx(u64),
+}
+
+impl CountEstimate {
+    fn len(&self) -> u64 {
+        match self {
+            Self::Exact(bitmap) => bitmap.len(),
+            Self::Approx(count) => *count,
+        }
+    }
+
+    fn exact(&self) -> Option<&RoaringTreemap> {
+        match self {
+            Self::Exact(bitmap) => Some(bitmap),
+            Self::Approx(_) => None,
+        }
+    }
+}
+
+// largest uint that can be stored, the top byte holds the primitive tag
+const MAX_INDEXED_UINT: u64 = 0x00FFFFFFFFFFFFFF;
+
+impl SearchState<'_> {
+    // returns an error if the search should stop
+    pub(crate) fn check(&self) -> Result<(), LatticeError> {
+        if let Some(cancel) = self.cancel
+            && cancel.load(Ordering::Relaxed)
+        {
+            return Err(LatticeError::Cancelled);
+        }
+        if let Some(deadline) = self.deadline
+            && Instant::now() >= deadline
+        {
+            return Err(LatticeError::Timeout);
+        }
+        Ok(())
+    }
+}
+
+/// A matched vertex id with its attributes.
+pub type VertexAttributes = (u64, Vec<(PropertyHandle, Primitive)>);
+
+/// A matched vertex with its graph and attributes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexRecord {
+    pub id: u64,
+    pub graph_id: u64,
+    pub attributes: Vec<(PropertyHandle, Primitive)>,
+}
+
 pub struct LatticeReader {
     pub(crate) rt: ReadTransaction,
+    pub(crate) encoding: IntEncoding,
+    id: u64, // unique per reader, ties preloaded entries to its snapshot
 }
 
+// source of reader ids
+static NEXT_READER_ID: AtomicU64 = AtomicU64::new(0);
+
 impl LatticeReader {
-    pub(crate) fn new(rt: ReadTransaction) -> Self {
-        Self { rt }
+    pub(crate) fn new(rt: ReadTransaction, encoding: IntEncoding) -> Self {
+        let id = NEXT_READER_ID.fetch_add(1, Ordering::Relaxed);
+        Self { rt, encoding, id }
+    }
+
+    /// Return every scalar index key with its vertex ids.
+    /// * Keys are a property id and a value hash, in key order.
+    #[cfg(feature = "testing")]
+    pub fn dump_scalar_index(&self) -> Result<Vec<ScalarIndexEntry>, LatticeError> {
+        let table = self.rt.open_table(INDEX_SCALAR)?;
+        let mut entries = vec![];
+        for entry in table.iter()? {
+            let (key, bytes) = entry?;
+            let bitmap = decode_bitmap(&bytes.value())?;
+            entries.push((key.value(), bitmap.into_iter().collect()));
+        }
+        Ok(entries)
     }
 
     /// Return the graph each vertex id belongs to.
@@ -36,106 +195,1793 @@ impl LatticeReader {
         Ok(results)
     }
 
+    /// Return the distinct graphs with at least one vertex holding the attribute value.
+    /// * Vertices saved without a graph mapping are not counted.
+    pub fn graphs_containing(
+        &self,
+        attr: PropertyHandle,
+        value: impl Value,
+    ) -> Result<RoaringTreemap, LatticeError> {
+        let value = value.to_primitive();
+        if let Err(e) = value.verify_property(attr.0) {
+            return Err(self.describe_error(e)?);
+        }
+        let hash = attr.hash_value(&value);
+
+        let vertices =
+            read_bitmap(&self.rt.open_table(INDEX_SCALAR)?, (attr.0, hash))?.unwrap_or_default();
+        let table = self.rt.open_table(VERTEX_GRAPH_MAP)?;
+        let mut graphs = RoaringTreemap::new();
+        for vertex in vertices {
+            if let Some(graph_id) = table.get(vertex)? {
+                graphs.insert(graph_id.value());
+            }
+        }
+
+        // inline graphs are not in the index
+        for graph in self.inline_graphs_with(attr.0)? {
+            let graph_id = graph.id;
+            if !scan_inline_graphs(&[graph], attr, |v| attr.hash_value(v) == hash).is_empty() {
+                graphs.insert(graph_id);
+            }
+        }
+        Ok(graphs)
+    }
+
+    /// Return up to `n` random vertex ids.
+    /// * The same seed always returns the same sample for unchanged data.
+    pub fn sample_vertices(&self, n: usize, seed: u64) -> Result<Vec<u64>, LatticeError> {
+        let table = self.rt.open_table(VERTEX_GRAPH_MAP)?;
+        let ids = table.iter()?.map(|entry| entry.map(|(k, _)| k.value()));
+        Ok(sampling::reservoir_sample(ids, n, seed)?)
+    }
+
+    /// Return the `k` vertices with the most edges of a label, with their degree.
+    /// * Sorted by highest degree first.
+    /// * Scans the entire edge index for the direction.
+    pub fn top_degree_vertices(
+        &self,
+        label: PropertyHandle,
+        dir: EdgeDirection,
+        k: usize,
+    ) -> Result<Vec<(u64, u64)>, LatticeError> {
+        let table = match dir {
+            EdgeDirection::Outgoing => self.rt.open_table(INDEX_FORWARD)?,
+            EdgeDirection::Incoming => self.rt.open_table(INDEX_REVERSE)?,
+        };
+
+        // keep the k highest degrees in a min heap
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        for entry in table.iter()? {
+            let (key, bytes) = entry?;
+            let (vertex, key_label) = key.value();
+            if key_label != label.0 {
+                continue;
+            }
+            let bitmap = decode_bitmap(&bytes.value())?;
+            heap.push(Reverse((bitmap.len(), Reverse(vertex))));
+            if heap.len() > k {
+                heap.pop();
+            }
+        }
+
+        let mut top: Vec<(u64, u64)> = heap
+            .into_iter()
+            .map(|Reverse((degree, Reverse(vertex)))| (vertex, degree))
+            .collect();
+        top.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        Ok(top)
+    }
+
+    /// Return whether an edge exists from `from` to `to` via label.
+    pub fn has_edge(
+        &self,
+        from: u64,
+        label: PropertyHandle,
+        to: u64,
+    ) -> Result<bool, LatticeError> {
+        let table = self.rt.open_table(INDEX_FORWARD)?;
+        let bitmap = read_bitmap(&table, (from, label.0))?;
+        Ok(bitmap.is_some_and(|b| b.contains(to)))
+    }
+
+    /// Return the ids of the saved queries whose materialized matches contain the vertex.
+    /// * Saved queries that are only evaluated inline are not included.
+    pub fn matching_queries(&self, vertex_id: u64) -> Result<Vec<u64>, LatticeError> {
+        let table = self.rt.open_table(INDEX_SCALAR)?;
+        let mut queries = vec![];
+        for entry in table.range((QUERY_MATCH, 0)..=(QUERY_MATCH, u64::MAX))? {
+            let (key, bytes) = entry?;
+            let bitmap = decode_bitmap(&bytes.value())?;
+            if bitmap.contains(vertex_id) {
+                queries.push(key.value().1);
+            }
+        }
+        Ok(queries)
+    }
+
+    /// Iterate every value hash of a property in the scalar index, along with the vertices that have it.
+    /// * Values are yielded in hash order.
+    /// * Vertices of inline graphs are merged in, their graphs are read up front.
+    pub fn iter_property_index(
+        &self,
+        attr: PropertyHandle,
+    ) -> Result<impl Iterator<Item = Result<(u64, RoaringTreemap), LatticeError>>, LatticeError>
+    {
+        let mut inline = inline_values(&self.inline_graphs_with(attr.0)?, attr)
+            .into_iter()
+            .peekable();
+        let table = self.rt.open_table(INDEX_SCALAR)?;
+        let range = table.range((attr.0, 0)..=(attr.0, u64::MAX))?;
+        let mut indexed = range
+            .map(|entry| {
+                let (key, bytes) = entry?;
+                let bitmap = decode_bitmap(&bytes.value())?;
+                Ok((key.value().1, bitmap))
+            })
+            .peekable();
+
+        // merge both in hash order
+        Ok(std::iter::from_fn(move || {
+            let indexed_hash = match indexed.peek() {
+                Some(Ok((hash, _))) => Some(*hash),
+                Some(Err(_)) => return indexed.next(),
+                None => None,
+            };
+            let inline_hash = inline.peek().map(|(hash, _)| *hash);
+            match (indexed_hash, inline_hash) {
+                (None, None) => None,
+                (None, Some(_)) => inline.next().map(Ok),
+                (Some(a), Some(b)) if b < a => inline.next().map(Ok),
+                (Some(a), Some(b)) if a == b => {
+                    let (_, extra) = inline.next()?;
+                    indexed
+                        .next()
+                        .map(|entry| entry.map(|(hash, bitmap)| (hash, bitmap | extra)))
+                }
+                _ => indexed.next(),
+            }
+        }))
+    }
+
+    /// Return which of the given vertices have any value for the property.
+    pub fn which_have_attribute(
+        &self,
+        ids: &[u64],
+        attr: PropertyHandle,
+    ) -> Result<RoaringTreemap, LatticeError> {
+        let wanted: RoaringTreemap = ids.iter().copied().collect();
+
+        // inline graphs are not in the index
+        let inline = self.inline_graphs_with(attr.0)?;
+        let mut present = scan_inline_graphs(&inline, attr, |_| true) & &wanted;
+
+        // every value of a property is a contiguous range of the scalar index
+        let table = self.rt.open_table(INDEX_SCALAR)?;
+        for entry in table.range((attr.0, 0)..=(attr.0, u64::MAX))? {
+            let (_, bytes) = entry?;
+            let bitmap = decode_bitmap(&bytes.value())?;
+            present |= bitmap & &wanted;
+            if present.len() == wanted.len() {
+                break;
+            }
+        }
+        Ok(present)
+    }
+
+    /// Return every `(from, label, to)` edge with at least one endpoint in the set.
+    pub fn incident_edges(
+        &self,
+        vertex_ids: &RoaringTreemap,
+    ) -> Result<Vec<(u64, u64, u64)>, LatticeError> {
+        let table_fwd = self.rt.open_table(INDEX_FORWARD)?;
+        let table_rev = self.rt.open_table(INDEX_REVERSE)?;
+        let mut edges = vec![];
+        for vertex in vertex_ids {
+            for entry in table_fwd.range((vertex, 0)..=(vertex, u64::MAX))? {
+                let (key, bytes) = entry?;
+                let (_, label) = key.value();
+                let bitmap = decode_bitmap(&bytes.value())?;
+                edges.extend(bitmap.into_iter().map(|to| (vertex, label, to)));
+            }
+            for entry in table_rev.range((vertex, 0)..=(vertex, u64::MAX))? {
+                let (key, bytes) = entry?;
+                let (_, label) = key.value();
+                let bitmap = decode_bitmap(&bytes.value())?;
+                // edges from inside the set were found by the forward scan
+                let outside = bitmap - vertex_ids;
+                edges.extend(outside.into_iter().map(|from| (from, label, vertex)));
+            }
+        }
+        edges.sort_unstable();
+        Ok(edges)
+    }
+
+    /// Return the labels a vertex has outgoing edges on.
+    pub fn outgoing_labels(&self, vertex_id: u64) -> Result<Vec<PropertyHandle>, LatticeError> {
+        let table = self.rt.open_table(INDEX_FORWARD)?;
+        self.labels_of(&table, vertex_id)
+    }
+
+    /// Return the labels a vertex has incoming edges on.
+    pub fn incoming_labels(&self, vertex_id: u64) -> Result<Vec<PropertyHandle>, LatticeError> {
+        let table = self.rt.open_table(INDEX_REVERSE)?;
+        self.labels_of(&table, vertex_id)
+    }
+
+    /// Return the path to every vertex reachable from `from` via outgoing label edges within `max_depth` hops.
+    /// * Each path starts at `from` and ends at the reached vertex.
+    /// * Vertices are reached once, by one of their shortest paths.
+    pub fn reachable_with_paths(
+        &self,
+        from: u64,
+        label: PropertyHandle,
+        max_depth: usize,
+    ) -> Result<Vec<Vec<u64>>, LatticeError> {
+        let table = self.rt.open_table(INDEX_FORWARD)?;
+        let mut parents = HashMap::from([(from, from)]);
+        let mut frontier = vec![from];
+        let mut reached = vec![];
+        for _ in 0..max_depth {
+            let mut next = vec![];
+            for vertex in frontier {
+                let Some(bitmap) = read_bitmap(&table, (vertex, label.0))? else {
+                    continue;
+                };
+                for neighbor in bitmap {
+                    if let Entry::Vacant(e) = parents.entry(neighbor) {
+                        e.insert(vertex);
+                        next.push(neighbor);
+                        reached.push(neighbor);
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+
+        // walk the parents back to the origin
+        let mut paths = Vec::with_capacity(reached.len());
+        for vertex in reached {
+            let mut path = vec![vertex];
+            let mut current = vertex;
+            while current != from {
+                current = parents[&current];
+                path.push(current);
+            }
+            path.reverse();
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Return the key values found on a vertex in every one of the graphs.
+    /// * Entities are matched across graphs by their `key_prop` value, ordered by value hash.
+    pub fn entities_in_all_graphs(
+        &self,
+        key_prop: PropertyHandle,
+        graph_ids: &[u64],
+    ) -> Result<Vec<Primitive>, LatticeError> {
+        let mut shared: Option<BTreeMap<u64, Primitive>> = None;
+        for graph_id in graph_ids {
+            let graph = self.load_prepared_graph(*graph_id)?;
+            let keys: BTreeMap<u64, Primitive> = graph
+                .vertices
+                .into_iter()
+                .flat_map(|v| v.attrs)
+                .filter(|(attr, _)| *attr == key_prop)
+                .map(|(attr, value)| (attr.hash_value(&value), value))
+                .collect();
+            shared = Some(match shared {
+                None => keys,
+                Some(mut shared) => {
+                    shared.retain(|hash, _| keys.contains_key(hash));
+                    shared
+                }
+            });
+            if shared.as_ref().is_some_and(|s| s.is_empty()) {
+                break;
+            }
+        }
+        Ok(shared.unwrap_or_default().into_values().collect())
+    }
+
+    /// Return the value hash a vertex's attribute is indexed under.
+    /// * Returns the first value if the vertex has several, or `None` if it has none.
+    pub fn vertex_attribute_hash(
+        &self,
+        vertex_id: u64,
+        attr: PropertyHandle,
+    ) -> Result<Option<u64>, LatticeError> {
+        let Some(Some(graph_id)) = self.get_graph_ids_from_vertices(&[vertex_id])?.pop() else {
+            return Err(LatticeError::VertexNotFound);
+        };
+        let graph = self.load_prepared_graph(graph_id)?;
+        let vertex = graph
+            .vertices
+            .iter()
+            .find(|v| v.id == vertex_id)
+            .ok_or(LatticeError::VertexNotFound)?;
+        Ok(vertex
+            .attrs
+            .iter()
+            .find(|(a, _)| *a == attr)
+            .map(|(a, value)| a.hash_value(value)))
+    }
+
+    /// Return the cheapest path from `from` to `to` via outgoing label edges, along with its total weight.
+    /// * Edge weights are read from `weight_attr`, edges without an unsigned integer weight are not followed.
+    /// * Edges never cross graphs, so only the graph containing `from` is loaded.
+    pub fn weighted_shortest_path(
+        &self,
+        from: u64,
+        to: u64,
+        label: PropertyHandle,
+        weight_attr: PropertyHandle,
+    ) -> Result<Option<(Vec<u64>, u64)>, LatticeError> {
+        let Some(Some(graph_id)) = self.get_graph_ids_from_vertices(&[from])?.pop() else {
+            return Ok(None);
+        };
+        let graph = self.load_prepared_graph(graph_id)?;
+
+        // weighted adjacency list of the label
+        let mut adjacency: HashMap<u64, Vec<(u64, u64)>> = HashMap::new();
+        for edge in &graph.edges {
+            if edge.label != label {
+                continue;
+            }
+            let weight = edge.attrs.iter().find_map(|(attr, value)| match value {
+                Primitive::UInt(weight) if *attr == weight_attr => Some(*weight),
+                _ => None,
+            });
+            if let Some(weight) = weight {
+                adjacency
+                    .entry(edge.from)
+                    .or_default()
+                    .push((edge.to, weight));
+            }
+        }
+
+        // dijkstra
+        let mut costs = HashMap::from([(from, 0)]);
+        let mut parents = HashMap::new();
+        let mut heap = BinaryHeap::from([Reverse((0, from))]);
+        while let Some(Reverse((cost, vertex))) = heap.pop() {
+            if vertex == to {
+                let mut path = vec![to];
+                let mut current = to;
+                while let Some(parent) = parents.get(&current) {
+                    current = *parent;
+                    path.push(current);
+                }
+                path.reverse();
+                return Ok(Some((path, cost)));
+            }
+            if cost > costs[&vertex] {
+                continue; // stale entry
+            }
+            for (neighbor, weight) in adjacency.get(&vertex).into_iter().flatten() {
+                let next_cost = cost.saturating_add(*weight);
+                if costs.get(neighbor).is_none_or(|c| next_cost < *c) {
+                    costs.insert(*neighbor, next_cost);
+                    parents.insert(*neighbor, vertex);
+                    heap.push(Reverse((next_cost, *neighbor)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Return a path with the fewest label edges from `from` to `to`, following edges in the direction.
+    /// * Only reads the edge index, no graphs are loaded.
+    /// * Returns `None` if `to` can't be reached within `max_hops` edges, or at all if there is no limit.
+    pub fn shortest_path(
+        &self,
+        from: u64,
+        to: u64,
+        label: PropertyHandle,
+        dir: EdgeDirection,
+        max_hops: Option<u64>,
+    ) -> Result<Option<Vec<u64>>, LatticeError> {
+        if from == to {
+            return Ok(Some(vec![from]));
+        }
+        let table = match dir {
+            EdgeDirection::Outgoing => self.rt.open_table(INDEX_FORWARD)?,
+            EdgeDirection::Incoming => self.rt.open_table(INDEX_REVERSE)?,
+        };
+        let mut parents = HashMap::from([(from, from)]);
+        let mut frontier = vec![from];
+        for _ in 0..max_hops.unwrap_or(u64::MAX) {
+            let mut next = vec![];
+            for vertex in frontier {
+                let Some(bitmap) = read_bitmap(&table, (vertex, label.0))? else {
+                    continue;
+                };
+                for neighbor in bitmap {
+                    let Entry::Vacant(e) = parents.entry(neighbor) else {
+                        continue;
+                    };
+                    e.insert(vertex);
+                    if neighbor == to {
+                        // walk the parents back to the origin
+                        let mut path = vec![to];
+                        let mut current = to;
+                        while current != from {
+                            current = parents[&current];
+                            path.push(current);
+                        }
+                        path.reverse();
+                        return Ok(Some(path));
+                    }
+                    next.push(neighbor);
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+        Ok(None)
+    }
+
+    /// Return whether two vertices are connected by label edges in either direction.
+    /// * Searches from both vertices at once, until the searches meet.
+    /// * Returns `None` if more than `max_nodes` vertices were visited before deciding.
+    pub fn same_component(
+        &self,
+        a: u64,
+        b: u64,
+        label: PropertyHandle,
+        max_nodes: usize,
+    ) -> Result<Option<bool>, LatticeError> {
+        if a == b {
+            return Ok(Some(true));
+        }
+        let table_fwd = self.rt.open_table(INDEX_FORWARD)?;
+        let table_rev = self.rt.open_table(INDEX_REVERSE)?;
+
+        let mut seen = [
+            RoaringTreemap::from_iter([a]),
+            RoaringTreemap::from_iter([b]),
+        ];
+        let mut frontiers = [vec![a], vec![b]];
+        loop {
+            // grow the smaller side by one level
+            let side = if frontiers[0].len() <= frontiers[1].len() {
+                0
+            } else {
+                1
+            };
+            let mut next = vec![];
+            for vertex in mem::take(&mut frontiers[side]) {
+                for table in [&table_fwd, &table_rev] {
+                    let Some(bitmap) = read_bitmap(table, (vertex, label.0))? else {
+                        continue;
+                    };
+                    for neighbor in bitmap {
+                        if seen[1 - side].contains(neighbor) {
+                            return Ok(Some(true)); // searches met
+                        }
+                        if seen[side].insert(neighbor) {
+                            next.push(neighbor);
+                        }
+                    }
+                }
+            }
+            if next.is_empty() {
+                return Ok(Some(false)); // one side's component is exhausted
+            }
+            if (seen[0].len() + seen[1].len()) as usize > max_nodes {
+                return Ok(None);
+            }
+            frontiers[side] = next;
+        }
+    }
+
+    /// Return the total number of edges on each label.
+    pub fn edge_count_by_label(&self) -> Result<HashMap<PropertyHandle, u64>, LatticeError> {
+        let table = self.rt.open_table(INDEX_FORWARD)?;
+        let mut counts = HashMap::new();
+        for entry in table.iter()? {
+            let (key, bytes) = entry?;
+            let (_, label) = key.value();
+            let bitmap = decode_bitmap(&bytes.value())?;
+            *counts.entry(label).or_insert(0) += bitmap.len();
+        }
+        counts
+            .into_iter()
+            .map(|(label, count)| Ok((self.label_handle(label)?, count)))
+            .collect()
+    }
+
+    // helper fn to build a handle for an indexed label, deleted properties have no options
+    fn label_handle(&self, id: u64) -> Result<PropertyHandle, LatticeError> {
+        Ok(self
+            .property_handle(id)?
+            .unwrap_or_else(|| PropertyHandle::plain(id)))
+    }
+
+    // helper fn to range scan the labels of an edge index for a vertex
+    fn labels_of(
+        &self,
+        table: &ReadOnlyTable<(u64, u64), Vec<u8>>,
+        vertex_id: u64,
+    ) -> Result<Vec<PropertyHandle>, LatticeError> {
+        let mut labels = vec![];
+        for entry in table.range((vertex_id, 0)..=(vertex_id, u64::MAX))? {
+            let (key, _) = entry?;
+            labels.push(self.label_handle(key.value().1)?);
+        }
+        Ok(labels)
+    }
+
+    /// Return the edges on a label whose weight attribute falls in the range, as `(from, to, weight)`.
+    /// * Only unsigned integer weights are indexed by value order.
+    pub fn edges_by_weight(
+        &self,
+        label: PropertyHandle,
+        weight: PropertyHandle,
+        range: impl RangeBounds<u64>,
+    ) -> Result<Vec<(u64, u64, u64)>, LatticeError> {
+        let min = match range.start_bound() {
+            Bound::Included(n) => *n,
+            Bound::Excluded(n) => n.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let max = match range.end_bound() {
+            Bound::Included(n) => *n,
+            Bound::Excluded(0) => return Ok(vec![]),
+            Bound::Excluded(n) => n - 1,
+            Bound::Unbounded => u64::MAX,
+        }
+        .min(MAX_INDEXED_UINT);
+        if min > max {
+            return Ok(vec![]);
+        }
+
+        // uint hashes keep the order of their values
+        let start = (label.0, weight.0, Primitive::UInt(min).hash(), 0);
+        let end = (label.0, weight.0, Primitive::UInt(max).hash(), u64::MAX);
+        let table = self.rt.open_table(INDEX_EDGE_ATTR_FWD)?;
+        let mut edges = vec![];
+        for entry in table.range(start..=end)? {
+            let (key, bytes) = entry?;
+            let (_, _, hash, from) = key.value();
+            let bitmap = decode_bitmap(&bytes.value())?;
+            for to in bitmap {
+                edges.push((from, to, hash & MAX_INDEXED_UINT));
+            }
+        }
+        Ok(edges)
+    }
+
+    /// Retrieve graph metadata from the database.
+    /// * Returns `None` if the graph was saved without metadata.
+    pub fn get_graph_meta<M: Decode<()>>(&self, graph_id: u64) -> Result<Option<M>, LatticeError> {
+        let table = self.rt.open_table(GRAPH_METAS)?;
+        let Some(bytes) = table.get(graph_id)? else {
+            return Ok(None);
+        };
+        let meta = codec::decode_from_slice(&bytes.value(), self.encoding)?;
+        Ok(Some(meta))
+    }
+
     pub fn load_graph(&self, graph_id: u64) -> Result<GraphBuilder, LatticeError> {
+        let prepared = self.load_prepared_graph(graph_id)?;
+        let mut builder = GraphBuilder::from_prepared(prepared);
+        builder.inline_attributes = self.inline_graph_ids()?.contains(&graph_id);
+        Ok(builder)
+    }
+
+    // helper fn to list the graphs saved with inline attributes
+    pub(crate) fn inline_graph_ids(&self) -> Result<Vec<u64>, LatticeError> {
+        let table = match self.rt.open_table(INLINE_GRAPHS) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(vec![]), // created before inline graphs
+            Err(e) => return Err(e.into()),
+        };
+        let mut ids = vec![];
+        for entry in table.iter()? {
+            ids.push(entry?.0.value());
+        }
+        Ok(ids)
+    }
+
+    // helper fn to load the inline graphs with a vertex attribute of the property
+    pub(crate) fn inline_graphs_with(
+        &self,
+        property: u64,
+    ) -> Result<Vec<PreparedGraph>, LatticeError> {
+        let table = match self.rt.open_table(INLINE_PROPS) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(vec![]), // created before inline graphs
+            Err(e) => return Err(e.into()),
+        };
+        let mut graphs = vec![];
+        for entry in table.range((property, 0)..=(property, u64::MAX))? {
+            let (key, _) = entry?;
+            graphs.push(self.load_prepared_graph(key.value().1)?);
+        }
+        Ok(graphs)
+    }
+
+    // helper fn to decode a stored graph
+    pub(crate) fn load_prepared_graph(&self, graph_id: u64) -> Result<PreparedGraph, LatticeError> {
         let table = self.rt.open_table(GRAPHS)?;
         let bytes = table
             .get(graph_id)?
             .ok_or(LatticeError::GraphNotFound)?
             .value();
-        let prepared = bincode::decode_from_slice(&bytes, config::standard())?.0;
-        Ok(GraphBuilder::from_prepared(prepared))
+        let mut graph: PreparedGraph =
+            codec::decode_from_slice(&bytes, self.encoding).map_err(|e| self.decode_error(e))?;
+        graph.resolve_text(&self.rt.open_table(INTERNED_IDS)?)?;
+        Ok(graph)
+    }
+
+    // blames failed decodes on the format version if the database is newer than this crate
+    fn decode_error(&self, error: DecodeError) -> LatticeError {
+        let version = self
+            .rt
+            .open_table(METADATA)
+            .ok()
+            .and_then(|table| table.get(META_FORMAT_VERSION).ok().flatten())
+            .map(|v| v.value());
+        match version {
+            Some(version) if version > FORMAT_VERSION.into() => {
+                LatticeError::UnsupportedVersion(version)
+            }
+            _ => error.into(),
+        }
+    }
+
+    /// Copy the vertices and the edges between them into a new graph.
+    /// * Vertices are added in the given order, edges to vertices outside the set are dropped.
+    /// * Saving the result creates new vertices, the originals are unchanged.
+    pub fn extract_subgraph(&self, vertex_ids: &[u64]) -> Result<GraphBuilder, LatticeError> {
+        // position of each wanted vertex in the new graph
+        let mut positions = HashMap::with_capacity(vertex_ids.len());
+        for id in vertex_ids {
+            let next = positions.len();
+            positions.entry(*id).or_insert(next);
+        }
+
+        // group vertices by graph
+        let table = self.rt.open_table(VERTEX_GRAPH_MAP)?;
+        let mut graph_ids = BTreeMap::new();
+        for id in positions.keys() {
+            let graph_id = table.get(id)?.ok_or(LatticeError::VertexNotFound)?;
+            graph_ids.insert(graph_id.value(), ());
+        }
+
+        // load each graph once and copy the wanted parts
+        let mut vertices = vec![vec![]; positions.len()];
+        let mut edges = vec![];
+        let mut edge_attrs = vec![];
+        for graph_id in graph_ids.into_keys() {
+            let graph = self.load_prepared_graph(graph_id)?;
+            for vertex in graph.vertices {
+                if let Some(pos) = positions.get(&vertex.id) {
+                    vertices[*pos] = vertex.attrs;
+                }
+            }
+            for edge in graph.edges {
+                if let (Some(from), Some(to)) = (positions.get(&edge.from), positions.get(&edge.to))
+                {
+                    edges.push((*from, edge.label, *to));
+                    edge_attrs.push(edge.attrs);
+                }
+            }
+        }
+
+        let mut builder = GraphBuilder::from_parts(vertices, edges)?;
+        for (idx, attrs) in edge_attrs.into_iter().enumerate() {
+            builder.edges.get_mut_index(idx).unwrap().attributes = attrs;
+        }
+        Ok(builder)
+    }
+
+    /// Return the stored vertices and edges of a graph using global ids.
+    pub fn dump_graph(&self, graph_id: u64) -> Result<GraphDump, LatticeError> {
+        let graph = self.load_prepared_graph(graph_id)?;
+        Ok(GraphDump {
+            id: graph.id,
+            vertices: graph
+                .vertices
+                .into_iter()
+                .map(|v| VertexDump {
+                    id: v.id,
+                    attributes: v.attrs,
+                })
+                .collect(),
+            edges: graph
+                .edges
+                .into_iter()
+                .map(|e| EdgeDump {
+                    from: e.from,
+                    label: e.label,
+                    to: e.to,
+                })
+                .collect(),
+        })
+    }
+
+    /// Search and return the graph id and attributes of every matched vertex.
+    /// * Each graph is only loaded once, no matter how many of its vertices matched.
+    /// * Vertices without a graph mapping are skipped.
+    pub fn search_records(&self, query: &PreparedQuery) -> Result<Vec<VertexRecord>, LatticeError> {
+        let ids = self.search(query)?;
+
+        // group result ids by graph
+        let table = self.rt.open_table(VERTEX_GRAPH_MAP)?;
+        let mut by_graph: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+        for (pos, vid) in ids.iter().enumerate() {
+            if let Some(gid) = table.get(vid)? {
+                by_graph.entry(gid.value()).or_default().push(pos);
+            }
+        }
+
+        // load each graph once and collect the matched vertices
+        let mut records: Vec<Option<VertexRecord>> = vec![None; ids.len()];
+        for (graph_id, positions) in by_graph {
+            let mut graph = self.load_prepared_graph(graph_id)?;
+            let mut attrs: HashMap<u64, _> = graph
+                .vertices
+                .iter_mut()
+                .map(|v| (v.id, std::mem::take(&mut v.attrs)))
+                .collect();
+            for pos in positions {
+                let id = ids[pos];
+                records[pos] = Some(VertexRecord {
+                    id,
+                    graph_id,
+                    attributes: attrs.remove(&id).unwrap_or_default(),
+                });
+            }
+        }
+        Ok(records.into_iter().flatten().collect())
+    }
+
+    /// Search and return the attributes of every matched vertex.
+    /// * Same as `search_records` without the graph ids.
+    pub fn search_with_attrs(
+        &self,
+        query: &PreparedQuery,
+    ) -> Result<Vec<VertexAttributes>, LatticeError> {
+        Ok(self
+            .search_records(query)?
+            .into_iter()
+            .map(|r| (r.id, r.attributes))
+            .collect())
+    }
+
+    /// Search and return each matching vertex id with its graph id.
+    /// * Vertices without a graph mapping are skipped.
+    pub fn search_with_graph_ids(
+        &self,
+        query: &PreparedQuery,
+    ) -> Result<Vec<(u64, u64)>, LatticeError> {
+        let bitmap = self.evaluate(query, &mut SearchState::default())?;
+
+        // bitmaps iterate in order, so lookups walk the map sequentially
+        let table = self.rt.open_table(VERTEX_GRAPH_MAP)?;
+        let mut results = Vec::with_capacity(bitmap.len() as usize);
+        for vid in bitmap {
+            if let Some(gid) = table.get(vid)? {
+                results.push((vid, gid.value()));
+            }
+        }
+        Ok(results)
     }
 
     pub fn search(&self, query: &PreparedQuery) -> Result<Vec<u64>, LatticeError> {
-        let mut results = HashMap::with_capacity(query.nodes.len());
+        let bitmap = self.evaluate(query, &mut SearchState::default())?;
+        Ok(bitmap.into_iter().collect())
+    }
 
-        let table_scl = self.rt.open_table(INDEX_SCALAR)?;
-        let table_fwd = self.rt.open_table(INDEX_FORWARD)?;
-        let table_rev = self.rt.open_table(INDEX_REVERSE)?;
+    /// Search and return only the number of matches.
+    /// * Same result as `search(query)?.len()`, without collecting the ids.
+    pub fn count(&self, query: &PreparedQuery) -> Result<u64, LatticeError> {
+        let bitmap = self.evaluate(query, &mut SearchState::default())?;
+        Ok(bitmap.len())
+    }
+
+    /// Search and return one page of the matching ids with the total number of matches.
+    /// * Ids are in ascending order, the page skips `offset` ids and holds at most `limit`.
+    pub fn search_page(
+        &self,
+        query: &PreparedQuery,
+        offset: u64,
+        limit: usize,
+    ) -> Result<(Vec<u64>, u64), LatticeError> {
+        let bitmap = self.evaluate(query, &mut SearchState::default())?;
+        let total = bitmap.len();
+        let page = bitmap
+            .iter()
+            .skip(offset.try_into().unwrap_or(usize::MAX))
+            .take(limit)
+            .collect();
+        Ok((page, total))
+    }
+
+    /// Search and return at most `limit` matching ids, after skipping the first `offset`.
+    /// * Ids are ordered by ascending vertex id, so pages stay stable while the data is unchanged.
+    /// * Use `search_page` to also get the total number of matches.
+    pub fn search_paginated(
+        &self,
+        query: &PreparedQuery,
+        offset: u64,
+        limit: usize,
+    ) -> Result<Vec<u64>, LatticeError> {
+        self.search_page(query, offset, limit).map(|(page, _)| page)
+    }
+
+    /// Search and return up to `n` random matching ids.
+    /// * The same seed always returns the same sample for unchanged data.
+    /// * Returned ids are sorted.
+    pub fn search_sample(
+        &self,
+        query: &PreparedQuery,
+        n: usize,
+        seed: u64,
+    ) -> Result<Vec<u64>, LatticeError> {
+        let bitmap = self.evaluate(query, &mut SearchState::default())?;
+        sampling::reservoir_sample(bitmap.into_iter().map(Ok), n, seed)
+    }
+
+    /// Load scalar index entries into memory, for searches with `search_preloaded`.
+    /// * Keys are a property and a value hash, as from `PropertyHandle::hash_value`.
+    pub fn preload(&self, keys: &[(PropertyHandle, u64)]) -> Result<PreloadedIndex, LatticeError> {
+        let table = self.rt.open_table(INDEX_SCALAR)?;
+        let mut bitmaps = HashMap::with_capacity(keys.len());
+        for (attr, value_hash) in keys {
+            let key = (attr.0, *value_hash);
+            let bitmap = read_bitmap(&table, key)?.unwrap_or_default();
+            bitmaps.insert(key, bitmap);
+        }
+        Ok(PreloadedIndex {
+            reader: self.id,
+            bitmaps,
+        })
+    }
+
+    /// Search, reading attribute matches from the preloaded entries when possible.
+    /// * Returns an error if the entries were preloaded by another reader, its snapshot may differ.
+    pub fn search_preloaded(
+        &self,
+        query: &PreparedQuery,
+        preloaded: &PreloadedIndex,
+    ) -> Result<Vec<u64>, LatticeError> {
+        if preloaded.reader != self.id {
+            return Err(LatticeError::SnapshotMismatch);
+        }
+        let mut state = SearchState {
+            preloaded: Some(preloaded),
+            ..Default::default()
+        };
+        let bitmap = self.evaluate(query, &mut state)?;
+        Ok(bitmap.into_iter().collect())
+    }
+
+    /// Search and split the results by their value hash of the property.
+    /// * Results without a value are left out, results with several values appear in each group.
+    pub fn group_results_by(
+        &self,
+        query: &PreparedQuery,
+        attr: PropertyHandle,
+    ) -> Result<HashMap<u64, Vec<u64>>, LatticeError> {
+        let results = self.evaluate(query, &mut SearchState::default())?;
+        let mut groups = HashMap::new();
+        for entry in self.iter_property_index(attr)? {
+            let (value_hash, bitmap) = entry?;
+            let group = bitmap & &results;
+            if !group.is_empty() {
+                groups.insert(value_hash, group.into_iter().collect());
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Search and report whether any limited edge traversal was truncated.
+    /// * Returns the results and `true` if the results may be partial.
+    pub fn search_partial(&self, query: &PreparedQuery) -> Result<(Vec<u64>, bool), LatticeError> {
+        let mut state = SearchState::default();
+        let bitmap = self.evaluate(query, &mut state)?;
+        Ok((bitmap.into_iter().collect(), state.truncated))
+    }
+
+    /// Search and return the results along with the result size of every query node.
+    /// * Sizes are in build order, the order nodes were evaluated in.
+    pub fn search_traced(
+        &self,
+        query: &PreparedQuery,
+    ) -> Result<(Vec<u64>, Vec<u64>), LatticeError> {
+        let mut state = SearchState {
+            node_sizes: Some(Vec::with_capacity(query.nodes.len())),
+            ..Default::default()
+        };
+        let bitmap = self.evaluate(query, &mut state)?;
+        Ok((
+            bitmap.into_iter().collect(),
+            state.node_sizes.unwrap_or_default(),
+        ))
+    }
+
+    /// Search, stopping with a timeout error once the deadline has passed.
+    pub fn search_with_deadline(
+        &self,
+        query: &PreparedQuery,
+        deadline: Instant,
+    ) -> Result<Vec<u64>, LatticeError> {
+        let mut state = SearchState {
+            deadline: Some(deadline),
+            ..Default::default()
+        };
+        let bitmap = self.evaluate(query, &mut state)?;
+        Ok(bitmap.into_iter().collect())
+    }
+
+    /// Search, stopping with a cancelled error once `cancel` is set.
+    pub fn search_cancellable(
+        &self,
+        query: &PreparedQuery,
+        cancel: &AtomicBool,
+    ) -> Result<Vec<u64>, LatticeError> {
+        let mut state = SearchState {
+            cancel: Some(cancel),
+            ..Default::default()
+        };
+        let bitmap = self.evaluate(query, &mut state)?;
+        Ok(bitmap.into_iter().collect())
+    }
+
+    /// Estimate the number of vertices a query matches without expanding every edge.
+    /// * Attribute and saved query nodes are exact, edge nodes scale the neighbor counts of a sample of their targets.
+    /// * Set nodes stay exact until a child is estimated, then use the sum, smallest, or first child count.
+    pub fn estimate_count(&self, query: &PreparedQuery) -> Result<u64, LatticeError> {
+        let mut results: HashMap<usize, CountEstimate> = HashMap::with_capacity(query.nodes.len());
+        let mut state = SearchState::default();
+        let source = ReaderSource::new(self)?;
+        let mut inline_graphs = HashMap::new();
+
+        // no estimate can be larger than the number of vertex ids handed out
+        let cap = self
+            .rt
+            .open_table(SEQUENCES)?
+            .get(SEQ_VERTEX_ID)?
+            .map(|v| v.value())
+            .unwrap_or(0);
 
         for (idx, node) in query.nodes.iter().enumerate() {
-            let bitmap = match node {
+            let estimate = match node {
                 Node::Union(children) => {
-                    let mut res = RoaringTreemap::new();
-                    for child_idx in children {
-                        if let Some(child_bitmap) = results.get(child_idx) {
-                            res |= child_bitmap;
-                        }
+                    let children = children
+                        .iter()
+                        .map(|c| child_result(&results, *c))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let exact: Option<Vec<&RoaringTreemap>> =
+                        children.iter().map(|c| c.exact()).collect();
+                    match exact {
+                        Some(bitmaps) => CountEstimate::Exact(
+                            bitmaps
+                                .into_iter()
+                                .fold(RoaringTreemap::new(), |acc, b| acc | b),
+                        ),
+                        None => CountEstimate::Approx(
+                            children
+                                .iter()
+                                .fold(0u64, |acc, c| acc.saturating_add(c.len()))
+                                .min(cap),
+                        ),
                     }
-                    res
                 }
                 Node::Intersect(children) => {
-                    // get first child then intersect it sequentially with other children
-                    if children.is_empty() {
-                        RoaringTreemap::new()
-                    } else {
-                        let mut bitmaps: Vec<&RoaringTreemap> =
-                            children.iter().filter_map(|id| results.get(id)).collect();
-
-                        if bitmaps.is_empty() {
-                            RoaringTreemap::new()
-                        } else {
-                            bitmaps.sort_by_key(|b| b.len());
-                            let mut res = bitmaps[0].clone();
-                            for other in &bitmaps[1..] {
-                                res &= *other;
-                                if res.is_empty() {
-                                    break;
-                                }
-                            }
-                            res
+                    let children = children
+                        .iter()
+                        .map(|c| child_result(&results, *c))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let exact: Option<Vec<&RoaringTreemap>> =
+                        children.iter().map(|c| c.exact()).collect();
+                    match exact {
+                        Some(bitmaps) => {
+                            let mut iter = bitmaps.into_iter();
+                            let first = iter.next().cloned().unwrap_or_default();
+                            CountEstimate::Exact(iter.fold(first, |acc, b| acc & b))
                         }
+                        None => CountEstimate::Approx(
+                            children.iter().map(|c| c.len()).min().unwrap_or(0),
+                        ),
                     }
                 }
                 Node::Difference(a, b) => {
-                    let a = results.get(a).unwrap();
-                    let b = results.get(b).unwrap();
-                    let mut res = a.clone();
-                    res -= b; // subtract bitmap
-                    res
+                    let a = child_result(&results, *a)?;
+                    let b = child_result(&results, *b)?;
+                    match (a.exact(), b.exact()) {
+                        (Some(a), Some(b)) => CountEstimate::Exact(a - b),
+                        _ => CountEstimate::Approx(a.len()),
+                    }
                 }
                 Node::Attribute { attr, value } => {
-                    let key = (attr.0, *value);
-                    // read from the index for all vertices with the value
-                    if let Some(bytes) = table_scl.get(key)? {
-                        RoaringTreemap::deserialize_from(&bytes.value()[..])
-                            .map_err(|e| bincode::error::EncodeError::OtherString(e.to_string()))?
-                    } else {
-                        RoaringTreemap::new()
+                    let indexed = source.scalar((attr.0, *value))?.unwrap_or_default();
+                    CountEstimate::Exact(
+                        indexed
+                            | inline_matches(&source, &mut inline_graphs, *attr, |v| {
+                                attr.hash_value(v) == *value
+                            })?,
+                    )
+                }
+                Node::Range { attr, start, end } => CountEstimate::Exact(
+                    source
+                        .scalar_range(attr.0, *start, *end)?
+                        .values()
+                        .fold(RoaringTreemap::new(), |acc, b| acc | b)
+                        | inline_matches(&source, &mut inline_graphs, *attr, |v| {
+                            (*start..*end).contains(&attr.hash_value(v))
+                        })?,
+                ),
+                Node::Prefix { attr, prefix } => CountEstimate::Exact(
+                    source
+                        .text_prefix(attr.0, prefix)?
+                        .values()
+                        .fold(RoaringTreemap::new(), |acc, b| acc | b)
+                        | inline_matches(&source, &mut inline_graphs, *attr, |v| {
+                            attr.index_text(v)
+                                .is_some_and(|t| t.starts_with(prefix.as_str()))
+                        })?,
+                ),
+                Node::EdgeDepth {
+                    dir,
+                    label,
+                    target,
+                    max_depth,
+                } => {
+                    let target = child_result(&results, *target)?;
+                    let count = estimate_traversal(&source, *dir, target, *label, *max_depth, cap)?;
+                    CountEstimate::Approx(count)
+                }
+                Node::Reachable { dir, label, target } => {
+                    let target = child_result(&results, *target)?;
+                    let count = estimate_traversal(&source, *dir, target, *label, u64::MAX, cap)?;
+                    CountEstimate::Approx(count)
+                }
+                Node::Complement { child, graph } => {
+                    let universe = graph_vertices(&source, *graph, &state)?;
+                    match child_result(&results, *child)?.exact() {
+                        Some(child) => CountEstimate::Exact(universe - child),
+                        None => CountEstimate::Approx(universe.len()),
                     }
                 }
-                Node::Edge { dir, label, target } => {
-                    let ids = results.get(target).unwrap();
-                    let mut res = RoaringTreemap::new();
-                    let table = match dir {
-                        EdgeDirection::Outgoing => &table_fwd, // find all vertices that are pointed to by target
-                        EdgeDirection::Incoming => &table_rev, // find all vertices that point to target
-                    };
-                    for id in ids {
-                        let key = (id, label.0);
-                        if let Some(bytes) = table.get(key)? {
-                            let connected_nodes = RoaringTreemap::deserialize_from(
-                                &bytes.value()[..],
-                            )
-                            .map_err(|e| bincode::error::EncodeError::OtherString(e.to_string()))?;
-                            res |= connected_nodes;
+                Node::SavedQuery(query) => {
+                    CountEstimate::Exact(saved_query_bitmap(&source, *query, &mut state)?)
+                }
+                Node::Degree {
+                    dir,
+                    label,
+                    predicate,
+                } => {
+                    CountEstimate::Exact(degree_bitmap(&source, *dir, *label, *predicate, &state)?)
+                }
+                Node::Edge { dir, label, target }
+                | Node::EdgeWhere {
+                    dir, label, target, ..
+                } => {
+                    // edge attribute filters are ignored, the estimate is an upper bound
+                    let target = child_result(&results, *target)?;
+                    let count = estimate_expansion(&source, *dir, target, *label, u64::MAX)?;
+                    CountEstimate::Approx(count.min(cap))
+                }
+                Node::EdgeLimited {
+                    dir,
+                    label,
+                    target,
+                    max_targets,
+                } => {
+                    let target = child_result(&results, *target)?;
+                    let count = estimate_expansion(&source, *dir, target, *label, *max_targets)?;
+                    CountEstimate::Approx(count.min(cap))
+                }
+            };
+            results.insert(idx, estimate);
+        }
+        Ok(results.get(&query.root).map(|r| r.len()).unwrap_or(0))
+    }
+
+    // evaluates every query node against the committed tables and returns the root bitmap
+    pub(crate) fn evaluate(
+        &self,
+        query: &PreparedQuery,
+        state: &mut SearchState<'_>,
+    ) -> Result<RoaringTreemap, LatticeError> {
+        evaluate_query(&ReaderSource::new(self)?, query, state)
+    }
+}
+
+// where a query reads index entries, inline graphs, and saved queries from
+pub(crate) trait QuerySource {
+    // bitmap under a (property, value hash) scalar index key
+    fn scalar(&self, key: (u64, u64)) -> Result<Option<RoaringTreemap>, LatticeError>;
+    // bitmaps of a property under every value hash in [start, end) of the scalar index
+    fn scalar_range(
+        &self,
+        attr: u64,
+        start: u64,
+        end: u64,
+    ) -> Result<BTreeMap<u64, RoaringTreemap>, LatticeError>;
+    // bitmaps of a property under every text starting with prefix in the text index
+    fn text_prefix(
+        &self,
+        attr: u64,
+        prefix: &str,
+    ) -> Result<BTreeMap<String, RoaringTreemap>, LatticeError>;
+    // bitmap under a (vertex, label) key of the forward or reverse edge index
+    fn edges(
+        &self,
+        dir: EdgeDirection,
+        key: (u64, u64),
+    ) -> Result<Option<RoaringTreemap>, LatticeError>;
+    // bitmap under a (label, property, value hash, vertex) key of an edge attribute index
+    fn edge_attrs(
+        &self,
+        dir: EdgeDirection,
+        key: (u64, u64, u64, u64),
+    ) -> Result<Option<RoaringTreemap>, LatticeError>;
+    // the inline graphs with a vertex attribute of the property
+    fn inline_graphs(&self, property: u64) -> Result<Vec<PreparedGraph>, LatticeError>;
+    // a saved query by id
+    fn saved_query(&self, query_id: u64) -> Result<PreparedQuery, LatticeError>;
+    // number of edges via label of every vertex that has at least one
+    fn degrees(
+        &self,
+        dir: EdgeDirection,
+        label: u64,
+        state: &SearchState<'_>,
+    ) -> Result<HashMap<u64, u64>, LatticeError>;
+    // every vertex mapped to a graph
+    fn all_vertices(&self) -> Result<RoaringTreemap, LatticeError>;
+    // the graph a vertex is mapped to
+    fn graph_of(&self, vertex: u64) -> Result<Option<u64>, LatticeError>;
+    // a stored graph by id
+    fn graph(&self, graph_id: u64) -> Result<PreparedGraph, LatticeError>;
+}
+
+// reads the committed tables of a read transaction
+struct ReaderSource<'a> {
+    reader: &'a LatticeReader,
+    table_scl: ReadOnlyTable<(u64, u64), Vec<u8>>,
+    table_txt: ReadOnlyTable<(u64, String), Vec<u8>>,
+    table_fwd: ReadOnlyTable<(u64, u64), Vec<u8>>,
+    table_rev: ReadOnlyTable<(u64, u64), Vec<u8>>,
+    table_attr_fwd: ReadOnlyTable<(u64, u64, u64, u64), Vec<u8>>,
+    table_attr_rev: ReadOnlyTable<(u64, u64, u64, u64), Vec<u8>>,
+}
+
+impl<'a> ReaderSource<'a> {
+    fn new(reader: &'a LatticeReader) -> Result<Self, LatticeError> {
+        Ok(Self {
+            reader,
+            table_scl: reader.rt.open_table(INDEX_SCALAR)?,
+            table_txt: reader.rt.open_table(INDEX_TEXT)?,
+            table_fwd: reader.rt.open_table(INDEX_FORWARD)?,
+            table_rev: reader.rt.open_table(INDEX_REVERSE)?,
+            table_attr_fwd: reader.rt.open_table(INDEX_EDGE_ATTR_FWD)?,
+            table_attr_rev: reader.rt.open_table(INDEX_EDGE_ATTR_REV)?,
+        })
+    }
+}
+
+impl QuerySource for ReaderSource<'_> {
+    fn scalar(&self, key: (u64, u64)) -> Result<Option<RoaringTreemap>, LatticeError> {
+        read_bitmap(&self.table_scl, key)
+    }
+
+    fn scalar_range(
+        &self,
+        attr: u64,
+        start: u64,
+        end: u64,
+    ) -> Result<BTreeMap<u64, RoaringTreemap>, LatticeError> {
+        read_bitmap_range(&self.table_scl, attr, start, end)
+    }
+
+    fn text_prefix(
+        &self,
+        attr: u64,
+        prefix: &str,
+    ) -> Result<BTreeMap<String, RoaringTreemap>, LatticeError> {
+        read_prefix_range(&self.table_txt, attr, prefix)
+    }
+
+    fn edges(
+        &self,
+        dir: EdgeDirection,
+        key: (u64, u64),
+    ) -> Result<Option<RoaringTreemap>, LatticeError> {
+        match dir {
+            EdgeDirection::Outgoing => read_bitmap(&self.table_fwd, key),
+            EdgeDirection::Incoming => read_bitmap(&self.table_rev, key),
+        }
+    }
+
+    fn edge_attrs(
+        &self,
+        dir: EdgeDirection,
+        key: (u64, u64, u64, u64),
+    ) -> Result<Option<RoaringTreemap>, LatticeError> {
+        match dir {
+            EdgeDirection::Outgoing => read_bitmap(&self.table_attr_fwd, key),
+            EdgeDirection::Incoming => read_bitmap(&self.table_attr_rev, key),
+        }
+    }
+
+    fn inline_graphs(&self, property: u64) -> Result<Vec<PreparedGraph>, LatticeError> {
+        self.reader.inline_graphs_with(property)
+    }
+
+    fn saved_query(&self, query_id: u64) -> Result<PreparedQuery, LatticeError> {
+        self.reader.get_prepared_query(QueryHandle(query_id))
+    }
+
+    fn degrees(
+        &self,
+        dir: EdgeDirection,
+        label: u64,
+        state: &SearchState<'_>,
+    ) -> Result<HashMap<u64, u64>, LatticeError> {
+        match dir {
+            EdgeDirection::Outgoing => scan_degrees(&self.table_fwd, label, state),
+            EdgeDirection::Incoming => scan_degrees(&self.table_rev, label, state),
+        }
+    }
+
+    fn all_vertices(&self) -> Result<RoaringTreemap, LatticeError> {
+        mapped_vertices(&self.reader.rt.open_table(VERTEX_GRAPH_MAP)?)
+    }
+
+    fn graph_of(&self, vertex: u64) -> Result<Option<u64>, LatticeError> {
+        let table = self.reader.rt.open_table(VERTEX_GRAPH_MAP)?;
+        Ok(table.get(vertex)?.map(|v| v.value()))
+    }
+
+    fn graph(&self, graph_id: u64) -> Result<PreparedGraph, LatticeError> {
+        self.reader.load_prepared_graph(graph_id)
+    }
+}
+
+// helper fn to decode a stored bitmap
+pub(crate) fn decode_bitmap(bytes: &[u8]) -> Result<RoaringTreemap, LatticeError> {
+    Ok(RoaringTreemap::deserialize_from(bytes)
+        .map_err(|e| bincode::error::EncodeError::OtherString(e.to_string()))?)
+}
+
+// helper fn to read and decode an index bitmap
+pub(crate) fn read_bitmap<K>(
+    table: &impl ReadableTable<K, Vec<u8>>,
+    key: K,
+) -> Result<Option<RoaringTreemap>, LatticeError>
+where
+    K: redb::Key + for<'a> redb::Value<SelfType<'a> = K> + 'static,
+{
+    let Some(bytes) = table.get(key)? else {
+        return Ok(None);
+    };
+    Ok(Some(decode_bitmap(&bytes.value())?))
+}
+
+// helper fn to read every scalar index bitmap of a property with a value hash in [start, end)
+pub(crate) fn read_bitmap_range(
+    table: &impl ReadableTable<(u64, u64), Vec<u8>>,
+    attr: u64,
+    start: u64,
+    end: u64,
+) -> Result<BTreeMap<u64, RoaringTreemap>, LatticeError> {
+    let mut bitmaps = BTreeMap::new();
+    if start >= end {
+        return Ok(bitmaps);
+    }
+    for entry in table.range((attr, start)..(attr, end))? {
+        let (key, bytes) = entry?;
+        let bitmap = decode_bitmap(&bytes.value())?;
+        bitmaps.insert(key.value().1, bitmap);
+    }
+    Ok(bitmaps)
+}
+
+// helper fn to read every text index bitmap of a property with text starting with prefix
+pub(crate) fn read_prefix_range(
+    table: &impl ReadableTable<(u64, String), Vec<u8>>,
+    attr: u64,
+    prefix: &str,
+) -> Result<BTreeMap<String, RoaringTreemap>, LatticeError> {
+    let mut bitmaps = BTreeMap::new();
+    // texts sharing a prefix are stored next to each other
+    for entry in table.range((attr, prefix.to_string())..)? {
+        let (key, bytes) = entry?;
+        let (prop, text) = key.value();
+        if prop != attr || !text.starts_with(prefix) {
+            break;
+        }
+        let bitmap = decode_bitmap(&bytes.value())?;
+        bitmaps.insert(text, bitmap);
+    }
+    Ok(bitmaps)
+}
+
+// helper fn to count the edges via label of every vertex in an edge index
+pub(crate) fn scan_degrees(
+    table: &impl ReadableTable<(u64, u64), Vec<u8>>,
+    label: u64,
+    state: &SearchState<'_>,
+) -> Result<HashMap<u64, u64>, LatticeError> {
+    let mut degrees = HashMap::new();
+    for (i, entry) in table.iter()?.enumerate() {
+        if i % CHECK_INTERVAL == 0 {
+            state.check()?;
+        }
+        let (key, bytes) = entry?;
+        let (vertex, edge_label) = key.value();
+        if edge_label != label {
+            continue;
+        }
+        let bitmap = decode_bitmap(&bytes.value())?;
+        degrees.insert(vertex, bitmap.len());
+    }
+    Ok(degrees)
+}
+
+// helper fn to collect every vertex with a graph mapping
+pub(crate) fn mapped_vertices(
+    table: &impl ReadableTable<u64, u64>,
+) -> Result<RoaringTreemap, LatticeError> {
+    let mut vertices = RoaringTreemap::new();
+    for entry in table.iter()? {
+        vertices.insert(entry?.0.value());
+    }
+    Ok(vertices)
+}
+
+// helper fn to collect the vertices of a graph that are mapped to it
+fn graph_vertices(
+    source: &impl QuerySource,
+    graph_id: u64,
+    state: &SearchState<'_>,
+) -> Result<RoaringTreemap, LatticeError> {
+    let graph = match source.graph(graph_id) {
+        Ok(graph) => graph,
+        Err(LatticeError::GraphNotFound) => return Ok(RoaringTreemap::new()),
+        Err(e) => return Err(e),
+    };
+    let mut vertices = RoaringTreemap::new();
+    for (i, vertex) in graph.vertices.iter().enumerate() {
+        if i % CHECK_INTERVAL == 0 {
+            state.check()?;
+        }
+        if source.graph_of(vertex.id)? == Some(graph_id) {
+            vertices.insert(vertex.id);
+        }
+    }
+    Ok(vertices)
+}
+
+// evaluates every query node and returns the root bitmap
+pub(crate) fn evaluate_query(
+    source: &impl QuerySource,
+    query: &PreparedQuery,
+    state: &mut SearchState<'_>,
+) -> Result<RoaringTreemap, LatticeError> {
+    let mut results = HashMap::with_capacity(query.nodes.len());
+    let mut inline_graphs = HashMap::new(); // loaded per property on its first lookup
+
+    for (idx, node) in query.nodes.iter().enumerate() {
+        state.check()?;
+        let bitmap = match node {
+            Node::Union(children) => {
+                let mut res = RoaringTreemap::new();
+                for child_idx in children {
+                    res |= child_result(&results, *child_idx)?;
+                }
+                res
+            }
+            Node::Intersect(children) => {
+                // get first child then intersect it sequentially with other children
+                let mut bitmaps = children
+                    .iter()
+                    .map(|id| child_result(&results, *id))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if bitmaps.is_empty() {
+                    RoaringTreemap::new()
+                } else {
+                    bitmaps.sort_by_key(|b| b.len());
+                    let mut res = bitmaps[0].clone();
+                    for other in &bitmaps[1..] {
+                        res &= *other;
+                        if res.is_empty() {
+                            break;
                         }
                     }
                     res
                 }
-                Node::SavedQuery(query) => {
-                    // similar to attribute lookup for pre-saved queries
-                    let key = (QUERY_MATCH, *query);
-                    if let Some(bytes) = table_scl.get(key)? {
-                        RoaringTreemap::deserialize_from(&bytes.value()[..])?
-                    } else {
-                        RoaringTreemap::new()
+            }
+            Node::Difference(a, b) => {
+                let a = child_result(&results, *a)?;
+                let b = child_result(&results, *b)?;
+                let mut res = a.clone();
+                res -= b; // subtract bitmap
+                res
+            }
+            Node::Attribute { attr, value } => {
+                let key = (attr.0, *value);
+                let preloaded = state.preloaded.and_then(|p| p.bitmaps.get(&key));
+                // read from the index for all vertices with the value
+                let mut res = if let Some(bitmap) = preloaded {
+                    bitmap.clone()
+                } else {
+                    source.scalar(key)?.unwrap_or_default()
+                };
+                // inline graphs are not in the index
+                res |= inline_matches(source, &mut inline_graphs, *attr, |v| {
+                    attr.hash_value(v) == *value
+                })?;
+                match query.values.get(&idx) {
+                    Some(expected) if query.verify_values => {
+                        verify_candidates(source, *attr, expected, res, state)?
                     }
+                    _ => res,
                 }
-            };
-            results.insert(idx, bitmap);
+            }
+            Node::Edge { dir, label, target } => {
+                // outgoing finds all vertices that are pointed to by target, incoming all vertices that point to target
+                let ids = child_result(&results, *target)?;
+                expand(source, *dir, ids.iter(), *label, state)?
+            }
+            Node::EdgeLimited {
+                dir,
+                label,
+                target,
+                max_targets,
+            } => {
+                let ids = child_result(&results, *target)?;
+                if ids.len() > *max_targets {
+                    state.truncated = true;
+                }
+                expand(
+                    source,
+                    *dir,
+                    ids.iter().take(*max_targets as usize),
+                    *label,
+                    state,
+                )?
+            }
+            Node::EdgeWhere {
+                dir,
+                label,
+                target,
+                attr,
+                value,
+            } => {
+                let ids = child_result(&results, *target)?;
+                let mut res = RoaringTreemap::new();
+                for (i, id) in ids.iter().enumerate() {
+                    if i % CHECK_INTERVAL == 0 {
+                        state.check()?;
+                    }
+                    if let Some(connected) =
+                        source.edge_attrs(*dir, (label.0, attr.0, *value, id))?
+                    {
+                        res |= connected;
+                    }
+                }
+                res
+            }
+            Node::Range { attr, start, end } => {
+                let mut res = RoaringTreemap::new();
+                for bitmap in source.scalar_range(attr.0, *start, *end)?.values() {
+                    res |= bitmap;
+                }
+                // inline graphs are not in the index
+                res |= inline_matches(source, &mut inline_graphs, *attr, |v| {
+                    (*start..*end).contains(&attr.hash_value(v))
+                })?;
+                res
+            }
+            Node::Prefix { attr, prefix } => {
+                let mut res = RoaringTreemap::new();
+                for bitmap in source.text_prefix(attr.0, prefix)?.values() {
+                    res |= bitmap;
+                }
+                // inline graphs are not in the index
+                res |= inline_matches(source, &mut inline_graphs, *attr, |v| {
+                    attr.index_text(v)
+                        .is_some_and(|t| t.starts_with(prefix.as_str()))
+                })?;
+                res
+            }
+            Node::EdgeDepth {
+                dir,
+                label,
+                target,
+                max_depth,
+            } => {
+                let ids = child_result(&results, *target)?;
+                traverse(source, *dir, ids, *label, *max_depth, state)?
+            }
+            Node::Reachable { dir, label, target } => {
+                let ids = child_result(&results, *target)?;
+                traverse(source, *dir, ids, *label, u64::MAX, state)?
+            }
+            Node::Complement { child, graph } => {
+                let mut res = graph_vertices(source, *graph, state)?;
+                res -= child_result(&results, *child)?;
+                res
+            }
+            Node::SavedQuery(query) => saved_query_bitmap(source, *query, state)?,
+            Node::Degree {
+                dir,
+                label,
+                predicate,
+            } => degree_bitmap(source, *dir, *label, *predicate, state)?,
+        };
+        if let Some(sizes) = &mut state.node_sizes {
+            sizes.push(bitmap.len());
         }
-        let bitmap = results.get(&query.root).cloned().unwrap_or_default();
-        Ok(bitmap.into_iter().collect())
+        results.insert(idx, bitmap);
+    }
+    Ok(results.remove(&query.root).unwrap_or_default())
+}
+
+// helper fn to read an evaluated child, children must be placed before their parents
+fn child_result<T>(results: &HashMap<usize, T>, idx: usize) -> Result<&T, LatticeError> {
+    results.get(&idx).ok_or(LatticeError::QueryNodeNotFound)
+}
+
+// helper fn to drop candidates whose stored value only shares the hash of the expected value
+fn verify_candidates(
+    source: &impl QuerySource,
+    attr: PropertyHandle,
+    expected: &Primitive,
+    candidates: RoaringTreemap,
+    state: &SearchState<'_>,
+) -> Result<RoaringTreemap, LatticeError> {
+    let mut loaded = HashSet::new();
+    let mut matched = RoaringTreemap::new();
+    let mut res = RoaringTreemap::new();
+    for (i, vertex) in candidates.iter().enumerate() {
+        if i % CHECK_INTERVAL == 0 {
+            state.check()?;
+        }
+        let Some(graph_id) = source.graph_of(vertex)? else {
+            res.insert(vertex); // unmapped vertices can't be checked
+            continue;
+        };
+        // each graph is checked once for every candidate inside it
+        if loaded.insert(graph_id) {
+            for v in source.graph(graph_id)?.vertices {
+                if v.attrs
+                    .iter()
+                    .any(|(a, val)| a.0 == attr.0 && attr.values_match(val, expected))
+                {
+                    matched.insert(v.id);
+                }
+            }
+        }
+        if matched.contains(vertex) {
+            res.insert(vertex);
+        }
+    }
+    Ok(res)
+}
+
+// helper fn to match the vertices of the inline graphs holding a property, loading them once per property
+fn inline_matches(
+    source: &impl QuerySource,
+    loaded: &mut HashMap<u64, Vec<PreparedGraph>>,
+    attr: PropertyHandle,
+    matches: impl Fn(&Primitive) -> bool,
+) -> Result<RoaringTreemap, LatticeError> {
+    let graphs = match loaded.entry(attr.0) {
+        Entry::Occupied(e) => e.into_mut(),
+        Entry::Vacant(e) => e.insert(source.inline_graphs(attr.0)?),
+    };
+    Ok(scan_inline_graphs(graphs, attr, matches))
+}
+
+// helper fn to group the vertices of inline graphs by the value hashes of a property
+fn inline_values(graphs: &[PreparedGraph], attr: PropertyHandle) -> BTreeMap<u64, RoaringTreemap> {
+    let mut values: BTreeMap<u64, RoaringTreemap> = BTreeMap::new();
+    for graph in graphs {
+        for vertex in &graph.vertices {
+            for (a, v) in &vertex.attrs {
+                if a.0 == attr.0 {
+                    values
+                        .entry(attr.hash_value(v))
+                        .or_default()
+                        .insert(vertex.id);
+                }
+            }
+        }
+    }
+    values
+}
+
+// helper fn to find matching vertices by scanning graphs instead of the index
+fn scan_inline_graphs(
+    graphs: &[PreparedGraph],
+    attr: PropertyHandle,
+    matches: impl Fn(&Primitive) -> bool,
+) -> RoaringTreemap {
+    let mut res = RoaringTreemap::new();
+    for graph in graphs {
+        for vertex in &graph.vertices {
+            if vertex
+                .attrs
+                .iter()
+                .any(|(a, v)| a.0 == attr.0 && matches(v))
+            {
+                res.insert(vertex.id);
+            }
+        }
+    }
+    res
+}
+
+// helper fn to read the materialized matches of a saved query, or evaluate it if there are none
+fn saved_query_bitmap(
+    source: &impl QuerySource,
+    query_id: u64,
+    state: &mut SearchState<'_>,
+) -> Result<RoaringTreemap, LatticeError> {
+    // each saved query is only read or evaluated once per search
+    if let Some(bitmap) = state.saved_results.get(&query_id) {
+        return Ok(bitmap.clone());
+    }
+
+    // similar to attribute lookup for pre-saved queries
+    let bitmap = match source.scalar((QUERY_MATCH, query_id))? {
+        Some(bitmap) => bitmap,
+        None => {
+            // a saved query reached again while evaluating it references itself
+            if !state.resolving.insert(query_id) {
+                return Err(LatticeError::QueryCycle);
+            }
+
+            // inner nodes are not part of the traced query
+            let saved = source.saved_query(query_id)?;
+            let node_sizes = state.node_sizes.take();
+            let res = evaluate_query(source, &saved, state);
+            state.node_sizes = node_sizes;
+            state.resolving.remove(&query_id);
+            res?
+        }
+    };
+    state.saved_results.insert(query_id, bitmap.clone());
+    Ok(bitmap)
+}
+
+// helper fn to find vertices by their number of edges via label
+fn degree_bitmap(
+    source: &impl QuerySource,
+    dir: EdgeDirection,
+    label: PropertyHandle,
+    predicate: DegreePredicate,
+    state: &SearchState<'_>,
+) -> Result<RoaringTreemap, LatticeError> {
+    let degrees = source.degrees(dir, label.0, state)?;
+    let mut res: RoaringTreemap = degrees
+        .iter()
+        .filter(|(_, degree)| predicate.matches(**degree))
+        .map(|(vertex, _)| *vertex)
+        .collect();
+
+    // vertices without edges of the label are not in the index
+    if predicate.matches(0) {
+        let mut missing = source.all_vertices()?;
+        for (i, vertex) in degrees.keys().enumerate() {
+            if i % CHECK_INTERVAL == 0 {
+                state.check()?;
+            }
+            missing.remove(*vertex);
+        }
+        res |= missing;
+    }
+    Ok(res)
+}
+
+// helper fn to union the neighbors of every id via label
+fn expand(
+    source: &impl QuerySource,
+    dir: EdgeDirection,
+    ids: impl Iterator<Item = u64>,
+    label: PropertyHandle,
+    state: &SearchState<'_>,
+) -> Result<RoaringTreemap, LatticeError> {
+    let mut res = RoaringTreemap::new();
+    for (i, id) in ids.enumerate() {
+        if i % CHECK_INTERVAL == 0 {
+            state.check()?;
+        }
+        if let Some(connected_nodes) = source.edges(dir, (id, label.0))? {
+            res |= connected_nodes;
+        }
+    }
+    Ok(res)
+}
+
+// helper fn to collect every vertex reached from start within max_depth hops
+// * stops early once a hop reaches nothing new, so cycles end the traversal
+fn traverse(
+    source: &impl QuerySource,
+    dir: EdgeDirection,
+    start: &RoaringTreemap,
+    label: PropertyHandle,
+    max_depth: u64,
+    state: &SearchState<'_>,
+) -> Result<RoaringTreemap, LatticeError> {
+    let mut reached = RoaringTreemap::new();
+    let mut frontier = start.clone();
+    for _ in 0..max_depth {
+        let mut next = expand(source, dir, frontier.iter(), label, state)?;
+        next -= &reached;
+        if next.is_empty() {
+            break;
+        }
+        reached |= &next;
+        frontier = next;
+    }
+    Ok(reached)
+}
+
+// helper fn to estimate the vertices reached within max_depth hops, at most cap
+// * every hop is assumed to fan out like the first one
+fn estimate_traversal(
+    source: &impl QuerySource,
+    dir: EdgeDirection,
+    target: &CountEstimate,
+    label: PropertyHandle,
+    max_depth: u64,
+    cap: u64,
+) -> Result<u64, LatticeError> {
+    let first = estimate_expansion(source, dir, target, label, u64::MAX)?;
+    if first == 0 || max_depth == 0 {
+        return Ok(0);
+    }
+
+    // geometric sum of the layers, float casts saturate so unbounded depths stay finite
+    let ratio = first as f64 / target.len().max(1) as f64;
+    let depth = max_depth as f64;
+    let count = if ratio == 1.0 {
+        first as f64 * depth
+    } else {
+        first as f64 * (ratio.powf(depth) - 1.0) / (ratio - 1.0)
+    };
+    Ok((count as u64).min(cap))
+}
+
+// helper fn to estimate the neighbors of a node from a sample of its vertices
+// * estimated targets are assumed to have one neighbor each
+fn estimate_expansion(
+    source: &impl QuerySource,
+    dir: EdgeDirection,
+    target: &CountEstimate,
+    label: PropertyHandle,
+    max_targets: u64,
+) -> Result<u64, LatticeError> {
+    let targets = target.len().min(max_targets);
+    let Some(ids) = target.exact() else {
+        return Ok(targets);
+    };
+
+    let mut sampled = 0;
+    let mut neighbors = 0;
+    for id in ids.iter().take(targets.min(ESTIMATE_SAMPLE) as usize) {
+        sampled += 1;
+        if let Some(bitmap) = source.edges(dir, (id, label.0))? {
+            neighbors += bitmap.len();
+        }
+    }
+    if sampled == 0 {
+        return Ok(0);
     }
+    Ok(neighbors.saturating_mul(targets) / sampled)
 }
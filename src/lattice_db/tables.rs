@@ -7,18 +7,41 @@ pub const SEQ_VERTEX_ID: u8 = 2;
 pub const SEQ_PROPERTY_ID: u8 = 3;
 pub const SEQ_QUERY_ID: u8 = 4;
 
+// METADATA (u64 Settings)
+pub const METADATA: TableDefinition<u8, u64> = TableDefinition::new("_lattice_meta");
+pub const META_INT_ENCODING: u8 = 1;
+pub const META_FORMAT_VERSION: u8 = 2;
+pub const META_RUN_OPTIMIZE: u8 = 3;
+
 // STORAGE (Blob)
 // GraphId -> PreparedGraph (encoded)
 pub const GRAPHS: TableDefinition<u64, Vec<u8>> = TableDefinition::new("_lattice_graphs");
+// GraphId -> Metadata
+pub const GRAPH_METAS: TableDefinition<u64, Vec<u8>> = TableDefinition::new("_lattice_graph_metas");
+// GraphId -> () for graphs whose vertex attributes are only stored inline
+pub const INLINE_GRAPHS: TableDefinition<u64, ()> = TableDefinition::new("_lattice_inline_graphs");
+// (PropertyId, GraphId) -> () for inline graphs with a vertex attribute of the property
+pub const INLINE_PROPS: TableDefinition<(u64, u64), ()> =
+    TableDefinition::new("_lattice_inline_props");
 // VertexId -> GraphId
 pub const VERTEX_GRAPH_MAP: TableDefinition<u64, u64> =
     TableDefinition::new("_lattice_vert_graph_map");
 
+// TEXT INTERNING
+// Text -> TextId, for properties that intern their text values
+pub const INTERNED_TEXT: TableDefinition<&str, u64> =
+    TableDefinition::new("_lattice_interned_text");
+// TextId -> Text
+pub const INTERNED_IDS: TableDefinition<u64, &str> = TableDefinition::new("_lattice_interned_ids");
+
 // PROPERTIES
 // PropertyId -> Metadata
 pub const PROPERTIES: TableDefinition<u64, Vec<u8>> = TableDefinition::new("_lattice_props");
 // PropertyName -> PropertyId
 pub const PROP_NAMES: TableDefinition<&str, u64> = TableDefinition::new("_lattice_prop_name_to_id");
+// (PropertyId, PropertyName) -> (), every alias of a property
+pub const PROP_IDS: TableDefinition<(u64, &str), ()> =
+    TableDefinition::new("_lattice_prop_id_to_name");
 
 // QUERIES
 // QueryId -> PreparedQuery (encoded)
@@ -32,9 +55,18 @@ pub const QUERY_METAS: TableDefinition<u64, Vec<u8>> = TableDefinition::new("_la
 // Scalar: (PropertyId, ValueHash) -> VertexId
 pub const INDEX_SCALAR: TableDefinition<(u64, u64), Vec<u8>> =
     TableDefinition::new("_lattice_idx_s");
+// Text: (PropertyId, Text) -> VertexId, sorted for prefix scans
+pub const INDEX_TEXT: TableDefinition<(u64, String), Vec<u8>> =
+    TableDefinition::new("_lattice_idx_t");
 // Forward: (from VertexId, PropertyId) -> to VertexId
 pub const INDEX_FORWARD: TableDefinition<(u64, u64), Vec<u8>> =
     TableDefinition::new("_lattice_idx_f");
 // Reverse: (to VertexId, PropertyId) -> from VertexId
 pub const INDEX_REVERSE: TableDefinition<(u64, u64), Vec<u8>> =
     TableDefinition::new("_lattice_idx_r");
+// Edge attribute forward: (Label, PropertyId, ValueHash, from VertexId) -> to VertexId
+pub const INDEX_EDGE_ATTR_FWD: TableDefinition<(u64, u64, u64, u64), Vec<u8>> =
+    TableDefinition::new("_lattice_idx_ea_f");
+// Edge attribute reverse: (Label, PropertyId, ValueHash, to VertexId) -> from VertexId
+pub const INDEX_EDGE_ATTR_REV: TableDefinition<(u64, u64, u64, u64), Vec<u8>> =
+    TableDefinition::new("_lattice_idx_ea_r");
@@ -1,35 +1,116 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap, hash_map::Entry},
+    hash::Hash,
+    mem,
+    time::{Duration, Instant},
+};
 
+use bincode::Encode;
 use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
-use redb::{ReadableTable, TableDefinition, WriteTransaction};
+use redb::{ReadableTable, Table, TableDefinition, WriteTransaction};
 use roaring::RoaringTreemap;
 
 use crate::{
+    PreparedQuery,
+    codec::{self, IntEncoding},
     errors::LatticeError,
     graph::{
         graph_builder::GraphBuilder,
         graph_prepared::{GraphCommitData, PreparedGraph},
     },
-    lattice_db::tables::{
-        GRAPHS, INDEX_FORWARD, INDEX_REVERSE, INDEX_SCALAR, SEQ_GRAPH_ID, SEQ_PROPERTY_ID,
-        SEQ_QUERY_ID, SEQ_VERTEX_ID, SEQUENCES, VERTEX_GRAPH_MAP,
+    lattice_db::{
+        db::WriteGuard,
+        reader::{
+            QuerySource, SearchState, decode_bitmap, evaluate_query, mapped_vertices, read_bitmap,
+            read_bitmap_range, read_prefix_range, scan_degrees,
+        },
+        tables::{
+            GRAPH_METAS, GRAPHS, INDEX_EDGE_ATTR_FWD, INDEX_EDGE_ATTR_REV, INDEX_FORWARD,
+            INDEX_REVERSE, INDEX_SCALAR, INDEX_TEXT, INLINE_GRAPHS, INLINE_PROPS, INTERNED_IDS,
+            INTERNED_TEXT, SEQ_GRAPH_ID, SEQ_PROPERTY_ID, SEQ_QUERY_ID, SEQ_VERTEX_ID, SEQUENCES,
+            VERTEX_GRAPH_MAP,
+        },
     },
+    query::{query_builder::EdgeDirection, query_save::QueryHandle},
+    values::Primitive,
 };
 
+#[cfg(feature = "testing")]
+use crate::properties::PropertyHandle;
+
+/// Statistics gathered by a writer, reported to the metrics hook on commit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CommitMetrics {
+    /// Number of graphs saved.
+    pub graphs_saved: u64,
+    /// Time spent computing graph changes.
+    pub diff_time: Duration,
+    /// Number of index bitmaps that had to be loaded from the database.
+    pub cache_misses: u64,
+    /// Bytes written to the scalar index.
+    pub scalar_bytes: u64,
+    /// Bytes written to the forward edge index.
+    pub forward_bytes: u64,
+    /// Bytes written to the reverse edge index.
+    pub reverse_bytes: u64,
+    /// Time spent writing the index caches.
+    pub write_time: Duration,
+}
+
+// advances a sequence by count, returning the first id of the range
+pub(crate) fn take_ids(cursor: &mut u64, count: u64) -> Result<u64, LatticeError> {
+    let id = *cursor;
+    *cursor = id
+        .checked_add(count)
+        .ok_or(LatticeError::IdSpaceExhausted)?;
+    Ok(id)
+}
+
+// tuple keys of the bitmap index tables
+pub(crate) trait IndexKey:
+    redb::Key + for<'a> redb::Value<SelfType<'a> = Self> + Hash + Ord + Clone + 'static
+{
+}
+
+impl<K> IndexKey for K where
+    K: redb::Key + for<'a> redb::Value<SelfType<'a> = K> + Hash + Ord + Clone + 'static
+{
+}
+
+const DEFAULT_PARALLEL_THRESHOLD: usize = 4;
+
+type MetricsHook = Box<dyn Fn(CommitMetrics) + Send + Sync>;
+
 pub struct LatticeWriter {
     pub(crate) wt: WriteTransaction,
-    graph_id_cursor: u64,
-    vertex_id_cursor: u64,
+    pub(crate) encoding: IntEncoding,
+    pub(crate) graph_id_cursor: u64,
+    pub(crate) vertex_id_cursor: u64,
     pub(crate) property_id_cursor: u64,
     pub(crate) query_id_cursor: u64,
+    check_integrity: bool,
+    map_vertices: bool,
+    run_optimize: bool,
+    parallel_threshold: usize,
+    metrics: CommitMetrics,
+    metrics_hook: Option<MetricsHook>,
+    _guard: WriteGuard,
 
     scalar_cache: HashMap<(u64, u64), RoaringTreemap>,
+    text_cache: HashMap<(u64, String), RoaringTreemap>,
     forward_cache: HashMap<(u64, u64), RoaringTreemap>,
     reverse_cache: HashMap<(u64, u64), RoaringTreemap>,
+    edge_attr_fwd_cache: HashMap<(u64, u64, u64, u64), RoaringTreemap>,
+    edge_attr_rev_cache: HashMap<(u64, u64, u64, u64), RoaringTreemap>,
 }
 
 impl LatticeWriter {
-    pub(crate) fn new(wt: WriteTransaction) -> Result<Self, LatticeError> {
+    pub(crate) fn new(
+        wt: WriteTransaction,
+        encoding: IntEncoding,
+        run_optimize: bool,
+        guard: WriteGuard,
+    ) -> Result<Self, LatticeError> {
         let graph_id_cursor;
         let vertex_id_cursor;
         let property_id_cursor;
@@ -49,27 +130,96 @@ impl LatticeWriter {
         }
         Ok(Self {
             wt,
+            encoding,
             graph_id_cursor,
             vertex_id_cursor,
             property_id_cursor,
             query_id_cursor,
+            check_integrity: cfg!(debug_assertions),
+            map_vertices: true,
+            run_optimize,
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+            metrics: CommitMetrics::default(),
+            metrics_hook: None,
+            _guard: guard,
             scalar_cache: HashMap::new(),
+            text_cache: HashMap::new(),
             forward_cache: HashMap::new(),
             reverse_cache: HashMap::new(),
+            edge_attr_fwd_cache: HashMap::new(),
+            edge_attr_rev_cache: HashMap::new(),
         })
     }
 
+    /// Enable or disable edge integrity checks on saved graphs.
+    /// * Enabled by default in debug builds.
+    /// * Inconsistent graphs return an error instead of panicking during the save.
+    pub fn set_integrity_checks(&mut self, enabled: bool) {
+        self.check_integrity = enabled;
+    }
+
+    /// Enable or disable recording which graph each new vertex belongs to.
+    /// * Enabled by default, disabling it makes saving ephemeral graphs faster and smaller.
+    /// * Vertices saved while disabled are unknown to `get_graph_ids_from_vertices`, `search_records`,
+    ///   and anything else that finds a graph from a vertex id.
+    pub fn set_vertex_graph_mapping(&mut self, enabled: bool) {
+        self.map_vertices = enabled;
+    }
+
+    /// Set the minimum number of graphs saved at once before saving in parallel.
+    /// * Defaults to 4, smaller batches are faster without the thread pool.
+    pub fn set_parallel_threshold(&mut self, threshold: usize) {
+        self.parallel_threshold = threshold;
+    }
+
+    /// Set a hook that receives the writer's metrics once it commits.
+    /// * Checkpoints report and reset the metrics, so every report only covers its own transaction.
+    pub fn set_metrics_hook(&mut self, hook: impl Fn(CommitMetrics) + Send + Sync + 'static) {
+        self.metrics_hook = Some(Box::new(hook));
+    }
+
     /// Takes in a number of ids needed and returns the starting global id of the range.
-    fn reserve_vertex_ids(&mut self, count: u64) -> u64 {
-        let id = self.vertex_id_cursor;
-        self.vertex_id_cursor += count;
-        id
+    fn reserve_vertex_ids(&mut self, count: u64) -> Result<u64, LatticeError> {
+        take_ids(&mut self.vertex_id_cursor, count)
     }
 
     pub fn save_graphs_parallel(
         &mut self,
         builders: Vec<GraphBuilder>,
     ) -> Result<(), LatticeError> {
+        self.save_graphs(builders)?;
+        Ok(())
+    }
+
+    /// Save graphs along with metadata for each graph.
+    /// * Graphs without metadata keep any metadata they were saved with before.
+    pub fn save_graphs_with_meta<M: Encode>(
+        &mut self,
+        graphs: Vec<(GraphBuilder, Option<M>)>,
+    ) -> Result<(), LatticeError> {
+        let (builders, metas): (Vec<_>, Vec<_>) = graphs.into_iter().unzip();
+        let graph_ids = self.save_graphs(builders)?;
+        let mut meta_table = self.wt.open_table(GRAPH_METAS)?;
+        for (graph_id, meta) in graph_ids.into_iter().zip(metas) {
+            if let Some(meta) = meta {
+                meta_table.insert(graph_id, codec::encode_to_vec(meta, self.encoding)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    // saves graphs, returning the id of each graph
+    pub(crate) fn save_graphs(
+        &mut self,
+        builders: Vec<GraphBuilder>,
+    ) -> Result<Vec<u64>, LatticeError> {
+        // validate every graph before any id or table is touched
+        if self.check_integrity {
+            for b in &builders {
+                b.check_edge_integrity()?;
+            }
+        }
+
         // reserve ids
         let mut new_vertex_count = vec![];
         let mut ids = Vec::with_capacity(builders.len());
@@ -78,125 +228,617 @@ impl LatticeWriter {
                 {
                     let count = b.count_new_vertices();
                     new_vertex_count.push(count);
-                    self.reserve_vertex_ids(count)
+                    self.reserve_vertex_ids(count)?
                 }, // ids for vertexes
                 if let Some(old_graph_data) = &b.old_graph_data {
                     // id for graph
                     old_graph_data.id
                 } else {
-                    let id = self.graph_id_cursor;
-                    self.graph_id_cursor += 1;
-                    id
+                    take_ids(&mut self.graph_id_cursor, 1)?
                 },
             ));
         }
 
+        let graph_ids = ids.iter().map(|(_, graph_id)| *graph_id).collect();
+        let inline_modes: Vec<bool> = builders.iter().map(|b| b.inline_attributes).collect();
+        let text_ids = self.intern_text(&builders)?;
+
         // open tables
         let mut graph_table = self.wt.open_table(GRAPHS)?;
         let mut vg_map_table = self.wt.open_table(VERTEX_GRAPH_MAP)?;
+        let mut inline_table = self.wt.open_table(INLINE_GRAPHS)?;
+        let mut inline_props = self.wt.open_table(INLINE_PROPS)?;
+        let dictionary = self.wt.open_table(INTERNED_IDS)?;
 
         // add new vertices to graph mappings
-        for (idx, (start_id, graph_id)) in ids.iter().enumerate() {
-            for v_id in *start_id..start_id + new_vertex_count[idx] {
-                vg_map_table.insert(v_id, *graph_id)?;
+        if self.map_vertices {
+            for (idx, (start_id, graph_id)) in ids.iter().enumerate() {
+                for v_id in *start_id..start_id + new_vertex_count[idx] {
+                    vg_map_table.insert(v_id, *graph_id)?;
+                }
             }
         }
 
         // possible future impl
         // let auto_queries = self.load_all_saved_queries()?;
         let auto_queries = vec![];
+        let encoding = self.encoding;
 
         // get graph data for every graph
-        let commit_data: Vec<Result<GraphCommitData, LatticeError>> = builders
-            .into_par_iter()
-            .zip(ids.into_par_iter())
-            .map(|(builder, (start_id, graph_id))| {
-                PreparedGraph::commit_data_from_builder(builder, start_id, graph_id, &auto_queries)
-            })
-            .collect();
+        let diff_start = Instant::now();
+        let prepare = |(builder, (start_id, graph_id)): (GraphBuilder, (u64, u64))| {
+            PreparedGraph::commit_data_from_builder(
+                builder,
+                start_id,
+                graph_id,
+                encoding,
+                &text_ids,
+                &auto_queries,
+            )
+        };
+        // small batches skip the thread pool
+        let commit_data: Vec<Result<GraphCommitData, LatticeError>> =
+            if builders.len() < self.parallel_threshold {
+                builders.into_iter().zip(ids).map(prepare).collect()
+            } else {
+                builders
+                    .into_par_iter()
+                    .zip(ids.into_par_iter())
+                    .map(prepare)
+                    .collect()
+            };
+        self.metrics.diff_time += diff_start.elapsed();
+        self.metrics.graphs_saved += commit_data.len() as u64;
 
         // update cache with the graph changes
-        for result in commit_data {
-            let data = result?;
+        let mut changes = Vec::with_capacity(commit_data.len());
+        for (result, inline) in commit_data.into_iter().zip(inline_modes) {
+            let mut data = result?;
+
+            // inline graphs keep their attributes out of the scalar index
+            let was_inline = inline_table.get(data.graph_id)?.is_some();
+            if was_inline || inline {
+                let old_graph = match graph_table.get(data.graph_id)? {
+                    Some(bytes) => Some(PreparedGraph::decode(
+                        &bytes.value(),
+                        self.encoding,
+                        &dictionary,
+                    )?),
+                    None => None,
+                };
+                let new_graph =
+                    PreparedGraph::decode(&data.prepared_graph, self.encoding, &dictionary)?;
+                match (was_inline, inline) {
+                    (true, true) => {
+                        data.add_attrs.clear();
+                        data.rem_attrs.clear();
+                    }
+                    (false, true) => {
+                        data.add_attrs.clear();
+                        data.rem_attrs = old_graph
+                            .as_ref()
+                            .map(|graph| graph.attr_entries())
+                            .unwrap_or_default();
+                        inline_table.insert(data.graph_id, ())?;
+                    }
+                    (true, false) => {
+                        data.rem_attrs.clear();
+                        data.add_attrs = new_graph.attr_entries();
+                        inline_table.remove(data.graph_id)?;
+                    }
+                    (false, false) => {}
+                }
+
+                // inline graphs are listed under the properties they hold, so scans can skip the rest
+                if was_inline && let Some(old_graph) = &old_graph {
+                    for property in old_graph.properties() {
+                        inline_props.remove((property, data.graph_id))?;
+                    }
+                }
+                if inline {
+                    for property in new_graph.properties() {
+                        inline_props.insert((property, data.graph_id), ())?;
+                    }
+                }
+            }
 
             // add graph
-            graph_table.insert(data.graph_id, data.prepared_graph)?;
+            graph_table.insert(data.graph_id, mem::take(&mut data.prepared_graph))?;
 
             // remove vertex to graph mappings
-            for v_id in data.deleted_vertices {
+            for v_id in mem::take(&mut data.deleted_vertices) {
                 vg_map_table.remove(v_id)?;
             }
+            changes.push(data);
+        }
+        drop(graph_table);
+        drop(vg_map_table);
+        drop(inline_table);
+        drop(inline_props);
+        drop(dictionary);
 
-            // cache changes to the indexes
-            for (vertex, property, hash) in data.add_attrs {
-                Self::update_bitmap(
-                    &self.wt,
-                    &mut self.scalar_cache,
-                    INDEX_SCALAR,
-                    (property, hash),
-                    vertex,
-                    true,
-                )?;
+        // cache changes to the indexes
+        for data in changes {
+            self.cache_changes(data)?;
+        }
+        Ok(graph_ids)
+    }
+
+    // helper fn to assign dictionary ids to the text values of interned properties
+    fn intern_text(
+        &mut self,
+        builders: &[GraphBuilder],
+    ) -> Result<HashMap<String, u64>, LatticeError> {
+        let mut text_table = self.wt.open_table(INTERNED_TEXT)?;
+        let mut id_table = self.wt.open_table(INTERNED_IDS)?;
+        let mut next_id = id_table.last()?.map(|(id, _)| id.value() + 1).unwrap_or(0);
+        let mut text_ids = HashMap::new();
+        for builder in builders {
+            for (_, vertex) in builder.vertices.iter() {
+                for (attr, value) in &vertex.attributes {
+                    let Primitive::Text(text) = value else {
+                        continue;
+                    };
+                    if !attr.options().intern_text || text_ids.contains_key(text) {
+                        continue;
+                    }
+                    let stored = text_table.get(text.as_str())?.map(|v| v.value());
+                    let id = match stored {
+                        Some(id) => id,
+                        None => {
+                            let id = take_ids(&mut next_id, 1)?;
+                            text_table.insert(text.as_str(), id)?;
+                            id_table.insert(id, text.as_str())?;
+                            id
+                        }
+                    };
+                    text_ids.insert(text.clone(), id);
+                }
             }
-            for (vertex, property, hash) in data.rem_attrs {
+        }
+        Ok(text_ids)
+    }
+
+    /// Delete a graph along with its vertices, edges, and index entries.
+    /// * Returns an error if the graph does not exist.
+    pub fn delete_graph(&mut self, graph_id: u64) -> Result<(), LatticeError> {
+        self.delete_graphs(&[graph_id])
+    }
+
+    /// Delete graphs along with their vertices, edges, and index entries.
+    /// * Returns an error if a graph does not exist, the writer should then be dropped without committing.
+    pub fn delete_graphs(&mut self, ids: &[u64]) -> Result<(), LatticeError> {
+        let mut changes = Vec::with_capacity(ids.len());
+        {
+            let mut graph_table = self.wt.open_table(GRAPHS)?;
+            let mut vg_map_table = self.wt.open_table(VERTEX_GRAPH_MAP)?;
+            let mut meta_table = self.wt.open_table(GRAPH_METAS)?;
+            let mut inline_table = self.wt.open_table(INLINE_GRAPHS)?;
+            let mut inline_props = self.wt.open_table(INLINE_PROPS)?;
+            let dictionary = self.wt.open_table(INTERNED_IDS)?;
+            for graph_id in ids {
+                let bytes = graph_table
+                    .remove(graph_id)?
+                    .ok_or(LatticeError::GraphNotFound)?
+                    .value();
+                let graph = PreparedGraph::decode(&bytes, self.encoding, &dictionary)?;
+                meta_table.remove(graph_id)?;
+                let inline = inline_table.remove(graph_id)?.is_some();
+                if inline {
+                    for property in graph.properties() {
+                        inline_props.remove((property, *graph_id))?;
+                    }
+                }
+
+                let mut data = graph.removal_commit_data(inline);
+                for v_id in mem::take(&mut data.deleted_vertices) {
+                    vg_map_table.remove(v_id)?;
+                }
+                changes.push(data);
+            }
+        }
+
+        // cache changes to the indexes
+        for data in changes {
+            self.cache_changes(data)?;
+        }
+        Ok(())
+    }
+
+    // helper fn to cache the index changes of a graph
+    fn cache_changes(&mut self, data: GraphCommitData) -> Result<(), LatticeError> {
+        // removes go first, an edge or attribute both removed and added must stay indexed
+        for (vertex, property, hash, text) in data.rem_attrs {
+            Self::update_bitmap(
+                &self.wt,
+                &mut self.metrics,
+                &mut self.scalar_cache,
+                INDEX_SCALAR,
+                (property, hash),
+                vertex,
+                false,
+            )?;
+            if let Some(text) = text {
                 Self::update_bitmap(
                     &self.wt,
-                    &mut self.scalar_cache,
-                    INDEX_SCALAR,
-                    (property, hash),
+                    &mut self.metrics,
+                    &mut self.text_cache,
+                    INDEX_TEXT,
+                    (property, text),
                     vertex,
                     false,
                 )?;
             }
-            for (from, label, to) in data.add_edges {
-                Self::update_bitmap(
-                    &self.wt,
-                    &mut self.forward_cache,
-                    INDEX_FORWARD,
-                    (from, label),
-                    to,
-                    true,
-                )?;
+        }
+        for (vertex, property, hash, text) in data.add_attrs {
+            Self::update_bitmap(
+                &self.wt,
+                &mut self.metrics,
+                &mut self.scalar_cache,
+                INDEX_SCALAR,
+                (property, hash),
+                vertex,
+                true,
+            )?;
+            if let Some(text) = text {
                 Self::update_bitmap(
                     &self.wt,
-                    &mut self.reverse_cache,
-                    INDEX_REVERSE,
-                    (to, label),
-                    from,
+                    &mut self.metrics,
+                    &mut self.text_cache,
+                    INDEX_TEXT,
+                    (property, text),
+                    vertex,
                     true,
                 )?;
             }
-            for (from, label, to) in data.rem_edges {
-                Self::update_bitmap(
-                    &self.wt,
-                    &mut self.forward_cache,
-                    INDEX_FORWARD,
-                    (from, label),
-                    to,
-                    false,
-                )?;
-                Self::update_bitmap(
-                    &self.wt,
-                    &mut self.reverse_cache,
-                    INDEX_REVERSE,
-                    (to, label),
-                    from,
-                    false,
-                )?;
+        }
+        for (from, label, to) in data.rem_edges {
+            Self::update_bitmap(
+                &self.wt,
+                &mut self.metrics,
+                &mut self.forward_cache,
+                INDEX_FORWARD,
+                (from, label),
+                to,
+                false,
+            )?;
+            Self::update_bitmap(
+                &self.wt,
+                &mut self.metrics,
+                &mut self.reverse_cache,
+                INDEX_REVERSE,
+                (to, label),
+                from,
+                false,
+            )?;
+        }
+        for (from, label, to) in data.add_edges {
+            Self::update_bitmap(
+                &self.wt,
+                &mut self.metrics,
+                &mut self.forward_cache,
+                INDEX_FORWARD,
+                (from, label),
+                to,
+                true,
+            )?;
+            Self::update_bitmap(
+                &self.wt,
+                &mut self.metrics,
+                &mut self.reverse_cache,
+                INDEX_REVERSE,
+                (to, label),
+                from,
+                true,
+            )?;
+        }
+        for (label, attr, hash, from, to) in data.rem_edge_attrs {
+            Self::update_bitmap(
+                &self.wt,
+                &mut self.metrics,
+                &mut self.edge_attr_fwd_cache,
+                INDEX_EDGE_ATTR_FWD,
+                (label, attr, hash, from),
+                to,
+                false,
+            )?;
+            Self::update_bitmap(
+                &self.wt,
+                &mut self.metrics,
+                &mut self.edge_attr_rev_cache,
+                INDEX_EDGE_ATTR_REV,
+                (label, attr, hash, to),
+                from,
+                false,
+            )?;
+        }
+        for (label, attr, hash, from, to) in data.add_edge_attrs {
+            Self::update_bitmap(
+                &self.wt,
+                &mut self.metrics,
+                &mut self.edge_attr_fwd_cache,
+                INDEX_EDGE_ATTR_FWD,
+                (label, attr, hash, from),
+                to,
+                true,
+            )?;
+            Self::update_bitmap(
+                &self.wt,
+                &mut self.metrics,
+                &mut self.edge_attr_rev_cache,
+                INDEX_EDGE_ATTR_REV,
+                (label, attr, hash, to),
+                from,
+                true,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Search the data as it will be once this writer commits.
+    /// * Sees graphs saved by this writer along with the committed ones.
+    pub fn search_pending(&self, query: &PreparedQuery) -> Result<Vec<u64>, LatticeError> {
+        let source = PendingSource::new(self)?;
+        let bitmap = evaluate_query(&source, query, &mut SearchState::default())?;
+        Ok(bitmap.into_iter().collect())
+    }
+
+    // removes every attribute index entry of a property, pending or committed
+    pub(crate) fn purge_attr_index(&mut self, property: u64) -> Result<(), LatticeError> {
+        self.scalar_cache.retain(|(prop, _), _| *prop != property);
+        self.text_cache.retain(|(prop, _), _| *prop != property);
+        self.edge_attr_fwd_cache
+            .retain(|(_, prop, _, _), _| *prop != property);
+        self.edge_attr_rev_cache
+            .retain(|(_, prop, _, _), _| *prop != property);
+        self.wt
+            .open_table(INDEX_SCALAR)?
+            .retain_in((property, 0)..=(property, u64::MAX), |_, _| false)?;
+        self.wt.open_table(INDEX_TEXT)?.retain_in(
+            (property, String::new())..(property + 1, String::new()),
+            |_, _| false,
+        )?;
+        // edge attribute keys start with the label, so the whole index is scanned
+        self.wt
+            .open_table(INDEX_EDGE_ATTR_FWD)?
+            .retain(|(_, prop, _, _), _| prop != property)?;
+        self.wt
+            .open_table(INDEX_EDGE_ATTR_REV)?
+            .retain(|(_, prop, _, _), _| prop != property)?;
+        // inline graphs are only scanned for the properties they are listed under
+        self.wt
+            .open_table(INLINE_PROPS)?
+            .retain_in((property, 0)..=(property, u64::MAX), |_, _| false)?;
+        Ok(())
+    }
+
+    /// Delete every graph and its index entries, keeping properties and saved queries.
+    /// * Graph and vertex ids start from 0 again.
+    pub fn clear_graphs(&mut self) -> Result<(), LatticeError> {
+        self.scalar_cache.clear();
+        self.text_cache.clear();
+        self.forward_cache.clear();
+        self.reverse_cache.clear();
+        self.edge_attr_fwd_cache.clear();
+        self.edge_attr_rev_cache.clear();
+
+        self.wt.open_table(GRAPHS)?.retain(|_, _| false)?;
+        self.wt.open_table(GRAPH_METAS)?.retain(|_, _| false)?;
+        self.wt.open_table(INLINE_GRAPHS)?.retain(|_, _| false)?;
+        self.wt.open_table(INLINE_PROPS)?.retain(|_, _| false)?;
+        self.wt.open_table(VERTEX_GRAPH_MAP)?.retain(|_, _| false)?;
+        self.wt.open_table(INDEX_SCALAR)?.retain(|_, _| false)?;
+        self.wt.open_table(INDEX_TEXT)?.retain(|_, _| false)?;
+        self.wt.open_table(INDEX_FORWARD)?.retain(|_, _| false)?;
+        self.wt.open_table(INDEX_REVERSE)?.retain(|_, _| false)?;
+        self.wt
+            .open_table(INDEX_EDGE_ATTR_FWD)?
+            .retain(|_, _| false)?;
+        self.wt
+            .open_table(INDEX_EDGE_ATTR_REV)?
+            .retain(|_, _| false)?;
+
+        self.graph_id_cursor = 0;
+        self.vertex_id_cursor = 0;
+        Ok(())
+    }
+
+    /// Move a graph to a new graph id, keeping its vertex ids.
+    /// * Future auto-assigned ids will be greater than the new id.
+    /// * If the new id is taken, returns an error.
+    pub fn change_graph_id(&mut self, old_id: u64, new_id: u64) -> Result<(), LatticeError> {
+        let mut graph_table = self.wt.open_table(GRAPHS)?;
+        if old_id == new_id {
+            return match graph_table.get(old_id)? {
+                Some(_) => Ok(()),
+                None => Err(LatticeError::GraphNotFound),
+            };
+        }
+        if graph_table.get(new_id)?.is_some() {
+            return Err(LatticeError::GraphIdTaken);
+        }
+
+        // the graph stores its own id
+        let bytes = graph_table
+            .remove(old_id)?
+            .ok_or(LatticeError::GraphNotFound)?
+            .value();
+        let dictionary = self.wt.open_table(INTERNED_IDS)?;
+        let mut graph = PreparedGraph::decode(&bytes, self.encoding, &dictionary)?;
+        let vertex_ids: Vec<u64> = graph.vertices.iter().map(|v| v.id).collect();
+        let properties = graph.properties();
+        graph.id = new_id;
+        let text_table = self.wt.open_table(INTERNED_TEXT)?;
+        graph_table.insert(new_id, graph.encode(self.encoding, &text_table)?)?;
+
+        // point the vertices at the new id
+        let mut vg_map_table = self.wt.open_table(VERTEX_GRAPH_MAP)?;
+        for vertex_id in vertex_ids {
+            if vg_map_table.get(vertex_id)?.is_some() {
+                vg_map_table.insert(vertex_id, new_id)?;
             }
         }
+
+        // move the graph's settings
+        let mut meta_table = self.wt.open_table(GRAPH_METAS)?;
+        let meta = meta_table.remove(old_id)?.map(|m| m.value());
+        if let Some(meta) = meta {
+            meta_table.insert(new_id, meta)?;
+        }
+        let mut inline_table = self.wt.open_table(INLINE_GRAPHS)?;
+        if inline_table.remove(old_id)?.is_some() {
+            inline_table.insert(new_id, ())?;
+            let mut inline_props = self.wt.open_table(INLINE_PROPS)?;
+            for property in properties {
+                inline_props.remove((property, old_id))?;
+                inline_props.insert((property, new_id), ())?;
+            }
+        }
+
+        if new_id >= self.graph_id_cursor {
+            self.graph_id_cursor = new_id
+                .checked_add(1)
+                .ok_or(LatticeError::IdSpaceExhausted)?;
+        }
         Ok(())
     }
 
+    /// Return every `(from, label, to)` edge found in only one of the forward and reverse indexes.
+    /// * Only checks committed data, changes made by this writer are not seen.
+    pub fn verify_edge_index_symmetry(&self) -> Result<Vec<(u64, u64, u64)>, LatticeError> {
+        let forward = self.wt.open_table(INDEX_FORWARD)?;
+        let reverse = self.wt.open_table(INDEX_REVERSE)?;
+        let mut asymmetric = vec![];
+        for (from, label, to) in Self::missing_mirrors(&forward, &reverse)? {
+            asymmetric.push((from, label, to));
+        }
+        for (to, label, from) in Self::missing_mirrors(&reverse, &forward)? {
+            asymmetric.push((from, label, to));
+        }
+        asymmetric.sort_unstable();
+        Ok(asymmetric)
+    }
+
+    // helper fn to find `(a, label, b)` entries of one index without `(b, label, a)` in the other
+    fn missing_mirrors(
+        index: &Table<(u64, u64), Vec<u8>>,
+        mirror: &Table<(u64, u64), Vec<u8>>,
+    ) -> Result<Vec<(u64, u64, u64)>, LatticeError> {
+        let mut mirror_cache = HashMap::new();
+        let mut missing = vec![];
+        for entry in index.iter()? {
+            let (key, bytes) = entry?;
+            let (a, label) = key.value();
+            for b in decode_bitmap(&bytes.value())? {
+                let mirrored = match mirror_cache.entry((b, label)) {
+                    Entry::Occupied(e) => e.into_mut(),
+                    Entry::Vacant(e) => e.insert(match mirror.get((b, label))? {
+                        Some(bytes) => decode_bitmap(&bytes.value())?,
+                        None => RoaringTreemap::new(),
+                    }),
+                };
+                if !mirrored.contains(a) {
+                    missing.push((a, label, b));
+                }
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Rebuild the forward, reverse, and edge attribute indexes from the stored graphs.
+    /// * Discards any edge changes made by this writer that were not saved as graphs.
+    pub fn repair_edge_indexes(&mut self) -> Result<(), LatticeError> {
+        self.forward_cache.clear();
+        self.reverse_cache.clear();
+        self.edge_attr_fwd_cache.clear();
+        self.edge_attr_rev_cache.clear();
+        self.wt.open_table(INDEX_FORWARD)?.retain(|_, _| false)?;
+        self.wt.open_table(INDEX_REVERSE)?.retain(|_, _| false)?;
+        self.wt
+            .open_table(INDEX_EDGE_ATTR_FWD)?
+            .retain(|_, _| false)?;
+        self.wt
+            .open_table(INDEX_EDGE_ATTR_REV)?
+            .retain(|_, _| false)?;
+
+        // the rebuilt indexes are written on commit
+        let graph_table = self.wt.open_table(GRAPHS)?;
+        let dictionary = self.wt.open_table(INTERNED_IDS)?;
+        for entry in graph_table.iter()? {
+            let (_, bytes) = entry?;
+            let graph = PreparedGraph::decode(&bytes.value(), self.encoding, &dictionary)?;
+            for (label, attr, hash, from, to) in graph.edge_attr_entries() {
+                self.edge_attr_fwd_cache
+                    .entry((label, attr, hash, from))
+                    .or_default()
+                    .insert(to);
+                self.edge_attr_rev_cache
+                    .entry((label, attr, hash, to))
+                    .or_default()
+                    .insert(from);
+            }
+            for edge in graph.edges {
+                self.forward_cache
+                    .entry((edge.from, edge.label.0))
+                    .or_default()
+                    .insert(edge.to);
+                self.reverse_cache
+                    .entry((edge.to, edge.label.0))
+                    .or_default()
+                    .insert(edge.from);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a vertex to a scalar index entry without changing any graph.
+    /// * Lets tests simulate two values colliding on the same hash.
+    #[cfg(feature = "testing")]
+    pub fn insert_scalar_entry(
+        &mut self,
+        attr: PropertyHandle,
+        value_hash: u64,
+        vertex: u64,
+    ) -> Result<(), LatticeError> {
+        Self::update_bitmap(
+            &self.wt,
+            &mut self.metrics,
+            &mut self.scalar_cache,
+            INDEX_SCALAR,
+            (attr.0, value_hash),
+            vertex,
+            true,
+        )
+    }
+
+    /// Add a vertex to the materialized matches of a saved query.
+    /// * Lets tests seed matches without evaluating the query.
+    #[cfg(feature = "testing")]
+    pub fn insert_query_match(&mut self, query_id: u64, vertex: u64) -> Result<(), LatticeError> {
+        Self::update_bitmap(
+            &self.wt,
+            &mut self.metrics,
+            &mut self.scalar_cache,
+            INDEX_SCALAR,
+            (crate::properties::QUERY_MATCH, query_id),
+            vertex,
+            true,
+        )
+    }
+
     // helper fn to update cache bitmap
-    fn update_bitmap(
+    fn update_bitmap<K>(
         wt: &WriteTransaction,
-        cache: &mut HashMap<(u64, u64), RoaringTreemap>,
-        table_def: redb::TableDefinition<(u64, u64), Vec<u8>>,
-        key: (u64, u64),
+        metrics: &mut CommitMetrics,
+        cache: &mut HashMap<K, RoaringTreemap>,
+        table_def: TableDefinition<K, Vec<u8>>,
+        key: K,
         id: u64,
         is_add: bool,
-    ) -> Result<(), LatticeError> {
+    ) -> Result<(), LatticeError>
+    where
+        K: IndexKey,
+    {
         // cache hit
         if let Some(bitmap) = cache.get_mut(&key) {
             if is_add {
@@ -208,11 +850,11 @@ impl LatticeWriter {
         }
 
         // cache miss, load from db or create a new bitmap
+        metrics.cache_misses += 1;
         let mut bitmap = {
             let table = wt.open_table(table_def)?;
-            if let Some(bytes) = table.get(key)? {
-                RoaringTreemap::deserialize_from(&bytes.value()[..])
-                    .map_err(|e| bincode::error::EncodeError::OtherString(e.to_string()))?
+            if let Some(bytes) = table.get(&key)? {
+                decode_bitmap(&bytes.value())?
             } else {
                 RoaringTreemap::new()
             }
@@ -226,44 +868,291 @@ impl LatticeWriter {
         Ok(())
     }
 
-    pub fn commit(self) -> Result<(), LatticeError> {
-        Self::commit_cache(&self.wt, self.scalar_cache, INDEX_SCALAR)?;
-        Self::commit_cache(&self.wt, self.forward_cache, INDEX_FORWARD)?;
-        Self::commit_cache(&self.wt, self.reverse_cache, INDEX_REVERSE)?;
-        {
-            let mut seq_table = self.wt.open_table(SEQUENCES)?;
-            seq_table.insert(SEQ_GRAPH_ID, self.graph_id_cursor)?;
-            seq_table.insert(SEQ_VERTEX_ID, self.vertex_id_cursor)?;
-            seq_table.insert(SEQ_PROPERTY_ID, self.property_id_cursor)?;
-            seq_table.insert(SEQ_QUERY_ID, self.query_id_cursor)?;
-        }
+    pub fn commit(mut self) -> Result<(), LatticeError> {
+        self.flush()?;
         self.wt.commit()?;
+        if let Some(hook) = self.metrics_hook {
+            hook(self.metrics);
+        }
         Ok(())
     }
 
-    // writes cache to the table
-    fn commit_cache(
+    // commits everything written so far, then continues in the transaction from begin
+    // * the write guard is kept, so no other writer can start in between
+    pub(crate) fn checkpoint(
+        mut self,
+        begin: impl FnOnce() -> Result<WriteTransaction, LatticeError>,
+    ) -> Result<Self, LatticeError> {
+        self.flush()?;
+        let LatticeWriter {
+            wt,
+            encoding,
+            check_integrity,
+            map_vertices,
+            run_optimize,
+            parallel_threshold,
+            metrics,
+            metrics_hook,
+            _guard,
+            ..
+        } = self;
+        wt.commit()?;
+        if let Some(hook) = &metrics_hook {
+            hook(metrics);
+        }
+
+        // cursors are reloaded from the sequences that were just committed, metrics start over
+        let mut writer = Self::new(begin()?, encoding, run_optimize, _guard)?;
+        writer.check_integrity = check_integrity;
+        writer.map_vertices = map_vertices;
+        writer.parallel_threshold = parallel_threshold;
+        writer.metrics_hook = metrics_hook;
+        Ok(writer)
+    }
+
+    // helper fn to write the index caches and id sequences into the transaction
+    fn flush(&mut self) -> Result<(), LatticeError> {
+        let write_start = Instant::now();
+        let optimize = self.run_optimize;
+        self.metrics.scalar_bytes += Self::commit_cache(
+            &self.wt,
+            mem::take(&mut self.scalar_cache),
+            INDEX_SCALAR,
+            optimize,
+        )?;
+        self.metrics.forward_bytes += Self::commit_cache(
+            &self.wt,
+            mem::take(&mut self.forward_cache),
+            INDEX_FORWARD,
+            optimize,
+        )?;
+        self.metrics.reverse_bytes += Self::commit_cache(
+            &self.wt,
+            mem::take(&mut self.reverse_cache),
+            INDEX_REVERSE,
+            optimize,
+        )?;
+        Self::commit_cache(
+            &self.wt,
+            mem::take(&mut self.text_cache),
+            INDEX_TEXT,
+            optimize,
+        )?;
+        Self::commit_cache(
+            &self.wt,
+            mem::take(&mut self.edge_attr_fwd_cache),
+            INDEX_EDGE_ATTR_FWD,
+            optimize,
+        )?;
+        Self::commit_cache(
+            &self.wt,
+            mem::take(&mut self.edge_attr_rev_cache),
+            INDEX_EDGE_ATTR_REV,
+            optimize,
+        )?;
+        self.metrics.write_time += write_start.elapsed();
+
+        let mut seq_table = self.wt.open_table(SEQUENCES)?;
+        seq_table.insert(SEQ_GRAPH_ID, self.graph_id_cursor)?;
+        seq_table.insert(SEQ_VERTEX_ID, self.vertex_id_cursor)?;
+        seq_table.insert(SEQ_PROPERTY_ID, self.property_id_cursor)?;
+        seq_table.insert(SEQ_QUERY_ID, self.query_id_cursor)?;
+        Ok(())
+    }
+
+    // writes cache to the table, returns the number of bytes written
+    pub(crate) fn commit_cache<K>(
         wt: &WriteTransaction,
-        cache: HashMap<(u64, u64), RoaringTreemap>,
-        table_def: TableDefinition<(u64, u64), Vec<u8>>,
-    ) -> Result<(), LatticeError> {
+        cache: HashMap<K, RoaringTreemap>,
+        table_def: TableDefinition<K, Vec<u8>>,
+        optimize: bool,
+    ) -> Result<u64, LatticeError>
+    where
+        K: IndexKey,
+    {
+        let mut written = 0;
         if cache.is_empty() {
-            return Ok(());
+            return Ok(written);
         }
         let mut table = wt.open_table(table_def)?;
-        let mut keys: Vec<_> = cache.keys().collect();
-        keys.sort_unstable(); // prevent disk thrashing
-        for key in keys {
-            let bitmap = cache.get(key).unwrap();
+        let mut entries: Vec<_> = cache.into_iter().collect();
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b)); // prevent disk thrashing
+        for (key, mut bitmap) in entries {
+            if optimize {
+                bitmap.optimize(); // run containers for contiguous ids
+            }
             let mut bytes = Vec::new();
             bitmap.serialize_into(&mut bytes)?;
             // if bitmap is empty, remove it from the db
             if bitmap.is_empty() {
-                table.remove(key)?;
+                table.remove(&key)?;
             } else {
-                table.insert(key, bytes)?;
+                written += bytes.len() as u64;
+                table.insert(&key, bytes)?;
             }
         }
-        Ok(())
+        Ok(written)
+    }
+}
+
+// reads the tables of a writer, preferring the bitmaps changed in its caches
+struct PendingSource<'a> {
+    writer: &'a LatticeWriter,
+    table_scl: Table<'a, (u64, u64), Vec<u8>>,
+    table_txt: Table<'a, (u64, String), Vec<u8>>,
+    table_fwd: Table<'a, (u64, u64), Vec<u8>>,
+    table_rev: Table<'a, (u64, u64), Vec<u8>>,
+    table_attr_fwd: Table<'a, (u64, u64, u64, u64), Vec<u8>>,
+    table_attr_rev: Table<'a, (u64, u64, u64, u64), Vec<u8>>,
+}
+
+impl<'a> PendingSource<'a> {
+    fn new(writer: &'a LatticeWriter) -> Result<Self, LatticeError> {
+        Ok(Self {
+            writer,
+            table_scl: writer.wt.open_table(INDEX_SCALAR)?,
+            table_txt: writer.wt.open_table(INDEX_TEXT)?,
+            table_fwd: writer.wt.open_table(INDEX_FORWARD)?,
+            table_rev: writer.wt.open_table(INDEX_REVERSE)?,
+            table_attr_fwd: writer.wt.open_table(INDEX_EDGE_ATTR_FWD)?,
+            table_attr_rev: writer.wt.open_table(INDEX_EDGE_ATTR_REV)?,
+        })
+    }
+}
+
+impl QuerySource for PendingSource<'_> {
+    fn scalar(&self, key: (u64, u64)) -> Result<Option<RoaringTreemap>, LatticeError> {
+        match self.writer.scalar_cache.get(&key) {
+            Some(bitmap) => Ok(Some(bitmap.clone())),
+            None => read_bitmap(&self.table_scl, key),
+        }
+    }
+
+    fn scalar_range(
+        &self,
+        attr: u64,
+        start: u64,
+        end: u64,
+    ) -> Result<BTreeMap<u64, RoaringTreemap>, LatticeError> {
+        let mut bitmaps = read_bitmap_range(&self.table_scl, attr, start, end)?;
+        for ((prop, hash), bitmap) in &self.writer.scalar_cache {
+            if *prop == attr && (start..end).contains(hash) {
+                bitmaps.insert(*hash, bitmap.clone());
+            }
+        }
+        Ok(bitmaps)
+    }
+
+    fn text_prefix(
+        &self,
+        attr: u64,
+        prefix: &str,
+    ) -> Result<BTreeMap<String, RoaringTreemap>, LatticeError> {
+        let mut bitmaps = read_prefix_range(&self.table_txt, attr, prefix)?;
+        for ((prop, text), bitmap) in &self.writer.text_cache {
+            if *prop == attr && text.starts_with(prefix) {
+                bitmaps.insert(text.clone(), bitmap.clone());
+            }
+        }
+        Ok(bitmaps)
+    }
+
+    fn edges(
+        &self,
+        dir: EdgeDirection,
+        key: (u64, u64),
+    ) -> Result<Option<RoaringTreemap>, LatticeError> {
+        let (cache, table) = match dir {
+            EdgeDirection::Outgoing => (&self.writer.forward_cache, &self.table_fwd),
+            EdgeDirection::Incoming => (&self.writer.reverse_cache, &self.table_rev),
+        };
+        match cache.get(&key) {
+            Some(bitmap) => Ok(Some(bitmap.clone())),
+            None => read_bitmap(table, key),
+        }
+    }
+
+    fn edge_attrs(
+        &self,
+        dir: EdgeDirection,
+        key: (u64, u64, u64, u64),
+    ) -> Result<Option<RoaringTreemap>, LatticeError> {
+        let (cache, table) = match dir {
+            EdgeDirection::Outgoing => (&self.writer.edge_attr_fwd_cache, &self.table_attr_fwd),
+            EdgeDirection::Incoming => (&self.writer.edge_attr_rev_cache, &self.table_attr_rev),
+        };
+        match cache.get(&key) {
+            Some(bitmap) => Ok(Some(bitmap.clone())),
+            None => read_bitmap(table, key),
+        }
+    }
+
+    fn inline_graphs(&self, property: u64) -> Result<Vec<PreparedGraph>, LatticeError> {
+        let inline_props = self.writer.wt.open_table(INLINE_PROPS)?;
+        let graph_table = self.writer.wt.open_table(GRAPHS)?;
+        let dictionary = self.writer.wt.open_table(INTERNED_IDS)?;
+        let mut graphs = vec![];
+        for entry in inline_props.range((property, 0)..=(property, u64::MAX))? {
+            let graph_id = entry?.0.value().1;
+            let bytes = graph_table
+                .get(graph_id)?
+                .ok_or(LatticeError::GraphNotFound)?
+                .value();
+            graphs.push(PreparedGraph::decode(
+                &bytes,
+                self.writer.encoding,
+                &dictionary,
+            )?);
+        }
+        Ok(graphs)
+    }
+
+    fn saved_query(&self, query_id: u64) -> Result<PreparedQuery, LatticeError> {
+        self.writer.get_prepared_query(QueryHandle(query_id))
+    }
+
+    fn degrees(
+        &self,
+        dir: EdgeDirection,
+        label: u64,
+        state: &SearchState<'_>,
+    ) -> Result<HashMap<u64, u64>, LatticeError> {
+        let (cache, table) = match dir {
+            EdgeDirection::Outgoing => (&self.writer.forward_cache, &self.table_fwd),
+            EdgeDirection::Incoming => (&self.writer.reverse_cache, &self.table_rev),
+        };
+        let mut degrees = scan_degrees(table, label, state)?;
+        for ((vertex, edge_label), bitmap) in cache {
+            if *edge_label != label {
+                continue;
+            }
+            if bitmap.is_empty() {
+                degrees.remove(vertex);
+            } else {
+                degrees.insert(*vertex, bitmap.len());
+            }
+        }
+        Ok(degrees)
+    }
+
+    fn all_vertices(&self) -> Result<RoaringTreemap, LatticeError> {
+        mapped_vertices(&self.writer.wt.open_table(VERTEX_GRAPH_MAP)?)
+    }
+
+    fn graph_of(&self, vertex: u64) -> Result<Option<u64>, LatticeError> {
+        let table = self.writer.wt.open_table(VERTEX_GRAPH_MAP)?;
+        Ok(table.get(vertex)?.map(|v| v.value()))
+    }
+
+    fn graph(&self, graph_id: u64) -> Result<PreparedGraph, LatticeError> {
+        let bytes = self
+            .writer
+            .wt
+            .open_table(GRAPHS)?
+            .get(graph_id)?
+            .ok_or(LatticeError::GraphNotFound)?
+            .value();
+        let dictionary = self.writer.wt.open_table(INTERNED_IDS)?;
+        PreparedGraph::decode(&bytes, self.writer.encoding, &dictionary)
     }
 }
@@ -1,9 +1,16 @@
 mod lattice_db;
-pub use lattice_db::db::LatticeDb;
-pub use lattice_db::reader::LatticeReader;
-pub use lattice_db::writer::LatticeWriter;
+pub use lattice_db::db::{DbOptions, FORMAT_VERSION, LatticeDb};
+pub use lattice_db::merge::IdRemap;
+pub use lattice_db::reader::{
+    EdgeDump, GraphDump, LatticeReader, PreloadedIndex, VertexAttributes, VertexDump, VertexRecord,
+};
+pub use lattice_db::writer::{CommitMetrics, LatticeWriter};
 
 mod errors;
+pub use errors::LatticeError;
+
+mod codec;
+pub use codec::IntEncoding;
 
 mod graph;
 pub use graph::graph_builder;
@@ -12,9 +19,10 @@ pub use graph::graph_builder::GraphBuilder;
 mod query;
 pub use query::query_builder;
 pub use query::query_builder::QueryBuilder;
-pub use query::query_prepared::PreparedQuery;
+pub use query::query_prepared::{PreparedQuery, PreparedQueryBuilder};
 
 mod utils;
+pub use utils::generational_vector::Handle;
 pub use utils::values;
 
 mod props;
@@ -22,3 +30,4 @@ pub use props::properties;
 
 pub use bincode;
 pub use bincode::{Decode, Encode};
+pub use roaring;
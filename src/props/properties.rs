@@ -1,18 +1,192 @@
-use bincode::{Decode, Encode, config};
+use std::hash::{Hash, Hasher};
+
+use bincode::{
+    Decode, Encode,
+    de::Decoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+};
 use redb::ReadableTable;
+use unicode_normalization::UnicodeNormalization;
 
 use crate::{
     LatticeReader, LatticeWriter,
+    codec::{self, IntEncoding},
     errors::LatticeError,
-    lattice_db::tables::{PROP_NAMES, PROPERTIES},
+    lattice_db::tables::{PROP_IDS, PROP_NAMES, PROPERTIES},
+    lattice_db::writer::take_ids,
+    values::Primitive,
 };
 
 pub(crate) const QUERY_MATCH: u64 = u64::MAX;
 
+// option flags, stored in the property's metadata row and next to its id in graphs
+const PROP_CASE_INSENSITIVE: u8 = 1 << 0;
+const PROP_NORMALIZE_WHITESPACE: u8 = 1 << 1;
+const PROP_UNICODE_NFC: u8 = 1 << 2;
+const PROP_INTERN_TEXT: u8 = 1 << 3;
+
+// marks a stored attribute whose value is an interned text id, never set on a property id
+pub(crate) const INTERNED_VALUE: u64 = 1 << 63;
+
 pub(crate) type PropertyId = u64;
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Encode, Decode)]
-pub struct PropertyHandle(pub(crate) PropertyId);
+/// Reference to a registered property.
+/// * Carries the options the property was registered with, handles are compared by id only.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PropertyHandle(pub(crate) PropertyId, pub(crate) PropertyOptions);
+
+impl PartialEq for PropertyHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for PropertyHandle {}
+
+impl Hash for PropertyHandle {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+// stored as the id followed by the option flags
+impl Encode for PropertyHandle {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.0.encode(encoder)?;
+        self.1.to_bits().encode(encoder)
+    }
+}
+
+impl<Context> Decode<Context> for PropertyHandle {
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let id = Decode::decode(decoder)?;
+        let bits: u8 = Decode::decode(decoder)?;
+        Ok(Self(id, PropertyOptions::from_bits(bits)))
+    }
+}
+
+bincode::impl_borrow_decode!(PropertyHandle);
+
+// a PROPERTIES row, the options are fixed and the metadata can be replaced
+#[derive(Encode, Decode)]
+pub(crate) struct PropertyRow {
+    pub(crate) options: u8,
+    pub(crate) meta: Vec<u8>,
+}
+
+/// Options that change how a property's values are matched.
+/// * Options are fixed when the property is registered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PropertyOptions {
+    /// Text values match regardless of case.
+    pub case_insensitive: bool,
+    /// Text values match after trimming and collapsing whitespace into single spaces.
+    pub normalize_whitespace: bool,
+    /// Text values match after Unicode NFC normalization.
+    pub unicode_nfc: bool,
+    /// Vertex text values are stored once in a shared dictionary, graphs only store their id.
+    /// * Shrinks graphs that repeat the same text, dictionary entries are never removed.
+    pub intern_text: bool,
+}
+
+impl PropertyOptions {
+    pub(crate) fn to_bits(self) -> u8 {
+        let mut bits = 0;
+        if self.case_insensitive {
+            bits |= PROP_CASE_INSENSITIVE;
+        }
+        if self.normalize_whitespace {
+            bits |= PROP_NORMALIZE_WHITESPACE;
+        }
+        if self.unicode_nfc {
+            bits |= PROP_UNICODE_NFC;
+        }
+        if self.intern_text {
+            bits |= PROP_INTERN_TEXT;
+        }
+        bits
+    }
+
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        Self {
+            case_insensitive: bits & PROP_CASE_INSENSITIVE != 0,
+            normalize_whitespace: bits & PROP_NORMALIZE_WHITESPACE != 0,
+            unicode_nfc: bits & PROP_UNICODE_NFC != 0,
+            intern_text: bits & PROP_INTERN_TEXT != 0,
+        }
+    }
+
+    fn changes_text(&self) -> bool {
+        self.case_insensitive || self.normalize_whitespace || self.unicode_nfc
+    }
+
+    // applies every enabled text option
+    fn normalize_text(&self, text: &str) -> String {
+        let mut text = if self.unicode_nfc {
+            text.nfc().collect()
+        } else {
+            text.to_string()
+        };
+        if self.normalize_whitespace {
+            text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        if self.case_insensitive {
+            text = text.to_lowercase();
+        }
+        text
+    }
+}
+
+impl PropertyHandle {
+    /// Returns the options the property was registered with.
+    pub fn options(&self) -> PropertyOptions {
+        self.1
+    }
+
+    // a handle to a property id without its options, for ids read back from an index
+    pub(crate) fn plain(id: PropertyId) -> Self {
+        Self(id, PropertyOptions::default())
+    }
+
+    // true if two values of this property are equal once the options are applied
+    pub(crate) fn values_match(&self, a: &Primitive, b: &Primitive) -> bool {
+        let options = self.options();
+        match (a, b) {
+            (Primitive::Text(a), Primitive::Text(b)) if options.changes_text() => {
+                options.normalize_text(a) == options.normalize_text(b)
+            }
+            _ => a == b,
+        }
+    }
+
+    // the text kept in the sorted text index, with the property options applied
+    pub(crate) fn index_text(&self, value: &Primitive) -> Option<String> {
+        match value {
+            Primitive::Text(t) => Some(self.options().normalize_text(t)),
+            _ => None,
+        }
+    }
+
+    // a text prefix with the property options applied, to scan the text index with
+    pub(crate) fn normalize_prefix(&self, prefix: &str) -> String {
+        self.options().normalize_text(prefix)
+    }
+
+    /// Hashes a value of this property.
+    /// * Applies the property options before hashing, so equivalent values share a hash.
+    pub fn hash_value(&self, value: &Primitive) -> u64 {
+        let options = self.options();
+        match value {
+            Primitive::Text(t) if options.changes_text() => {
+                Primitive::Text(options.normalize_text(t)).hash()
+            }
+            _ => value.hash(),
+        }
+    }
+}
 
 impl LatticeWriter {
     /// Register a property and return the handle.
@@ -27,12 +201,147 @@ impl LatticeWriter {
         A: Into<Option<&'a str>>,
         M: Encode,
     {
-        // incr id
-        let id = self.property_id_cursor;
-        self.property_id_cursor += 1;
+        self.register_property_with_options(alias, meta, PropertyOptions::default())
+    }
 
+    /// Register a property with matching options and return the handle.
+    /// * If an alias is provided, creates a fast property lookup.
+    /// * If the alias is taken, returns an error.
+    pub fn register_property_with_options<'a, A, M>(
+        &mut self,
+        alias: A,
+        meta: &M,
+        options: PropertyOptions,
+    ) -> Result<PropertyHandle, LatticeError>
+    where
+        A: Into<Option<&'a str>>,
+        M: Encode,
+    {
+        // incr id, the sequence can't reach the interned value marker
+        let id = take_ids(&mut self.property_id_cursor, 1)?;
+        if id & INTERNED_VALUE != 0 {
+            return Err(LatticeError::IdSpaceExhausted);
+        }
+        self.write_property(PropertyHandle(id, options), alias.into(), meta)
+    }
+
+    /// Register a property with a specific id and return the handle.
+    /// * Future auto-assigned ids will be greater than the given id.
+    /// * If the id or alias is taken, returns an error.
+    /// * Ids with the top bit set are reserved and return an error.
+    pub fn register_property_with_id<'a, A, M>(
+        &mut self,
+        id: u64,
+        alias: A,
+        meta: &M,
+        options: PropertyOptions,
+    ) -> Result<PropertyHandle, LatticeError>
+    where
+        A: Into<Option<&'a str>>,
+        M: Encode,
+    {
+        if id & INTERNED_VALUE != 0 {
+            return Err(LatticeError::PropertyIdTaken);
+        } // reserved for interned values and saved query matches
+        if self.wt.open_table(PROPERTIES)?.get(id)?.is_some() {
+            return Err(LatticeError::PropertyIdTaken);
+        }
+
+        let handle = self.write_property(PropertyHandle(id, options), alias.into(), meta)?;
+        if id >= self.property_id_cursor {
+            self.property_id_cursor = id + 1;
+        }
+        Ok(handle)
+    }
+
+    /// Add another alias for an existing property.
+    /// * Every alias of a property resolves to the same handle.
+    /// * If the alias is taken, returns an error.
+    pub fn add_property_alias(
+        &mut self,
+        handle: PropertyHandle,
+        alias: &str,
+    ) -> Result<(), LatticeError> {
+        if self.wt.open_table(PROPERTIES)?.get(handle.0)?.is_none() {
+            return Err(LatticeError::PropertyNotFound);
+        }
+        let mut name_table = self.wt.open_table(PROP_NAMES)?;
+        if name_table.get(alias)?.is_some() {
+            return Err(LatticeError::AliasAlreadyExists);
+        }
+        name_table.insert(alias, handle.0)?;
+        self.wt
+            .open_table(PROP_IDS)?
+            .insert((handle.0, alias), ())?;
+        Ok(())
+    }
+
+    /// Overwrite the metadata of an existing property.
+    /// * The property keeps its id and aliases.
+    pub fn update_property_meta<M: Encode>(
+        &mut self,
+        handle: PropertyHandle,
+        meta: &M,
+    ) -> Result<(), LatticeError> {
+        let mut meta_table = self.wt.open_table(PROPERTIES)?;
+        let mut row = read_property_row(&meta_table, handle.0, self.encoding)?
+            .ok_or(LatticeError::PropertyNotFound)?;
+        row.meta = codec::encode_to_vec(meta, self.encoding)?;
+        meta_table.insert(handle.0, codec::encode_to_vec(&row, self.encoding)?)?;
+        Ok(())
+    }
+
+    /// Delete a property along with its aliases and attribute index entries.
+    /// * The aliases can be registered again afterwards.
+    /// * Saved graphs keep their values and edges for the property, they are no longer matched by attribute queries.
+    /// * If the property does not exist, returns an error.
+    pub fn delete_property(&mut self, handle: PropertyHandle) -> Result<(), LatticeError> {
+        if self.wt.open_table(PROPERTIES)?.remove(handle.0)?.is_none() {
+            return Err(LatticeError::PropertyNotFound);
+        }
+
+        // remove every alias
+        {
+            let mut id_table = self.wt.open_table(PROP_IDS)?;
+            let mut name_table = self.wt.open_table(PROP_NAMES)?;
+            for entry in id_table.range((handle.0, "")..)? {
+                let (key, _) = entry?;
+                let (id, name) = key.value();
+                if id != handle.0 {
+                    break;
+                }
+                name_table.remove(name)?;
+            }
+            id_table.retain_in((handle.0, "")..(handle.0 + 1, ""), |_, _| false)?;
+        }
+
+        self.purge_attr_index(handle.0)
+    }
+
+    /// Get the first alias of a property in alphabetical order.
+    pub fn get_property_alias(
+        &self,
+        handle: PropertyHandle,
+    ) -> Result<Option<String>, LatticeError> {
+        first_alias(&self.wt.open_table(PROP_IDS)?, handle.0)
+    }
+
+    /// Name the property of a `NumberTooBig` error by its alias, other errors are returned unchanged.
+    /// * Builders only know property ids, use this to find the attribute behind their errors.
+    pub fn describe_error(&self, error: LatticeError) -> Result<LatticeError, LatticeError> {
+        let table = self.wt.open_table(PROP_IDS)?;
+        name_property(error, |id| first_alias(&table, id))
+    }
+
+    // helper fn to write the alias and metadata of a property
+    fn write_property<M: Encode>(
+        &mut self,
+        handle: PropertyHandle,
+        alias: Option<&str>,
+        meta: &M,
+    ) -> Result<PropertyHandle, LatticeError> {
+        let id = handle.0;
         // assign alias, prevents collisions
-        let alias = alias.into();
         if let Some(name) = alias {
             let mut name_table = self.wt.open_table(PROP_NAMES)?;
             if name_table.get(name)?.is_some() {
@@ -40,13 +349,63 @@ impl LatticeWriter {
             }
 
             name_table.insert(name, id)?;
+            self.wt.open_table(PROP_IDS)?.insert((id, name), ())?;
         }
 
         // write metadata
+        let row = PropertyRow {
+            options: handle.1.to_bits(),
+            meta: codec::encode_to_vec(meta, self.encoding)?,
+        };
         let mut meta_table = self.wt.open_table(PROPERTIES)?;
-        let meta_bytes = bincode::encode_to_vec(meta, config::standard())?;
-        meta_table.insert(id, meta_bytes)?;
-        Ok(PropertyHandle(id))
+        meta_table.insert(id, codec::encode_to_vec(&row, self.encoding)?)?;
+        Ok(handle)
+    }
+}
+
+// helper fn to read the alphabetically first alias of a property
+fn first_alias(
+    table: &impl ReadableTable<(u64, &'static str), ()>,
+    id: PropertyId,
+) -> Result<Option<String>, LatticeError> {
+    match table.range((id, "")..)?.next() {
+        Some(entry) => {
+            let (key, _) = entry?;
+            let (key_id, alias) = key.value();
+            Ok((key_id == id).then(|| alias.to_string()))
+        }
+        None => Ok(None),
+    }
+}
+
+// helper fn to fill in the alias of a NumberTooBig error
+fn name_property(
+    error: LatticeError,
+    alias_of: impl FnOnce(PropertyId) -> Result<Option<String>, LatticeError>,
+) -> Result<LatticeError, LatticeError> {
+    match error {
+        LatticeError::NumberTooBig {
+            value,
+            property: Some(id),
+            alias: None,
+        } => Ok(LatticeError::NumberTooBig {
+            value,
+            property: Some(id),
+            alias: alias_of(id)?,
+        }),
+        error => Ok(error),
+    }
+}
+
+// helper fn to read and decode a PROPERTIES row
+pub(crate) fn read_property_row(
+    table: &impl ReadableTable<u64, Vec<u8>>,
+    id: PropertyId,
+    encoding: IntEncoding,
+) -> Result<Option<PropertyRow>, LatticeError> {
+    match table.get(id)? {
+        Some(bytes) => Ok(Some(codec::decode_from_slice(&bytes.value(), encoding)?)),
+        None => Ok(None),
     }
 }
 
@@ -54,7 +413,35 @@ impl LatticeReader {
     /// Get a property handle from an alias.
     pub fn get_property_handle(&self, alias: &str) -> Result<Option<PropertyHandle>, LatticeError> {
         let table = self.rt.open_table(PROP_NAMES)?;
-        Ok(table.get(alias)?.map(|v| PropertyHandle(v.value())))
+        let Some(id) = table.get(alias)?.map(|v| v.value()) else {
+            return Ok(None);
+        };
+        self.property_handle(id)
+    }
+
+    // helper fn to build a handle for a property id with its stored options
+    pub(crate) fn property_handle(
+        &self,
+        id: PropertyId,
+    ) -> Result<Option<PropertyHandle>, LatticeError> {
+        let table = self.rt.open_table(PROPERTIES)?;
+        let row = read_property_row(&table, id, self.encoding)?;
+        Ok(row.map(|row| PropertyHandle(id, PropertyOptions::from_bits(row.options))))
+    }
+
+    /// Get the first alias of a property in alphabetical order.
+    pub fn get_property_alias(
+        &self,
+        handle: PropertyHandle,
+    ) -> Result<Option<String>, LatticeError> {
+        first_alias(&self.rt.open_table(PROP_IDS)?, handle.0)
+    }
+
+    /// Name the property of a `NumberTooBig` error by its alias, other errors are returned unchanged.
+    /// * Builders only know property ids, use this to find the attribute behind their errors.
+    pub fn describe_error(&self, error: LatticeError) -> Result<LatticeError, LatticeError> {
+        let table = self.rt.open_table(PROP_IDS)?;
+        name_property(error, |id| first_alias(&table, id))
     }
 
     /// Get the property metadata.
@@ -63,11 +450,9 @@ impl LatticeReader {
         handle: PropertyHandle,
     ) -> Result<M, LatticeError> {
         let table = self.rt.open_table(PROPERTIES)?;
-        let bytes = table
-            .get(handle.0)?
-            .ok_or_else(|| LatticeError::PropertyNotFound)?
-            .value();
-        let (meta, _) = bincode::decode_from_slice(&bytes, config::standard())?;
+        let row = read_property_row(&table, handle.0, self.encoding)?
+            .ok_or(LatticeError::PropertyNotFound)?;
+        let meta = codec::decode_from_slice(&row.meta, self.encoding)?;
         Ok(meta)
     }
 }
@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use bincode::{Decode, Encode};
 
 use crate::{
@@ -10,11 +12,32 @@ use crate::{
 };
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EdgeDirection {
     Outgoing,
     Incoming,
 }
 
+/// Compares the number of edges a vertex has.
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DegreePredicate {
+    Eq(u64),
+    Gt(u64),
+    Lt(u64),
+}
+
+impl DegreePredicate {
+    /// Returns true if the degree satisfies the predicate.
+    pub fn matches(&self, degree: u64) -> bool {
+        match self {
+            DegreePredicate::Eq(n) => degree == *n,
+            DegreePredicate::Gt(n) => degree > *n,
+            DegreePredicate::Lt(n) => degree < *n,
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct NodeHandle(pub(crate) Handle);
 
@@ -36,6 +59,61 @@ pub enum QueryNode {
     },
     // saved query
     SavedQuery(u64),
+    // search for pre-hashed values
+    AttributeHash {
+        attr: PropertyHandle,
+        value_hash: u64,
+    },
+    // search for linked nodes, reading at most max_targets vertices of target
+    EdgeLimited {
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        target: NodeHandle,
+        max_targets: u64,
+    },
+    // search for vertices by their number of edges
+    Degree {
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        predicate: DegreePredicate,
+    },
+    // search for linked nodes, following only edges with an attribute value
+    EdgeWhere {
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        target: NodeHandle,
+        attr: PropertyHandle,
+        value: Primitive,
+    },
+    // search for integer values in a range of hashes
+    Range {
+        attr: PropertyHandle,
+        start: u64,
+        end: u64,
+    },
+    // search for text values starting with a prefix
+    Prefix {
+        attr: PropertyHandle,
+        prefix: String,
+    },
+    // vertices of a graph not in child
+    Complement {
+        child: NodeHandle,
+        graph: u64,
+    },
+    // search for linked nodes, following up to max_depth edges
+    EdgeDepth {
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        target: NodeHandle,
+        max_depth: u64,
+    },
+    // search for linked nodes, following edges until nothing new is reached
+    Reachable {
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        target: NodeHandle,
+    },
 }
 
 impl QueryNode {
@@ -60,11 +138,46 @@ impl QueryBuilder {
         value: V,
     ) -> Result<NodeHandle, LatticeError> {
         let value = value.to_primitive();
-        value.verify()?;
+        value.verify_property(attr.0)?;
         let handle = self.nodes.add(QueryNode::Attribute { attr, value });
         Ok(NodeHandle(handle))
     }
 
+    /// Find vertices with an integer attribute value in the half-open range.
+    /// * Both bounds must be unsigned, or both signed, and only values of that kind match.
+    /// * Float bounds are refused, float hashes are not ordered.
+    pub fn match_range<V: Value>(
+        &mut self,
+        attr: PropertyHandle,
+        range: Range<V>,
+    ) -> Result<NodeHandle, LatticeError> {
+        let (start, end) =
+            Primitive::range_hashes(&range.start.to_primitive(), &range.end.to_primitive())
+                .ok_or(LatticeError::InvalidRange)?;
+        let handle = self.nodes.add(QueryNode::Range { attr, start, end });
+        Ok(NodeHandle(handle))
+    }
+
+    /// Find vertices with a text attribute value starting with the prefix.
+    /// * The property options apply to the prefix, so case-insensitive properties match any case.
+    /// * An empty prefix matches every vertex with a text value for the property.
+    pub fn match_prefix(&mut self, attr: PropertyHandle, prefix: &str) -> NodeHandle {
+        let handle = self.nodes.add(QueryNode::Prefix {
+            attr,
+            prefix: prefix.to_string(),
+        });
+        NodeHandle(handle)
+    }
+
+    /// Find vertices by an already hashed attribute value.
+    /// * The hash must come from `PropertyHandle::hash_value` of the same property, otherwise nothing will match.
+    pub fn match_attr_hash(&mut self, attr: PropertyHandle, value_hash: u64) -> NodeHandle {
+        let handle = self
+            .nodes
+            .add(QueryNode::AttributeHash { attr, value_hash });
+        NodeHandle(handle)
+    }
+
     /// All vertices that are pointed to by `subject` via label.
     pub fn match_outgoing(
         &mut self,
@@ -97,6 +210,143 @@ impl QueryBuilder {
         Ok(NodeHandle(handle))
     }
 
+    /// All vertices that are pointed to by `subject` via label.
+    /// * Only the first `max_targets` vertices of `subject` are expanded.
+    /// * Use `LatticeReader::search_partial` to know if the result was truncated.
+    pub fn match_outgoing_limited(
+        &mut self,
+        label: PropertyHandle,
+        subject: NodeHandle,
+        max_targets: u64,
+    ) -> Result<NodeHandle, LatticeError> {
+        self.nodes
+            .get(subject.0)
+            .ok_or(LatticeError::EdgeNotFound)?;
+        let handle = self.nodes.add(QueryNode::EdgeLimited {
+            dir: EdgeDirection::Outgoing,
+            label,
+            target: subject,
+            max_targets,
+        });
+        Ok(NodeHandle(handle))
+    }
+
+    /// All vertices that point to `target` via label.
+    /// * Only the first `max_targets` vertices of `target` are expanded.
+    /// * Use `LatticeReader::search_partial` to know if the result was truncated.
+    pub fn match_incoming_limited(
+        &mut self,
+        label: PropertyHandle,
+        target: NodeHandle,
+        max_targets: u64,
+    ) -> Result<NodeHandle, LatticeError> {
+        self.nodes.get(target.0).ok_or(LatticeError::EdgeNotFound)?;
+        let handle = self.nodes.add(QueryNode::EdgeLimited {
+            dir: EdgeDirection::Incoming,
+            label,
+            target,
+            max_targets,
+        });
+        Ok(NodeHandle(handle))
+    }
+
+    /// All vertices reachable from `subject` by following at most `max_depth` label edges.
+    /// * Vertices of `subject` are only included if they can be reached from one another.
+    pub fn match_outgoing_depth(
+        &mut self,
+        label: PropertyHandle,
+        subject: NodeHandle,
+        max_depth: u64,
+    ) -> Result<NodeHandle, LatticeError> {
+        self.nodes
+            .get(subject.0)
+            .ok_or(LatticeError::EdgeNotFound)?;
+        let handle = self.nodes.add(QueryNode::EdgeDepth {
+            dir: EdgeDirection::Outgoing,
+            label,
+            target: subject,
+            max_depth,
+        });
+        Ok(NodeHandle(handle))
+    }
+
+    /// All vertices that reach `target` by following at most `max_depth` label edges.
+    /// * Vertices of `target` are only included if they can reach one another.
+    pub fn match_incoming_depth(
+        &mut self,
+        label: PropertyHandle,
+        target: NodeHandle,
+        max_depth: u64,
+    ) -> Result<NodeHandle, LatticeError> {
+        self.nodes.get(target.0).ok_or(LatticeError::EdgeNotFound)?;
+        let handle = self.nodes.add(QueryNode::EdgeDepth {
+            dir: EdgeDirection::Incoming,
+            label,
+            target,
+            max_depth,
+        });
+        Ok(NodeHandle(handle))
+    }
+
+    /// All vertices reachable from `subject` by following label edges in the direction, at any depth.
+    /// * Cycles are followed once, vertices of `subject` are only included if they are reached again.
+    pub fn match_reachable(
+        &mut self,
+        label: PropertyHandle,
+        subject: NodeHandle,
+        dir: EdgeDirection,
+    ) -> Result<NodeHandle, LatticeError> {
+        self.nodes
+            .get(subject.0)
+            .ok_or(LatticeError::EdgeNotFound)?;
+        let handle = self.nodes.add(QueryNode::Reachable {
+            dir,
+            label,
+            target: subject,
+        });
+        Ok(NodeHandle(handle))
+    }
+
+    /// All vertices that are pointed to by `subject` via label, where the edge has the attribute value.
+    /// * Reads the edge attribute index, edges without the attribute never match.
+    pub fn match_outgoing_where<V: Value>(
+        &mut self,
+        label: PropertyHandle,
+        subject: NodeHandle,
+        edge_attr: PropertyHandle,
+        edge_value: V,
+    ) -> Result<NodeHandle, LatticeError> {
+        self.nodes
+            .get(subject.0)
+            .ok_or(LatticeError::EdgeNotFound)?;
+        let value = edge_value.to_primitive();
+        value.verify_property(edge_attr.0)?;
+        let handle = self.nodes.add(QueryNode::EdgeWhere {
+            dir: EdgeDirection::Outgoing,
+            label,
+            target: subject,
+            attr: edge_attr,
+            value,
+        });
+        Ok(NodeHandle(handle))
+    }
+
+    /// All vertices whose number of edges via label satisfies the predicate.
+    /// * Scans the edge index, and vertices without a graph mapping are only found with a degree above 0.
+    pub fn match_degree(
+        &mut self,
+        label: PropertyHandle,
+        dir: EdgeDirection,
+        predicate: DegreePredicate,
+    ) -> NodeHandle {
+        let handle = self.nodes.add(QueryNode::Degree {
+            dir,
+            label,
+            predicate,
+        });
+        NodeHandle(handle)
+    }
+
     /// Find a vertex that satisfies multiple features within children.
     pub fn group_and(&mut self, children: Vec<NodeHandle>) -> Result<NodeHandle, LatticeError> {
         for c in &children {
@@ -130,6 +380,40 @@ impl QueryBuilder {
         let handle = self.nodes.add(QueryNode::Difference(include, exclude));
         Ok(NodeHandle(handle))
     }
+
+    /// Find the vertices of a graph that do not satisfy child.
+    /// * Only vertices mapped to the graph are part of it, see `LatticeWriter::set_vertex_graph_mapping`.
+    pub fn complement(
+        &mut self,
+        child: NodeHandle,
+        within: u64,
+    ) -> Result<NodeHandle, LatticeError> {
+        self.nodes.get(child.0).ok_or(LatticeError::EdgeNotFound)?;
+        let handle = self.nodes.add(QueryNode::Complement {
+            child,
+            graph: within,
+        });
+        Ok(NodeHandle(handle))
+    }
+
+    /// All vertices matched by a saved query.
+    /// * Saved queries without materialized matches are evaluated during the search.
+    pub fn include_saved(&mut self, query_id: u64) -> NodeHandle {
+        NodeHandle(self.nodes.add(QueryNode::SavedQuery(query_id)))
+    }
+
+    /// All vertices two outgoing hops from `origin` via label, that are not one hop away or in `origin`.
+    /// * For example, friends of friends who are not already friends.
+    pub fn recommend(
+        &mut self,
+        label: PropertyHandle,
+        origin: NodeHandle,
+    ) -> Result<NodeHandle, LatticeError> {
+        let one_hop = self.match_outgoing(label, origin)?;
+        let two_hop = self.match_outgoing(label, one_hop)?;
+        let known = self.group_or(vec![origin, one_hop])?;
+        self.difference(two_hop, known)
+    }
 }
 
 impl Default for QueryBuilder {
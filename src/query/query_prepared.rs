@@ -1,8 +1,17 @@
 use std::collections::HashMap;
 
-use bincode::{Decode, Encode};
+use bincode::{
+    Decode, Encode,
+    de::Decoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+};
 
-use crate::{errors::LatticeError, properties::PropertyHandle};
+use crate::{
+    errors::LatticeError,
+    properties::PropertyHandle,
+    values::{Primitive, Value},
+};
 
 use super::query_builder::*;
 
@@ -27,12 +36,95 @@ pub(crate) enum Node {
     },
     // saved query
     SavedQuery(u64),
+    // search for linked nodes, reading at most max_targets vertices of target
+    EdgeLimited {
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        target: NodeIdx,
+        max_targets: u64,
+    },
+    // search for vertices by their number of edges
+    Degree {
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        predicate: DegreePredicate,
+    },
+    // search for linked nodes, following only edges with an attribute value
+    EdgeWhere {
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        target: NodeIdx,
+        attr: PropertyHandle,
+        value: u64, // hashed value
+    },
+    // search for integer values, by a half-open range of hashed values
+    Range {
+        attr: PropertyHandle,
+        start: u64,
+        end: u64,
+    },
+    // search for text values by prefix, the prefix is normalized like the indexed text
+    Prefix {
+        attr: PropertyHandle,
+        prefix: String,
+    },
+    // vertices mapped to a graph, minus the child
+    Complement {
+        child: NodeIdx,
+        graph: u64,
+    },
+    // search for linked nodes, following up to max_depth edges
+    EdgeDepth {
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        target: NodeIdx,
+        max_depth: u64,
+    },
+    // search for linked nodes, following edges until nothing new is reached
+    Reachable {
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        target: NodeIdx,
+    },
 }
 
-#[derive(Encode, Decode)]
 pub struct PreparedQuery {
     pub(crate) nodes: Vec<Node>,
     pub(crate) root: NodeIdx,
+    // original values of attribute nodes, kept in memory only
+    pub(crate) values: HashMap<NodeIdx, Primitive>,
+    pub(crate) verify_values: bool,
+}
+
+// only the nodes are stored, so saved queries keep their format
+impl Encode for PreparedQuery {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.nodes.encode(encoder)?;
+        self.root.encode(encoder)
+    }
+}
+
+impl<Context> Decode<Context> for PreparedQuery {
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(Self {
+            nodes: Decode::decode(decoder)?,
+            root: Decode::decode(decoder)?,
+            values: HashMap::new(),
+            verify_values: false,
+        })
+    }
+}
+
+bincode::impl_borrow_decode!(PreparedQuery);
+
+impl PreparedQuery {
+    /// Check attribute matches against the values stored in their graphs, dropping hash collisions.
+    /// * Slower, the graph of every candidate vertex is loaded.
+    /// * Only attributes matched by value are checked, pre-hashed attributes and saved queries are not.
+    /// * Vertices saved without a graph mapping can't be checked and are kept.
+    pub fn set_verify_values(&mut self, enabled: bool) {
+        self.verify_values = enabled;
+    }
 }
 
 impl QueryBuilder {
@@ -48,6 +140,7 @@ impl QueryBuilder {
         // overhead may not be worth it if query is optimized
         // removes nodes with different indices but exact match on children or attributes
         let mut dup_cache = HashMap::new();
+        let mut values = HashMap::new();
 
         for handle in build_order {
             let src_node = self
@@ -80,7 +173,7 @@ impl QueryBuilder {
                     Node::Difference(a, b)
                 }
                 QueryNode::Attribute { attr, value } => {
-                    let value_hash = value.hash();
+                    let value_hash = attr.hash_value(value);
                     Node::Attribute {
                         attr: *attr,
                         value: value_hash,
@@ -95,15 +188,96 @@ impl QueryBuilder {
                     }
                 }
                 QueryNode::SavedQuery(id) => Node::SavedQuery(*id),
+                QueryNode::AttributeHash { attr, value_hash } => Node::Attribute {
+                    attr: *attr,
+                    value: *value_hash,
+                },
+                QueryNode::EdgeLimited {
+                    dir,
+                    label,
+                    target,
+                    max_targets,
+                } => {
+                    let target_id = *visited.get(&target.0.index).unwrap();
+                    Node::EdgeLimited {
+                        dir: *dir,
+                        label: *label,
+                        target: target_id,
+                        max_targets: *max_targets,
+                    }
+                }
+                QueryNode::Degree {
+                    dir,
+                    label,
+                    predicate,
+                } => Node::Degree {
+                    dir: *dir,
+                    label: *label,
+                    predicate: *predicate,
+                },
+                QueryNode::EdgeWhere {
+                    dir,
+                    label,
+                    target,
+                    attr,
+                    value,
+                } => {
+                    let target_id = *visited.get(&target.0.index).unwrap();
+                    Node::EdgeWhere {
+                        dir: *dir,
+                        label: *label,
+                        target: target_id,
+                        attr: *attr,
+                        value: attr.hash_value(value),
+                    }
+                }
+                QueryNode::Range { attr, start, end } => Node::Range {
+                    attr: *attr,
+                    start: *start,
+                    end: *end,
+                },
+                QueryNode::Prefix { attr, prefix } => Node::Prefix {
+                    attr: *attr,
+                    prefix: attr.normalize_prefix(prefix),
+                },
+                QueryNode::Complement { child, graph } => Node::Complement {
+                    child: *visited.get(&child.0.index).unwrap(),
+                    graph: *graph,
+                },
+                QueryNode::EdgeDepth {
+                    dir,
+                    label,
+                    target,
+                    max_depth,
+                } => Node::EdgeDepth {
+                    dir: *dir,
+                    label: *label,
+                    target: *visited.get(&target.0.index).unwrap(),
+                    max_depth: *max_depth,
+                },
+                QueryNode::Reachable { dir, label, target } => Node::Reachable {
+                    dir: *dir,
+                    label: *label,
+                    target: *visited.get(&target.0.index).unwrap(),
+                },
             };
 
-            let idx = if let Some(&idx) = dup_cache.get(&compiled_node) {
-                idx
-            } else {
-                let new_idx = output.len();
-                dup_cache.insert(compiled_node.clone(), new_idx);
-                output.push(compiled_node);
-                new_idx
+            // colliding values share a node, but must stay apart for verification
+            let value = match src_node {
+                QueryNode::Attribute { value, .. } => Some(value),
+                _ => None,
+            };
+            let idx = match dup_cache.get(&compiled_node) {
+                Some(&idx) if values.get(&idx) == value => idx,
+                _ => {
+                    let new_idx = output.len();
+                    dup_cache.insert(compiled_node.clone(), new_idx);
+                    output.push(compiled_node);
+                    if let Some(value) = value {
+                        values.insert(new_idx, value.clone());
+                    }
+                    new_idx
+                }
             };
 
             // dedup visiting twice (diamond case)
@@ -114,6 +288,8 @@ impl QueryBuilder {
         Ok(PreparedQuery {
             nodes: output,
             root: root_idx,
+            values,
+            verify_values: false,
         })
     }
 
@@ -146,10 +322,19 @@ impl QueryBuilder {
                         QueryNode::Difference(a, b) => {
                             stack.extend_from_slice(&[(*a, false), (*b, false)]);
                         }
-                        QueryNode::Edge { target, .. } => {
+                        QueryNode::Edge { target, .. }
+                        | QueryNode::EdgeLimited { target, .. }
+                        | QueryNode::EdgeWhere { target, .. }
+                        | QueryNode::EdgeDepth { target, .. }
+                        | QueryNode::Reachable { target, .. }
+                        | QueryNode::Complement { child: target, .. } => {
                             stack.push((*target, false));
                         }
-                        QueryNode::Attribute { .. } => {}
+                        QueryNode::Attribute { .. }
+                        | QueryNode::AttributeHash { .. }
+                        | QueryNode::Degree { .. }
+                        | QueryNode::Range { .. }
+                        | QueryNode::Prefix { .. } => {}
                         QueryNode::SavedQuery(_) => {}
                     }
                 }
@@ -158,3 +343,322 @@ impl QueryBuilder {
         order
     }
 }
+
+impl PreparedQuery {
+    // points the query at new property, saved query, and graph ids, unmapped ids are kept
+    pub(crate) fn remap_ids(
+        &mut self,
+        properties: &HashMap<PropertyHandle, PropertyHandle>,
+        queries: &HashMap<u64, u64>,
+        graphs: &HashMap<u64, u64>,
+    ) {
+        for node in &mut self.nodes {
+            match node {
+                Node::Attribute { attr: prop, .. }
+                | Node::Edge { label: prop, .. }
+                | Node::EdgeLimited { label: prop, .. }
+                | Node::EdgeDepth { label: prop, .. }
+                | Node::Reachable { label: prop, .. }
+                | Node::Degree { label: prop, .. }
+                | Node::Range { attr: prop, .. }
+                | Node::Prefix { attr: prop, .. } => {
+                    if let Some(new) = properties.get(prop) {
+                        *prop = *new;
+                    }
+                }
+                Node::EdgeWhere { label, attr, .. } => {
+                    for prop in [label, attr] {
+                        if let Some(new) = properties.get(prop) {
+                            *prop = *new;
+                        }
+                    }
+                }
+                Node::SavedQuery(id) => {
+                    if let Some(new) = queries.get(id) {
+                        *id = *new;
+                    }
+                }
+                Node::Complement { graph, .. } => {
+                    if let Some(new) = graphs.get(graph) {
+                        *graph = *new;
+                    }
+                }
+                Node::Union(_) | Node::Intersect(_) | Node::Difference(..) => {}
+            }
+        }
+    }
+}
+
+/// Builds a PreparedQuery directly, for callers that plan queries themselves.
+/// * Every method returns the index of the new node, used to reference it from other nodes.
+/// * Nodes not reachable from the root are dropped when built.
+#[derive(Default)]
+pub struct PreparedQueryBuilder {
+    nodes: Vec<Node>,
+    values: HashMap<NodeIdx, Primitive>,
+}
+
+impl PreparedQuery {
+    /// Start building a PreparedQuery node by node.
+    pub fn builder() -> PreparedQueryBuilder {
+        PreparedQueryBuilder::default()
+    }
+}
+
+impl PreparedQueryBuilder {
+    fn push(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Vertices in any of the children.
+    pub fn union(&mut self, children: Vec<usize>) -> usize {
+        self.push(Node::Union(children))
+    }
+
+    /// Vertices in all of the children.
+    pub fn intersect(&mut self, children: Vec<usize>) -> usize {
+        self.push(Node::Intersect(children))
+    }
+
+    /// Vertices in include, but not in exclude.
+    pub fn difference(&mut self, include: usize, exclude: usize) -> usize {
+        self.push(Node::Difference(include, exclude))
+    }
+
+    /// Vertices mapped to the graph, but not in child.
+    pub fn complement(&mut self, child: usize, within: u64) -> usize {
+        self.push(Node::Complement {
+            child,
+            graph: within,
+        })
+    }
+
+    /// Vertices with the attribute value.
+    pub fn attribute<V: Value>(
+        &mut self,
+        attr: PropertyHandle,
+        value: V,
+    ) -> Result<usize, LatticeError> {
+        let value = value.to_primitive();
+        value.verify_property(attr.0)?;
+        let idx = self.push(Node::Attribute {
+            attr,
+            value: attr.hash_value(&value),
+        });
+        self.values.insert(idx, value);
+        Ok(idx)
+    }
+
+    /// Vertices with an already hashed attribute value.
+    pub fn attribute_hash(&mut self, attr: PropertyHandle, value_hash: u64) -> usize {
+        self.push(Node::Attribute {
+            attr,
+            value: value_hash,
+        })
+    }
+
+    /// Vertices linked to target via label.
+    pub fn edge(&mut self, dir: EdgeDirection, label: PropertyHandle, target: usize) -> usize {
+        self.push(Node::Edge { dir, label, target })
+    }
+
+    /// Vertices linked to target via label, expanding at most `max_targets` vertices of target.
+    pub fn edge_limited(
+        &mut self,
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        target: usize,
+        max_targets: u64,
+    ) -> usize {
+        self.push(Node::EdgeLimited {
+            dir,
+            label,
+            target,
+            max_targets,
+        })
+    }
+
+    /// Vertices reached from target by following at most `max_depth` label edges.
+    pub fn edge_depth(
+        &mut self,
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        target: usize,
+        max_depth: u64,
+    ) -> usize {
+        self.push(Node::EdgeDepth {
+            dir,
+            label,
+            target,
+            max_depth,
+        })
+    }
+
+    /// Vertices reached from target by following label edges at any depth.
+    pub fn reachable(&mut self, dir: EdgeDirection, label: PropertyHandle, target: usize) -> usize {
+        self.push(Node::Reachable { dir, label, target })
+    }
+
+    /// Vertices linked to target via label, following only edges with the attribute value.
+    pub fn edge_where<V: Value>(
+        &mut self,
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        target: usize,
+        attr: PropertyHandle,
+        value: V,
+    ) -> Result<usize, LatticeError> {
+        let value = value.to_primitive();
+        value.verify_property(attr.0)?;
+        Ok(self.push(Node::EdgeWhere {
+            dir,
+            label,
+            target,
+            attr,
+            value: attr.hash_value(&value),
+        }))
+    }
+
+    /// Vertices with an integer attribute value in the half-open range.
+    /// * Both bounds must be unsigned, or both signed.
+    pub fn range<V: Value>(
+        &mut self,
+        attr: PropertyHandle,
+        range: std::ops::Range<V>,
+    ) -> Result<usize, LatticeError> {
+        let (start, end) =
+            Primitive::range_hashes(&range.start.to_primitive(), &range.end.to_primitive())
+                .ok_or(LatticeError::InvalidRange)?;
+        Ok(self.push(Node::Range { attr, start, end }))
+    }
+
+    /// Vertices with a text attribute value starting with the prefix.
+    pub fn prefix(&mut self, attr: PropertyHandle, prefix: &str) -> usize {
+        self.push(Node::Prefix {
+            attr,
+            prefix: attr.normalize_prefix(prefix),
+        })
+    }
+
+    /// Vertices whose number of edges via label satisfies the predicate.
+    pub fn degree(
+        &mut self,
+        dir: EdgeDirection,
+        label: PropertyHandle,
+        predicate: DegreePredicate,
+    ) -> usize {
+        self.push(Node::Degree {
+            dir,
+            label,
+            predicate,
+        })
+    }
+
+    /// Vertices matched by a saved query.
+    pub fn saved_query(&mut self, query_id: u64) -> usize {
+        self.push(Node::SavedQuery(query_id))
+    }
+
+    /// Validate the nodes and build the query.
+    /// * Returns an error if a node index does not exist, or if the nodes reachable from the root form a cycle.
+    pub fn build(self, root: usize) -> Result<PreparedQuery, LatticeError> {
+        if root >= self.nodes.len() {
+            return Err(LatticeError::RootNotFound);
+        }
+
+        // depth first search, children are placed before their parents
+        let mut placed: HashMap<NodeIdx, NodeIdx> = HashMap::new();
+        let mut in_progress = vec![false; self.nodes.len()];
+        let mut order = vec![];
+        let mut stack = vec![(root, false)];
+        while let Some((idx, children_placed)) = stack.pop() {
+            if children_placed {
+                in_progress[idx] = false;
+                placed.insert(idx, order.len());
+                order.push(idx);
+                continue;
+            }
+            if placed.contains_key(&idx) {
+                continue;
+            }
+            if in_progress[idx] {
+                return Err(LatticeError::InvalidQueryPlan); // node is its own ancestor
+            }
+            in_progress[idx] = true;
+            stack.push((idx, true));
+            for child in self.nodes[idx].children() {
+                if child >= self.nodes.len() {
+                    return Err(LatticeError::QueryNodeNotFound);
+                }
+                stack.push((child, false));
+            }
+        }
+
+        // renumber the nodes in build order
+        let mut nodes: Vec<Option<Node>> = self.nodes.into_iter().map(Some).collect();
+        let mut output = Vec::with_capacity(order.len());
+        for idx in order {
+            let mut node = nodes[idx].take().unwrap();
+            node.map_children(|child| placed[&child]);
+            output.push(node);
+        }
+        let values = self
+            .values
+            .into_iter()
+            .filter_map(|(idx, value)| Some((*placed.get(&idx)?, value)))
+            .collect();
+        Ok(PreparedQuery {
+            nodes: output,
+            root: placed[&root],
+            values,
+            verify_values: false,
+        })
+    }
+}
+
+impl Node {
+    // indices of the nodes this node reads from
+    fn children(&self) -> Vec<NodeIdx> {
+        match self {
+            Node::Union(children) | Node::Intersect(children) => children.clone(),
+            Node::Difference(a, b) => vec![*a, *b],
+            Node::Edge { target, .. }
+            | Node::EdgeLimited { target, .. }
+            | Node::EdgeWhere { target, .. }
+            | Node::EdgeDepth { target, .. }
+            | Node::Reachable { target, .. }
+            | Node::Complement { child: target, .. } => vec![*target],
+            Node::Attribute { .. }
+            | Node::SavedQuery(_)
+            | Node::Degree { .. }
+            | Node::Range { .. }
+            | Node::Prefix { .. } => vec![],
+        }
+    }
+
+    fn map_children(&mut self, f: impl Fn(NodeIdx) -> NodeIdx) {
+        match self {
+            Node::Union(children) | Node::Intersect(children) => {
+                for child in children {
+                    *child = f(*child);
+                }
+            }
+            Node::Difference(a, b) => {
+                *a = f(*a);
+                *b = f(*b);
+            }
+            Node::Edge { target, .. }
+            | Node::EdgeLimited { target, .. }
+            | Node::EdgeWhere { target, .. }
+            | Node::EdgeDepth { target, .. }
+            | Node::Reachable { target, .. }
+            | Node::Complement { child: target, .. } => *target = f(*target),
+            Node::Attribute { .. }
+            | Node::SavedQuery(_)
+            | Node::Degree { .. }
+            | Node::Range { .. }
+            | Node::Prefix { .. } => {}
+        }
+    }
+}
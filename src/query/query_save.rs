@@ -1,13 +1,24 @@
-use bincode::{Decode, Encode, config};
+use bincode::{Decode, Encode};
 use redb::ReadableTable;
 
 use crate::{
-    LatticeReader, LatticeWriter, PreparedQuery, QueryBuilder,
+    LatticeReader, LatticeWriter, PreparedQuery, QueryBuilder, codec,
     errors::LatticeError,
-    lattice_db::tables::{QUERIES, QUERY_METAS, QUERY_NAMES},
+    lattice_db::{
+        tables::{QUERIES, QUERY_METAS, QUERY_NAMES},
+        writer::take_ids,
+    },
 };
 
-pub struct QueryHandle(u64);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueryHandle(pub(crate) u64);
+
+impl QueryHandle {
+    /// The id of the saved query, as used by `QueryBuilder::include_saved`.
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+}
 
 impl LatticeWriter {
     pub fn save_query<'a, M, A>(
@@ -21,8 +32,7 @@ impl LatticeWriter {
         M: Encode,
     {
         // incr id
-        let id = self.query_id_cursor;
-        self.query_id_cursor += 1;
+        let id = take_ids(&mut self.query_id_cursor, 1)?;
 
         // assign alias, prevents collisions
         let alias = alias.into();
@@ -36,18 +46,34 @@ impl LatticeWriter {
 
         // write metadata
         let mut meta_table = self.wt.open_table(QUERY_METAS)?;
-        let meta_bytes = bincode::encode_to_vec(meta, config::standard())?;
+        let meta_bytes = codec::encode_to_vec(meta, self.encoding)?;
         meta_table.insert(id, meta_bytes)?;
 
         // write query
         let query = query.compile()?;
         let mut table = self.wt.open_table(QUERIES)?;
-        let query_bytes = bincode::encode_to_vec(query, config::standard())?;
+        let query_bytes = codec::encode_to_vec(query, self.encoding)?;
         table.insert(id, query_bytes)?;
 
         Ok(QueryHandle(id))
     }
 
+    /// Overwrite the metadata of a saved query.
+    /// * The query keeps its id and alias.
+    pub fn update_query_meta<M: Encode>(
+        &mut self,
+        handle: QueryHandle,
+        meta: &M,
+    ) -> Result<(), LatticeError> {
+        if self.wt.open_table(QUERIES)?.get(handle.0)?.is_none() {
+            return Err(LatticeError::QueryNotFound);
+        }
+        let mut meta_table = self.wt.open_table(QUERY_METAS)?;
+        let meta_bytes = codec::encode_to_vec(meta, self.encoding)?;
+        meta_table.insert(handle.0, meta_bytes)?;
+        Ok(())
+    }
+
     /// Return a prepared query.
     pub fn get_prepared_query(&self, handle: QueryHandle) -> Result<PreparedQuery, LatticeError> {
         let table = self.wt.open_table(QUERIES)?;
@@ -55,7 +81,7 @@ impl LatticeWriter {
             .get(handle.0)?
             .ok_or(LatticeError::QueryNotFound)?
             .value();
-        let prepared = bincode::decode_from_slice(&bytes, config::standard())?.0;
+        let prepared = codec::decode_from_slice(&bytes, self.encoding)?;
         Ok(prepared)
     }
 
@@ -95,7 +121,7 @@ impl LatticeReader {
             .get(handle.0)?
             .ok_or_else(|| LatticeError::QueryNotFound)?
             .value();
-        let (meta, _) = bincode::decode_from_slice(&bytes, config::standard())?;
+        let meta = codec::decode_from_slice(&bytes, self.encoding)?;
         Ok(meta)
     }
 
@@ -106,7 +132,7 @@ impl LatticeReader {
             .get(handle.0)?
             .ok_or(LatticeError::QueryNotFound)?
             .value();
-        let prepared = bincode::decode_from_slice(&bytes, config::standard())?.0;
+        let prepared = codec::decode_from_slice(&bytes, self.encoding)?;
         Ok(prepared)
     }
 }
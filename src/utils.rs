@@ -1,2 +1,3 @@
 pub(crate) mod generational_vector;
+pub(crate) mod sampling;
 pub mod values;
@@ -1,9 +1,32 @@
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+use bincode::{Decode, Encode};
+
+/// Reference to a slot inside a generational vector.
+/// * Can be stored externally, but is only valid against the same builder it came from.
+/// * A handle from another builder, or from before the slot was reused, won't match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Handle {
     pub(crate) generation: u32,
     pub(crate) index: usize,
 }
 
+impl Handle {
+    /// Recreate a handle from its stored parts.
+    pub fn from_parts(generation: u32, index: usize) -> Self {
+        Self { generation, index }
+    }
+
+    /// Returns the slot generation.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Returns the slot index.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
 #[derive(Debug)]
 struct Slot<T> {
     item: Option<T>,
@@ -18,6 +41,7 @@ struct Slot<T> {
 pub struct GenVec<T> {
     items: Vec<Slot<T>>,
     freed: Vec<usize>,
+    base_generation: u32, // generation of new slots, past every truncated slot
 }
 
 impl<T> GenVec<T> {
@@ -25,6 +49,7 @@ impl<T> GenVec<T> {
         Self {
             items: vec![],
             freed: vec![],
+            base_generation: 0,
         }
     }
 
@@ -42,11 +67,11 @@ impl<T> GenVec<T> {
             // no freed items, creates a new slot
             let idx = self.items.len();
             self.items.push(Slot {
-                generation: 0,
+                generation: self.base_generation,
                 item: Some(item),
             });
             Handle {
-                generation: 0,
+                generation: self.base_generation,
                 index: idx,
             }
         }
@@ -93,6 +118,23 @@ impl<T> GenVec<T> {
         slot.item.as_ref()
     }
 
+    /// Removes the empty slots past the last item, reclaiming their memory.
+    /// * Handles to the removed slots stay invalid, even once new items fill their indices.
+    pub fn shrink_to_fit(&mut self) {
+        let len = self
+            .items
+            .iter()
+            .rposition(|slot| slot.item.is_some())
+            .map_or(0, |idx| idx + 1);
+        for slot in &self.items[len..] {
+            self.base_generation = self.base_generation.max(slot.generation);
+        }
+        self.items.truncate(len);
+        self.items.shrink_to_fit();
+        self.freed.retain(|idx| *idx < len);
+        self.freed.shrink_to_fit();
+    }
+
     // /// Get the length of the vector.
     // pub fn len(&self) -> usize {
     //     self.items.len() - self.freed.len()
@@ -227,4 +269,30 @@ pub mod tests {
         assert_eq!(g.freed.len(), 0);
         assert_eq!(g.items.len(), 3);
     }
+
+    #[test]
+    fn test_gen_vec_shrink_to_fit() {
+        let mut g = GenVec::new();
+        let h1 = g.add(1);
+        let h2 = g.add(2);
+        let h3 = g.add(3);
+        g.remove(h1);
+        g.remove(h3);
+        g.remove(h2);
+        let h4 = g.add(4);
+
+        // only the slots past the last item are removed
+        g.shrink_to_fit();
+        assert_eq!(g.items.len(), 2);
+        assert_eq!(g.freed, vec![0]);
+
+        // truncated handles don't match items added in their place
+        let h5 = g.add(5);
+        let h6 = g.add(6);
+        assert_eq!(h6.index, h3.index);
+        assert!(g.get(h3).is_none());
+        assert_eq!(g.get(h4), Some(&4));
+        assert_eq!(g.get(h5), Some(&5));
+        assert_eq!(g.get(h6), Some(&6));
+    }
 }
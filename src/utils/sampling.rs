@@ -0,0 +1,70 @@
+/// Small deterministic pseudo random generator (SplitMix64).
+/// * Not suitable for anything security related.
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..bound`.
+    pub(crate) fn next_below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+/// Reservoir sample up to `n` items, deterministic for a given seed.
+/// * Returned items are sorted.
+pub(crate) fn reservoir_sample<E>(
+    items: impl Iterator<Item = Result<u64, E>>,
+    n: usize,
+    seed: u64,
+) -> Result<Vec<u64>, E> {
+    let mut rng = SplitMix64::new(seed);
+    let mut reservoir = Vec::with_capacity(n);
+    if n == 0 {
+        return Ok(reservoir);
+    }
+    for (seen, item) in items.enumerate() {
+        let item = item?;
+        if reservoir.len() < n {
+            reservoir.push(item);
+        } else {
+            // replace a kept item with probability n / seen
+            let j = rng.next_below(seen as u64 + 1) as usize;
+            if j < n {
+                reservoir[j] = item;
+            }
+        }
+    }
+    reservoir.sort_unstable();
+    Ok(reservoir)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservoir_sample_deterministic() {
+        let items = || (0..1000u64).map(Ok::<u64, ()>);
+        let a = reservoir_sample(items(), 10, 42).unwrap();
+        let b = reservoir_sample(items(), 10, 42).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 10);
+
+        // asking for more than exist returns everything
+        let all = reservoir_sample((0..5u64).map(Ok::<u64, ()>), 10, 42).unwrap();
+        assert_eq!(all, vec![0, 1, 2, 3, 4]);
+    }
+}
@@ -5,28 +5,126 @@ use crate::errors::LatticeError;
 
 /// Value representation when stored inside the Graph.
 #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Primitive {
     UInt(u64),
     Text(String),
+    /// Explicitly null, distinct from the attribute being absent.
+    Null,
+    /// Raw bytes, matched by exact value.
+    Bytes(Vec<u8>),
+    /// Signed integer, limited to 56 bits.
+    Int(i64),
+    /// Floating point number, NaN can't be stored.
+    /// * Matching is bit-exact once `-0.0` is normalized to `0.0`, so `0.1 + 0.2` won't match `0.3`.
+    /// * The hash does not keep the order of values, so floats only support equality matches, not `match_range`.
+    Float(f64),
+}
+
+/// What to do with a number too large to be stored as a UInt.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnOverflow {
+    /// Return a `NumberTooBig` error.
+    #[default]
+    Error,
+    /// Store the number as its 8 big-endian bytes in a `Primitive::Bytes`.
+    /// * Match it with `n.to_be_bytes().to_vec()`.
+    /// * Applies to signed integers as well.
+    StoreAsBytes,
+}
+
+impl OnOverflow {
+    // verifies a value for a property, converting it if the policy allows
+    pub(crate) fn apply(self, property: u64, value: Primitive) -> Result<Primitive, LatticeError> {
+        match (self, value) {
+            (OnOverflow::StoreAsBytes, Primitive::UInt(n)) if n & 0xFF00000000000000 != 0 => {
+                Ok(Primitive::Bytes(n.to_be_bytes().to_vec()))
+            }
+            (OnOverflow::StoreAsBytes, Primitive::Int(n)) if !(MIN_INT..=MAX_INT).contains(&n) => {
+                Ok(Primitive::Bytes(n.to_be_bytes().to_vec()))
+            }
+            (_, value) => {
+                value.verify_property(property)?;
+                Ok(value)
+            }
+        }
+    }
 }
 
 const PRIMITIVE_UINT: u64 = 1 << 56;
 const PRIMITIVE_TEXT: u64 = 2 << 56;
+const PRIMITIVE_NULL: u64 = 3 << 56;
+const PRIMITIVE_BYTES: u64 = 4 << 56;
+const PRIMITIVE_INT: u64 = 5 << 56;
+const PRIMITIVE_FLOAT: u64 = 6 << 56;
+
+// signed integers are biased into the low 56 bits, keeping their order
+const INT_BIAS: i64 = 1 << 55;
+const MIN_INT: i64 = -INT_BIAS;
+const MAX_INT: i64 = INT_BIAS - 1;
 
 impl Primitive {
     /// Verify the value can be used as a Value for the graph.
     pub fn verify(&self) -> Result<(), LatticeError> {
+        self.check(None)
+    }
+
+    // verifies a value assigned to a property, naming it in the error
+    pub(crate) fn verify_property(&self, property: u64) -> Result<(), LatticeError> {
+        self.check(Some(property))
+    }
+
+    // helper fn to check the value fits in the index
+    fn check(&self, property: Option<u64>) -> Result<(), LatticeError> {
         match self {
             Primitive::UInt(n) => {
                 if (*n & 0xFF00000000000000) != 0 {
-                    return Err(LatticeError::NumberTooBig(n.to_string()));
+                    return Err(LatticeError::NumberTooBig {
+                        value: n.to_string(),
+                        property,
+                        alias: None,
+                    });
+                }
+            }
+            Primitive::Int(n) => {
+                if !(MIN_INT..=MAX_INT).contains(n) {
+                    return Err(LatticeError::NumberTooBig {
+                        value: n.to_string(),
+                        property,
+                        alias: None,
+                    });
                 }
             }
-            Primitive::Text(_) => {}
+            Primitive::Float(f) => {
+                if f.is_nan() {
+                    return Err(LatticeError::InvalidFloat);
+                }
+            }
+            Primitive::Text(_) | Primitive::Null | Primitive::Bytes(_) => {}
         }
         Ok(())
     }
 
+    // hash range [start, end) of the integers in lo..hi, hashes of integers keep their order
+    // * bounds outside what can be stored are clamped, returns None for other value kinds
+    pub(crate) fn range_hashes(lo: &Primitive, hi: &Primitive) -> Option<(u64, u64)> {
+        let (start, end) = match (lo, hi) {
+            (Primitive::UInt(lo), Primitive::UInt(hi)) => {
+                let max = 1 << 56;
+                (
+                    *lo.min(&max) + PRIMITIVE_UINT,
+                    *hi.min(&max) + PRIMITIVE_UINT,
+                )
+            }
+            (Primitive::Int(lo), Primitive::Int(hi)) => {
+                let bias = |n: i64| n.clamp(MIN_INT, MAX_INT + 1).wrapping_add(INT_BIAS) as u64;
+                (bias(*lo) + PRIMITIVE_INT, bias(*hi) + PRIMITIVE_INT)
+            }
+            _ => return None,
+        };
+        Some((start, end.max(start)))
+    }
+
     /// Hashes the value.
     /// * Value lookups are stored as hashes inside the database.
     pub fn hash(&self) -> u64 {
@@ -35,6 +133,16 @@ impl Primitive {
             Primitive::Text(t) => {
                 (rapidhash_v3(t.as_bytes()) & 0x00FFFFFFFFFFFFFF) | PRIMITIVE_TEXT
             }
+            Primitive::Null => PRIMITIVE_NULL,
+            Primitive::Bytes(b) => (rapidhash_v3(b) & 0x00FFFFFFFFFFFFFF) | PRIMITIVE_BYTES,
+            Primitive::Int(n) => {
+                (n.wrapping_add(INT_BIAS) as u64 & 0x00FFFFFFFFFFFFFF) | PRIMITIVE_INT
+            }
+            Primitive::Float(f) => {
+                let canonical = if *f == 0.0 { 0.0f64 } else { *f }; // -0.0 matches 0.0
+                (rapidhash_v3(&canonical.to_bits().to_be_bytes()) & 0x00FFFFFFFFFFFFFF)
+                    | PRIMITIVE_FLOAT
+            }
         }
     }
 }
@@ -68,8 +176,65 @@ impl Value for u8 {
     }
 }
 
+impl Value for i64 {
+    fn to_primitive(self) -> Primitive {
+        Primitive::Int(self)
+    }
+}
+
+impl Value for i32 {
+    fn to_primitive(self) -> Primitive {
+        Primitive::Int(self as i64)
+    }
+}
+
+impl Value for i16 {
+    fn to_primitive(self) -> Primitive {
+        Primitive::Int(self as i64)
+    }
+}
+
+impl Value for i8 {
+    fn to_primitive(self) -> Primitive {
+        Primitive::Int(self as i64)
+    }
+}
+
+impl Value for f64 {
+    fn to_primitive(self) -> Primitive {
+        Primitive::Float(self)
+    }
+}
+
+impl Value for f32 {
+    fn to_primitive(self) -> Primitive {
+        Primitive::Float(self as f64)
+    }
+}
+
 impl Value for &str {
     fn to_primitive(self) -> Primitive {
         Primitive::Text(self.to_string())
     }
 }
+
+impl Value for Vec<u8> {
+    fn to_primitive(self) -> Primitive {
+        Primitive::Bytes(self)
+    }
+}
+
+impl Value for &[u8] {
+    fn to_primitive(self) -> Primitive {
+        Primitive::Bytes(self.to_vec())
+    }
+}
+
+impl<V: Value> Value for Option<V> {
+    fn to_primitive(self) -> Primitive {
+        match self {
+            Some(v) => v.to_primitive(),
+            None => Primitive::Null,
+        }
+    }
+}
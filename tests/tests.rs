@@ -1 +1,3554 @@
+use lattice_db::{
+    DbOptions, EdgeDump, FORMAT_VERSION, GraphBuilder, GraphDump, Handle, IntEncoding, LatticeDb,
+    LatticeError, PreparedQuery, QueryBuilder, VertexDump,
+    graph_builder::{EdgeHandle, VertexHandle},
+    properties::{PropertyHandle, PropertyOptions},
+    query_builder::{DegreePredicate, EdgeDirection},
+    values::{OnOverflow, Primitive},
+};
 
+#[test]
+fn test_fixed_int_encoding_round_trip() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let options = DbOptions {
+        int_encoding: IntEncoding::Fixed,
+        ..Default::default()
+    };
+    let db = LatticeDb::create_with_options(file.path(), options).unwrap();
+
+    let mut wr = db.begin_write().unwrap();
+    let prop = wr.register_property("name", &"meta".to_string()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(prop, "alice").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    drop(db);
+
+    // reopening picks up the stored encoding, not the default
+    let db = LatticeDb::open(file.path()).unwrap();
+    assert_eq!(db.int_encoding(), IntEncoding::Fixed);
+
+    let rd = db.begin_read().unwrap();
+    let meta: String = rd.get_property_meta(prop).unwrap();
+    assert_eq!(meta, "meta");
+    let graph = rd.load_graph(0).unwrap();
+    assert_eq!(graph.iter_vertices().count(), 1);
+
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(prop, "alice").unwrap();
+    query.set_root(root);
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![0]);
+}
+
+#[test]
+fn test_unknown_int_encoding() {
+    let (db, file) = LatticeDb::create_temporary().unwrap();
+    drop(db);
+
+    // an encoding this version doesn't know
+    let metadata: redb::TableDefinition<u8, u64> = redb::TableDefinition::new("_lattice_meta");
+    let raw = redb::Database::open(file.path()).unwrap();
+    let wt = raw.begin_write().unwrap();
+    wt.open_table(metadata).unwrap().insert(1, 7).unwrap();
+    wt.commit().unwrap();
+    drop(raw);
+
+    assert!(matches!(
+        LatticeDb::open(file.path()),
+        Err(LatticeError::UnknownEncoding(7))
+    ));
+}
+
+#[test]
+fn test_case_insensitive_property() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let options = PropertyOptions {
+        case_insensitive: true,
+        ..Default::default()
+    };
+    let name = wr
+        .register_property_with_options("name", &(), options)
+        .unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(name, "Apple").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.get_property_handle("name").unwrap(), Some(name));
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(name, "aPPLE").unwrap();
+    query.set_root(root);
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![0]);
+
+    // stored value keeps its original casing
+    let graph = rd.dump_graph(0).unwrap();
+    assert_eq!(
+        graph.vertices[0].attributes,
+        vec![(name, Primitive::Text("Apple".to_string()))]
+    );
+}
+
+#[test]
+fn test_property_options_with_id() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let options = PropertyOptions {
+        case_insensitive: true,
+        ..Default::default()
+    };
+    let tag = wr
+        .register_property_with_id(1 << 62, "tag", &1u64, options)
+        .unwrap();
+    assert_eq!(tag.options(), options);
+    let plain = wr
+        .register_property_with_id(5, "plain", &(), PropertyOptions::default())
+        .unwrap();
+    assert_eq!(plain.options(), PropertyOptions::default());
+    assert!(matches!(
+        wr.register_property_with_id(1 << 63, "reserved", &(), options),
+        Err(LatticeError::PropertyIdTaken)
+    ));
+    wr.update_property_meta(tag, &2u64).unwrap();
+    wr.commit().unwrap();
+
+    // options are read back from the property row
+    let rd = db.begin_read().unwrap();
+    let found = rd.get_property_handle("tag").unwrap().unwrap();
+    assert_eq!(found.options(), options);
+    assert_eq!(rd.get_property_meta::<u64>(found).unwrap(), 2);
+    let found = rd.get_property_handle("plain").unwrap().unwrap();
+    assert_eq!(found.options(), PropertyOptions::default());
+}
+
+#[test]
+fn test_normalized_whitespace_property() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let options = PropertyOptions {
+        case_insensitive: true,
+        normalize_whitespace: true,
+        ..Default::default()
+    };
+    let tag = wr
+        .register_property_with_options(None, &(), options)
+        .unwrap();
+    let mut graph = GraphBuilder::new();
+    graph
+        .new_vertex()
+        .new_attribute(tag, "  Apple \t Pie ")
+        .unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(tag, "apple pie").unwrap();
+    query.set_root(root);
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![0]);
+}
+
+#[test]
+fn test_split_components() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    // two chains a -> b and c -> d -> e, plus lone vertex f
+    let mut graph = GraphBuilder::new();
+    let v: Vec<_> = (0..6).map(|_| graph.new_vertex().handle()).collect();
+    graph.new_edge(v[0], link, v[1]).unwrap();
+    graph.new_edge(v[3], link, v[2]).unwrap();
+    graph.new_edge(v[3], link, v[4]).unwrap();
+
+    let parts = graph.split_components().unwrap();
+    let sizes: Vec<_> = parts.iter().map(|g| g.iter_vertices().count()).collect();
+    assert_eq!(sizes, vec![2, 3, 1]);
+    let edges: Vec<_> = parts.iter().map(|g| g.iter_edges().count()).collect();
+    assert_eq!(edges, vec![1, 2, 0]);
+
+    wr.save_graphs_parallel(parts).unwrap();
+    wr.commit().unwrap();
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.dump_graph(1).unwrap().edges.len(), 2);
+
+    // a loaded graph would keep its vertices in the stored graph as well
+    assert!(matches!(
+        rd.load_graph(1).unwrap().split_components(),
+        Err(LatticeError::GraphAlreadySaved)
+    ));
+}
+
+#[test]
+fn test_try_begin_write_busy() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let wr = db.begin_write().unwrap();
+    assert!(matches!(
+        db.try_begin_write(),
+        Err(LatticeError::WriterBusy)
+    ));
+    drop(wr);
+    let wr = db.try_begin_write().unwrap();
+    wr.commit().unwrap();
+    assert!(db.try_begin_write().is_ok());
+}
+
+#[test]
+fn test_edges_by_weight() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let road = wr.register_property(None, &()).unwrap();
+    let cost = wr.register_property(None, &()).unwrap();
+
+    let mut graph = GraphBuilder::new();
+    let v: Vec<_> = (0..4).map(|_| graph.new_vertex().handle()).collect();
+    for (i, weight) in [5u64, 20, 50].into_iter().enumerate() {
+        graph
+            .build_edge(v[i], road, v[i + 1])
+            .unwrap()
+            .new_attribute(cost, weight)
+            .unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(
+        rd.edges_by_weight(road, cost, 10..).unwrap(),
+        vec![(1, 2, 20), (2, 3, 50)]
+    );
+    assert_eq!(
+        rd.edges_by_weight(road, cost, ..=5).unwrap(),
+        vec![(0, 1, 5)]
+    );
+
+    // removed edges leave the index
+    let mut graph = rd.load_graph(0).unwrap();
+    drop(rd);
+    let (handle, _) = graph.iter_edges().next().unwrap();
+    graph.remove_edge(EdgeHandle::from_raw(handle)).unwrap();
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.edges_by_weight(road, cost, ..).unwrap().len(), 2);
+}
+
+#[test]
+fn test_merge_from() {
+    // builds a shard with one two-vertex graph
+    fn shard(name: &str) -> (LatticeDb, tempfile::NamedTempFile) {
+        let (db, file) = LatticeDb::create_temporary().unwrap();
+        let mut wr = db.begin_write().unwrap();
+        let name_prop = wr.register_property("name", &()).unwrap();
+        let knows = wr.register_property("knows", &()).unwrap();
+        let mut graph = GraphBuilder::new();
+        let a = graph
+            .new_vertex()
+            .new_attribute(name_prop, name)
+            .unwrap()
+            .handle();
+        let b = graph.new_vertex().handle();
+        graph.new_edge(a, knows, b).unwrap();
+        wr.save_graphs_parallel(vec![graph]).unwrap();
+        let mut query = QueryBuilder::new();
+        let root = query.match_attr(name_prop, name).unwrap();
+        query.set_root(root);
+        wr.save_query(&query, name, &()).unwrap();
+        wr.commit().unwrap();
+        (db, file)
+    }
+    let (mut db, _file) = shard("alice");
+    let (other, _other_file) = shard("bob");
+
+    let remap = db.merge_from(&other).unwrap();
+    assert_eq!(remap.graphs[&0], 1);
+    assert_eq!(remap.vertices[&0], 2);
+    assert_eq!(remap.vertices[&1], 3);
+
+    // aliased properties are shared, so the merged data is searchable as one
+    let rd = db.begin_read().unwrap();
+    let name = rd.get_property_handle("name").unwrap().unwrap();
+    let knows = rd.get_property_handle("knows").unwrap().unwrap();
+    let mut query = QueryBuilder::new();
+    let bob = query.match_attr(name, "bob").unwrap();
+    let known = query.match_incoming(knows, bob).unwrap();
+    query.set_root(known);
+    assert!(rd.search(&query.compile().unwrap()).unwrap().is_empty());
+    let mut query = QueryBuilder::new();
+    let bob = query.match_attr(name, "bob").unwrap();
+    let known = query.match_outgoing(knows, bob).unwrap();
+    query.set_root(known);
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![3]);
+
+    let handle = rd.get_query_handle("bob").unwrap().unwrap();
+    let saved = rd.get_prepared_query(handle).unwrap();
+    assert_eq!(rd.search(&saved).unwrap(), vec![2]);
+}
+
+#[test]
+fn test_merge_graph_meta_and_options() {
+    let (mut db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let tag = wr.register_property("tag", &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(tag, "x").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let (other, _other_file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = other.begin_write().unwrap();
+    let other_tag = wr.register_property("tag", &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph
+        .new_vertex()
+        .new_attribute(other_tag, "y")
+        .unwrap()
+        .handle();
+    let b = graph.new_vertex().handle();
+    graph.new_edge(a, other_tag, b).unwrap();
+    wr.save_graphs_with_meta(vec![(graph, Some(5u64))]).unwrap();
+    wr.commit().unwrap();
+
+    // metadata and vertex ids follow the graph
+    let remap = db.merge_from(&other).unwrap();
+    let rd = db.begin_read().unwrap();
+    let new_graph = remap.graphs[&0];
+    assert_eq!(rd.get_graph_meta::<u64>(new_graph).unwrap(), Some(5));
+    let saved: Vec<u64> = rd
+        .dump_graph(new_graph)
+        .unwrap()
+        .vertices
+        .iter()
+        .map(|v| v.id)
+        .collect();
+    assert_eq!(saved, vec![remap.vertices[&0], remap.vertices[&1]]);
+    drop(rd);
+
+    // a shared alias with different options is refused
+    let (strict, _strict_file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = strict.begin_write().unwrap();
+    let options = PropertyOptions {
+        case_insensitive: true,
+        ..Default::default()
+    };
+    wr.register_property_with_options("tag", &(), options)
+        .unwrap();
+    wr.commit().unwrap();
+    assert!(matches!(
+        db.merge_from(&strict),
+        Err(LatticeError::PropertyOptionsMismatch(name)) if name == "tag"
+    ));
+}
+
+#[test]
+fn test_recommend() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property("name", &()).unwrap();
+    let friend = wr.register_property("friend", &()).unwrap();
+
+    // me -> a, me -> b, a -> b, a -> c, b -> d, b -> me
+    let mut graph = GraphBuilder::new();
+    let v: Vec<_> = ["me", "a", "b", "c", "d"]
+        .into_iter()
+        .map(|n| graph.new_vertex().new_attribute(name, n).unwrap().handle())
+        .collect();
+    for (from, to) in [(0, 1), (0, 2), (1, 2), (1, 3), (2, 4), (2, 0)] {
+        graph.new_edge(v[from], friend, v[to]).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let me = query.match_attr(name, "me").unwrap();
+    let root = query.recommend(friend, me).unwrap();
+    query.set_root(root);
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![3, 4]);
+}
+
+#[test]
+fn test_graph_meta() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_with_meta(vec![
+        (GraphBuilder::new(), Some("import.csv".to_string())),
+        (GraphBuilder::new(), None),
+    ])
+    .unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let meta: Option<String> = rd.get_graph_meta(0).unwrap();
+    assert_eq!(meta.as_deref(), Some("import.csv"));
+    assert_eq!(rd.get_graph_meta::<String>(1).unwrap(), None);
+}
+
+#[test]
+fn test_integrity_check_before_saving() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    wr.set_integrity_checks(true);
+    let road = wr.register_property(None, &()).unwrap();
+
+    // a vertex handle from another graph, pointing past the only vertex
+    let mut other = GraphBuilder::new();
+    other.new_vertex();
+    let foreign = other.new_vertex().handle();
+
+    let mut valid = GraphBuilder::new();
+    valid.new_vertex();
+    valid.new_vertex();
+    let mut broken = GraphBuilder::new();
+    let a = broken.new_vertex().handle();
+    broken.new_edge(a, road, a).unwrap();
+    let (first, _) = broken.iter_edges().next().unwrap();
+    broken.new_edge(a, road, a).unwrap();
+    broken
+        .edit_edge(EdgeHandle::from_raw(first))
+        .unwrap()
+        .set_source(foreign)
+        .unwrap();
+    assert!(matches!(
+        wr.save_graphs_parallel(vec![valid, broken]),
+        Err(LatticeError::DanglingEdge)
+    ));
+
+    // nothing was reserved or written by the failed save
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    let rd = db.begin_read().unwrap();
+    let saved = rd.dump_graph(0).unwrap();
+    assert_eq!(saved.vertices.len(), 1);
+    assert_eq!(saved.vertices[0].id, 0);
+    assert!(rd.dump_graph(1).is_err());
+    assert_eq!(rd.sample_vertices(10, 0).unwrap(), vec![0]);
+}
+
+#[test]
+fn test_edit_edge_attributes() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let road = wr.register_property(None, &()).unwrap();
+    let cost = wr.register_property(None, &()).unwrap();
+    let toll = wr.register_property(None, &()).unwrap();
+
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    graph
+        .build_edge(a, road, b)
+        .unwrap()
+        .new_attribute(cost, 5u64)
+        .unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // only the attributes of the edge change
+    let rd = db.begin_read().unwrap();
+    let mut graph = rd.load_graph(0).unwrap();
+    drop(rd);
+    let (handle, _) = graph.iter_edges().next().unwrap();
+    graph
+        .edit_edge(EdgeHandle::from_raw(handle))
+        .unwrap()
+        .new_attribute(toll, 2u64)
+        .unwrap();
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.outgoing_labels(0).unwrap(), vec![road]);
+    assert_eq!(rd.incoming_labels(1).unwrap(), vec![road]);
+    assert_eq!(rd.edges_by_weight(road, cost, ..).unwrap(), vec![(0, 1, 5)]);
+    assert_eq!(rd.edges_by_weight(road, toll, ..).unwrap(), vec![(0, 1, 2)]);
+}
+
+#[test]
+fn test_edge_index_repair() {
+    let (db, file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let cost = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    graph
+        .build_edge(a, link, b)
+        .unwrap()
+        .new_attribute(cost, 3u64)
+        .unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    drop(db);
+
+    // drop the reverse and edge attribute rows, and inject a forward row for an edge that doesn't exist
+    {
+        let forward: redb::TableDefinition<(u64, u64), Vec<u8>> =
+            redb::TableDefinition::new("_lattice_idx_f");
+        let reverse: redb::TableDefinition<(u64, u64), Vec<u8>> =
+            redb::TableDefinition::new("_lattice_idx_r");
+        let attr_fwd: redb::TableDefinition<(u64, u64, u64, u64), Vec<u8>> =
+            redb::TableDefinition::new("_lattice_idx_ea_f");
+        let raw = redb::Database::open(file.path()).unwrap();
+        let wt = raw.begin_write().unwrap();
+        let mut bitmap = lattice_db::roaring::RoaringTreemap::new();
+        bitmap.insert(9);
+        let mut bytes = vec![];
+        bitmap.serialize_into(&mut bytes).unwrap();
+        wt.open_table(forward)
+            .unwrap()
+            .insert((5, 0), bytes)
+            .unwrap();
+        wt.open_table(reverse)
+            .unwrap()
+            .retain(|_, _| false)
+            .unwrap();
+        wt.open_table(attr_fwd)
+            .unwrap()
+            .retain(|_, _| false)
+            .unwrap();
+        wt.commit().unwrap();
+    }
+    let db = LatticeDb::open(file.path()).unwrap();
+
+    let mut wr = db.begin_write().unwrap();
+    assert_eq!(
+        wr.verify_edge_index_symmetry().unwrap(),
+        vec![(0, 0, 1), (5, 0, 9)]
+    );
+    wr.repair_edge_indexes().unwrap();
+    wr.commit().unwrap();
+
+    let wr = db.begin_write().unwrap();
+    assert!(wr.verify_edge_index_symmetry().unwrap().is_empty());
+    drop(wr);
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.outgoing_labels(0).unwrap(), vec![link]);
+    assert_eq!(rd.incoming_labels(1).unwrap(), vec![link]);
+    assert!(rd.outgoing_labels(5).unwrap().is_empty());
+    assert_eq!(rd.edges_by_weight(link, cost, ..).unwrap(), vec![(0, 1, 3)]);
+}
+
+#[test]
+fn test_reachable_with_paths() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let pays = wr.register_property(None, &()).unwrap();
+
+    // 0 -> 1 -> 2 -> 3, 0 -> 2
+    let mut graph = GraphBuilder::new();
+    let v: Vec<_> = (0..4).map(|_| graph.new_vertex().handle()).collect();
+    for (from, to) in [(0, 1), (1, 2), (2, 3), (0, 2)] {
+        graph.new_edge(v[from], pays, v[to]).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(
+        rd.reachable_with_paths(0, pays, 2).unwrap(),
+        vec![vec![0, 1], vec![0, 2], vec![0, 2, 3]]
+    );
+    assert_eq!(
+        rd.reachable_with_paths(0, pays, 1).unwrap(),
+        vec![vec![0, 1], vec![0, 2]]
+    );
+}
+
+#[test]
+fn test_run_optimize_shrinks_index() {
+    fn scalar_bytes(run_optimize: bool) -> u64 {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let options = DbOptions {
+            run_optimize,
+            ..Default::default()
+        };
+        let db = LatticeDb::create_with_options(file.path(), options).unwrap();
+        let mut wr = db.begin_write().unwrap();
+        let prop = wr.register_property(None, &()).unwrap();
+        let mut graph = GraphBuilder::new();
+        for _ in 0..10_000 {
+            graph.new_vertex().new_attribute(prop, 1u8).unwrap();
+        }
+        let bytes = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let hook_bytes = bytes.clone();
+        wr.set_metrics_hook(move |metrics| {
+            hook_bytes.store(metrics.scalar_bytes, std::sync::atomic::Ordering::Relaxed);
+        });
+        wr.save_graphs_parallel(vec![graph]).unwrap();
+        wr.commit().unwrap();
+        bytes.load(std::sync::atomic::Ordering::Relaxed)
+    }
+    assert!(scalar_bytes(true) < scalar_bytes(false));
+}
+
+#[test]
+fn test_prepared_query_builder() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let follows = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let fan = graph.new_vertex().handle();
+    let star = graph
+        .new_vertex()
+        .new_attribute(name, "star")
+        .unwrap()
+        .handle();
+    graph.new_edge(fan, follows, star).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // nodes may be added in any order, as long as they form a DAG
+    let mut plan = PreparedQuery::builder();
+    let fans = plan.edge(EdgeDirection::Incoming, follows, 1);
+    plan.attribute(name, "star").unwrap();
+    let query = plan.build(fans).unwrap();
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&query).unwrap(), vec![0]);
+
+    let mut plan = PreparedQuery::builder();
+    let a = plan.union(vec![1]);
+    plan.intersect(vec![a]);
+    assert!(matches!(plan.build(a), Err(LatticeError::InvalidQueryPlan)));
+    let mut plan = PreparedQuery::builder();
+    let a = plan.union(vec![7]);
+    assert!(matches!(
+        plan.build(a),
+        Err(LatticeError::QueryNodeNotFound)
+    ));
+}
+
+#[test]
+fn test_iter_property_index() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let color = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    for value in ["red", "blue", "red"] {
+        graph.new_vertex().new_attribute(color, value).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let mut column: Vec<_> = rd
+        .iter_property_index(color)
+        .unwrap()
+        .map(|entry| {
+            let (hash, vertices) = entry.unwrap();
+            (hash, vertices.into_iter().collect::<Vec<_>>())
+        })
+        .collect();
+    column.sort_by_key(|(_, vertices)| vertices.len());
+    assert_eq!(
+        column,
+        vec![
+            (Primitive::Text("blue".to_string()).hash(), vec![1]),
+            (Primitive::Text("red".to_string()).hash(), vec![0, 2]),
+        ]
+    );
+}
+
+#[test]
+fn test_clear_graphs() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property("name", &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(name, "a").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut wr = db.begin_write().unwrap();
+    wr.clear_graphs().unwrap();
+    wr.clear_graphs().unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.get_property_handle("name").unwrap(), Some(name));
+    assert!(matches!(rd.load_graph(0), Err(LatticeError::GraphNotFound)));
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(name, "a").unwrap();
+    query.set_root(root);
+    assert!(rd.search(&query.compile().unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_same_component() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    // 0 -> 1 <- 2 -> 3, and 4 alone
+    let mut graph = GraphBuilder::new();
+    let v: Vec<_> = (0..5).map(|_| graph.new_vertex().handle()).collect();
+    for (from, to) in [(0, 1), (2, 1), (2, 3)] {
+        graph.new_edge(v[from], link, v[to]).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.same_component(0, 3, link, 100).unwrap(), Some(true));
+    assert_eq!(rd.same_component(0, 4, link, 100).unwrap(), Some(false));
+    assert_eq!(rd.same_component(0, 3, link, 2).unwrap(), None);
+}
+
+#[test]
+fn test_multi_attribute() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let tag = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph
+        .new_vertex()
+        .new_multi_attribute(tag, ["rust", "db"])
+        .unwrap();
+    graph.new_vertex().new_multi_attribute(tag, ["db"]).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    for (value, expected) in [("rust", vec![0]), ("db", vec![0, 1])] {
+        let mut query = QueryBuilder::new();
+        let root = query.match_attr(tag, value).unwrap();
+        query.set_root(root);
+        assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_weighted_shortest_path() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let road = wr.register_property(None, &()).unwrap();
+    let cost = wr.register_property(None, &()).unwrap();
+
+    // 0 -> 3 directly costs 10, 0 -> 1 -> 2 -> 3 costs 6
+    let mut graph = GraphBuilder::new();
+    let v: Vec<_> = (0..4).map(|_| graph.new_vertex().handle()).collect();
+    for (from, to, weight) in [(0, 3, 10u64), (0, 1, 1), (1, 2, 2), (2, 3, 3)] {
+        graph
+            .build_edge(v[from], road, v[to])
+            .unwrap()
+            .new_attribute(cost, weight)
+            .unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(
+        rd.weighted_shortest_path(0, 3, road, cost).unwrap(),
+        Some((vec![0, 1, 2, 3], 6))
+    );
+    assert_eq!(rd.weighted_shortest_path(3, 0, road, cost).unwrap(), None);
+}
+
+#[test]
+fn test_entities_in_all_graphs() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let email = wr.register_property(None, &()).unwrap();
+    let graphs = [vec!["a", "b", "c"], vec!["b", "c"], vec!["c", "b", "d"]]
+        .into_iter()
+        .map(|emails| {
+            let mut graph = GraphBuilder::new();
+            for e in emails {
+                graph.new_vertex().new_attribute(email, e).unwrap();
+            }
+            graph
+        })
+        .collect();
+    wr.save_graphs_parallel(graphs).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let mut shared = rd.entities_in_all_graphs(email, &[0, 1, 2]).unwrap();
+    shared.sort_by_key(|v| format!("{v:?}"));
+    assert_eq!(
+        shared,
+        vec![
+            Primitive::Text("b".to_string()),
+            Primitive::Text("c".to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_extract_subgraph() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    // a -> b -> c
+    let mut graph = GraphBuilder::new();
+    let v: Vec<_> = ["a", "b", "c"]
+        .into_iter()
+        .map(|n| graph.new_vertex().new_attribute(name, n).unwrap().handle())
+        .collect();
+    graph.new_edge(v[0], link, v[1]).unwrap();
+    graph.new_edge(v[1], link, v[2]).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let sub = rd.extract_subgraph(&[2, 1]).unwrap();
+    let attrs: Vec<_> = sub
+        .iter_vertices()
+        .map(|(_, v)| v.attributes.clone())
+        .collect();
+    assert_eq!(
+        attrs,
+        vec![
+            vec![(name, Primitive::Text("c".to_string()))],
+            vec![(name, Primitive::Text("b".to_string()))],
+        ]
+    );
+    assert_eq!(sub.iter_edges().count(), 1);
+    drop(rd);
+
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![sub]).unwrap();
+    wr.commit().unwrap();
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.dump_graph(1).unwrap().edges.len(), 1);
+}
+
+#[test]
+fn test_inline_attributes() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+
+    let mut indexed = GraphBuilder::new();
+    indexed.new_vertex().new_attribute(name, "a").unwrap();
+    let mut inline = GraphBuilder::new();
+    inline.set_inline_attributes(true);
+    inline.new_vertex().new_attribute(name, "a").unwrap();
+    wr.save_graphs_parallel(vec![indexed, inline]).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let a = query.match_attr(name, "a").unwrap();
+    query.set_root(a);
+    let query = query.compile().unwrap();
+
+    // only the indexed vertex is in the index, but every lookup finds both
+    let rd = db.begin_read().unwrap();
+    #[cfg(feature = "testing")]
+    assert_eq!(rd.dump_scalar_index().unwrap().len(), 1);
+    assert_eq!(rd.which_have_attribute(&[0, 1], name).unwrap().len(), 2);
+    assert_eq!(rd.search(&query).unwrap(), vec![0, 1]);
+    assert_eq!(rd.estimate_count(&query).unwrap(), 2);
+    let values: Vec<_> = rd
+        .iter_property_index(name)
+        .unwrap()
+        .map(|entry| entry.unwrap().1.into_iter().collect::<Vec<_>>())
+        .collect();
+    assert_eq!(values, vec![vec![0, 1]]);
+
+    // switching modes moves the attributes in and out of the index
+    let mut graph = rd.load_graph(1).unwrap();
+    graph.set_inline_attributes(false);
+    let mut other = rd.load_graph(0).unwrap();
+    other.set_inline_attributes(true);
+    drop(rd);
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![graph, other]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    #[cfg(feature = "testing")]
+    assert_eq!(rd.dump_scalar_index().unwrap()[0].1, vec![1]);
+    assert_eq!(rd.which_have_attribute(&[0, 1], name).unwrap().len(), 2);
+    assert_eq!(rd.search(&query).unwrap(), vec![0, 1]);
+    assert_eq!(rd.graphs_containing(name, "a").unwrap().len(), 2);
+
+    // deleted inline graphs are no longer scanned
+    drop(rd);
+    let mut wr = db.begin_write().unwrap();
+    wr.delete_graph(0).unwrap();
+    wr.commit().unwrap();
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&query).unwrap(), vec![1]);
+}
+
+#[test]
+fn test_number_too_big_names_property() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let size = wr.register_property(Some("size"), &()).unwrap();
+    let count = wr.register_property(None, &()).unwrap();
+
+    let mut query = QueryBuilder::new();
+    let err = query.match_attr(size, u64::MAX).err().unwrap();
+    assert!(matches!(
+        err,
+        LatticeError::NumberTooBig {
+            property: Some(_),
+            alias: None,
+            ..
+        }
+    ));
+    let err = wr.describe_error(err).unwrap();
+    assert!(matches!(
+        &err,
+        LatticeError::NumberTooBig { alias: Some(alias), .. } if alias == "size"
+    ));
+    assert!(err.to_string().ends_with("for property size"));
+
+    // properties without an alias are named by id
+    let err = query.match_attr(count, u64::MAX).err().unwrap();
+    assert!(matches!(
+        wr.describe_error(err).unwrap(),
+        LatticeError::NumberTooBig {
+            property: Some(_),
+            alias: None,
+            ..
+        }
+    ));
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(
+        rd.get_property_alias(size).unwrap().as_deref(),
+        Some("size")
+    );
+    assert!(matches!(
+        rd.graphs_containing(size, u64::MAX),
+        Err(LatticeError::NumberTooBig { alias: Some(_), .. })
+    ));
+}
+
+#[test]
+fn test_format_version() {
+    let (db, file) = LatticeDb::create_temporary().unwrap();
+    assert_eq!(db.format_version().unwrap(), FORMAT_VERSION);
+    assert!(db.is_compatible());
+    drop(db);
+
+    let db = LatticeDb::open(file.path()).unwrap();
+    assert_eq!(db.format_version().unwrap(), FORMAT_VERSION);
+}
+
+#[test]
+fn test_newer_format_version() {
+    let (db, file) = LatticeDb::create_temporary().unwrap();
+    drop(db);
+
+    // a version written by a newer crate
+    let newer = u64::from(FORMAT_VERSION) + 1;
+    let metadata: redb::TableDefinition<u8, u64> = redb::TableDefinition::new("_lattice_meta");
+    let raw = redb::Database::open(file.path()).unwrap();
+    let wt = raw.begin_write().unwrap();
+    wt.open_table(metadata).unwrap().insert(2, newer).unwrap();
+    wt.commit().unwrap();
+    drop(raw);
+
+    assert!(matches!(
+        LatticeDb::open(file.path()),
+        Err(LatticeError::UnsupportedVersion(v)) if v == newer
+    ));
+    assert!(matches!(
+        LatticeDb::create(file.path()),
+        Err(LatticeError::UnsupportedVersion(v)) if v == newer
+    ));
+}
+// writes a database in the format used before the format version was stored
+fn write_legacy_database(path: &std::path::Path) {
+    let config = lattice_db::bincode::config::standard();
+    let text = Primitive::Text("a".to_string());
+    let raw = redb::Database::create(path).unwrap();
+    let wt = raw.begin_write().unwrap();
+    let graphs: redb::TableDefinition<u64, Vec<u8>> = redb::TableDefinition::new("_lattice_graphs");
+    let props: redb::TableDefinition<u64, Vec<u8>> = redb::TableDefinition::new("_lattice_props");
+    let prop_names: redb::TableDefinition<&str, u64> =
+        redb::TableDefinition::new("_lattice_prop_name_to_id");
+    let queries: redb::TableDefinition<u64, Vec<u8>> =
+        redb::TableDefinition::new("_lattice_saved_queries");
+    let query_names: redb::TableDefinition<&str, u64> =
+        redb::TableDefinition::new("_lattice_query_names");
+    let scalar: redb::TableDefinition<(u64, u64), Vec<u8>> =
+        redb::TableDefinition::new("_lattice_idx_s");
+
+    // (id, [(vertex id, [(property id, value)])], [(from, label, to)])
+    let graph = (
+        0u64,
+        vec![(0u64, vec![(0u64, text.clone())]), (1, vec![])],
+        vec![(0u64, 0u64, 1u64)],
+    );
+    let bytes = lattice_db::bincode::encode_to_vec(graph, config).unwrap();
+    wt.open_table(graphs).unwrap().insert(0, bytes).unwrap();
+    let meta = lattice_db::bincode::encode_to_vec(7u64, config).unwrap();
+    wt.open_table(props).unwrap().insert(0, meta).unwrap();
+    wt.open_table(prop_names)
+        .unwrap()
+        .insert("name", 0)
+        .unwrap();
+
+    // an attribute node, variant 3
+    let query = (vec![(3u32, 0u64, text.hash())], 0usize);
+    let bytes = lattice_db::bincode::encode_to_vec(query, config).unwrap();
+    wt.open_table(queries).unwrap().insert(0, bytes).unwrap();
+    wt.open_table(query_names).unwrap().insert("q", 0).unwrap();
+    let mut bitmap = lattice_db::roaring::RoaringTreemap::new();
+    bitmap.insert(0);
+    let mut bytes = vec![];
+    bitmap.serialize_into(&mut bytes).unwrap();
+    wt.open_table(scalar)
+        .unwrap()
+        .insert((0, text.hash()), bytes)
+        .unwrap();
+    wt.commit().unwrap();
+}
+
+#[test]
+fn test_upgrade_legacy_format() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let text = Primitive::Text("a".to_string());
+    write_legacy_database(file.path());
+
+    let db = LatticeDb::open(file.path()).unwrap();
+    assert_eq!(db.format_version().unwrap(), FORMAT_VERSION);
+    let rd = db.begin_read().unwrap();
+    let name = rd.get_property_handle("name").unwrap().unwrap();
+    assert_eq!(name.options(), PropertyOptions::default());
+    assert_eq!(rd.get_property_meta::<u64>(name).unwrap(), 7);
+    let graph = rd.dump_graph(0).unwrap();
+    assert_eq!(graph.vertices[0].attributes, vec![(name, text)]);
+    assert_eq!(graph.edges.len(), 1);
+    let query = rd
+        .get_prepared_query(rd.get_query_handle("q").unwrap().unwrap())
+        .unwrap();
+    assert_eq!(rd.search(&query).unwrap(), vec![0]);
+
+    // text saved before the ordered text index existed is backfilled
+    let mut query = QueryBuilder::new();
+    let root = query.match_prefix(name, "a");
+    query.set_root(root);
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![0]);
+}
+
+#[test]
+fn test_create_over_legacy_format() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    write_legacy_database(file.path());
+
+    // the legacy data was written with the default encoding, not the requested one
+    let options = DbOptions {
+        int_encoding: IntEncoding::Fixed,
+        ..Default::default()
+    };
+    let db = LatticeDb::create_with_options(file.path(), options).unwrap();
+    assert_eq!(db.int_encoding(), IntEncoding::Variable);
+    assert_eq!(db.format_version().unwrap(), FORMAT_VERSION);
+    let rd = db.begin_read().unwrap();
+    let name = rd.get_property_handle("name").unwrap().unwrap();
+    assert_eq!(rd.get_property_meta::<u64>(name).unwrap(), 7);
+    assert_eq!(rd.dump_graph(0).unwrap().edges.len(), 1);
+    drop(rd);
+    drop(db);
+
+    let db = LatticeDb::open(file.path()).unwrap();
+    assert_eq!(db.int_encoding(), IntEncoding::Variable);
+}
+
+#[test]
+fn test_remove_edges_by_label() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let keep = wr.register_property(None, &()).unwrap();
+    let drop = wr.register_property(None, &()).unwrap();
+
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    graph.new_edge(a, keep, b).unwrap();
+    graph.new_edge(a, drop, b).unwrap();
+    graph.new_edge(b, drop, a).unwrap();
+    graph.new_edge(a, drop, a).unwrap();
+
+    assert_eq!(graph.remove_edges_by_label(drop), 3);
+    assert_eq!(graph.remove_edges_by_label(drop), 0);
+    assert_eq!(graph.iter_edges().count(), 1);
+
+    // only the remaining edge is still attached
+    let a = graph.get_vertex(a).unwrap();
+    assert_eq!(a.outgoing_edges.len(), 1);
+    assert!(a.incoming_edges.is_empty());
+    let b = graph.get_vertex(b).unwrap();
+    assert_eq!(b.incoming_edges.len(), 1);
+    assert!(b.outgoing_edges.is_empty());
+}
+
+#[test]
+fn test_degree() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    graph.new_edge(a, link, b).unwrap();
+    graph.new_edge(a, link, b).unwrap();
+    graph.new_edge(a, link, a).unwrap();
+
+    // parallel edges count separately, a self loop counts in both directions
+    let out = EdgeDirection::Outgoing;
+    let inc = EdgeDirection::Incoming;
+    assert_eq!(graph.degree(a, out).unwrap(), 3);
+    assert_eq!(graph.degree(a, inc).unwrap(), 1);
+    assert_eq!(graph.degree(b, out).unwrap(), 0);
+    assert_eq!(graph.degree(b, inc).unwrap(), 2);
+
+    let ab = EdgeHandle::from_raw(graph.iter_edges().next().unwrap().0);
+    graph.remove_edge(ab).unwrap();
+    assert_eq!(graph.degree(a, out).unwrap(), 2);
+    assert_eq!(graph.degree(b, inc).unwrap(), 1);
+
+    graph.remove_vertex(b).unwrap();
+    assert_eq!(graph.degree(a, out).unwrap(), 1);
+    assert!(matches!(
+        graph.degree(b, out),
+        Err(LatticeError::VertexNotFound)
+    ));
+}
+
+#[test]
+fn test_estimate_count() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    // hub -> three leaves
+    let mut graph = GraphBuilder::new();
+    let hub = graph
+        .new_vertex()
+        .new_attribute(name, "hub")
+        .unwrap()
+        .handle();
+    for _ in 0..3 {
+        let leaf = graph
+            .new_vertex()
+            .new_attribute(name, "leaf")
+            .unwrap()
+            .handle();
+        graph.new_edge(hub, link, leaf).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    let rd = db.begin_read().unwrap();
+
+    // leaf nodes are exact
+    let mut query = QueryBuilder::new();
+    let leaves = query.match_attr(name, "leaf").unwrap();
+    query.set_root(leaves);
+    assert_eq!(rd.estimate_count(&query.compile().unwrap()).unwrap(), 3);
+
+    // edge expansions count the neighbors of their targets
+    let mut query = QueryBuilder::new();
+    let hub = query.match_attr(name, "hub").unwrap();
+    let out = query.match_outgoing(link, hub).unwrap();
+    query.set_root(out);
+    assert_eq!(rd.estimate_count(&query.compile().unwrap()).unwrap(), 3);
+
+    // estimated unions are capped by the number of vertices
+    let leaves = query.match_attr(name, "leaf").unwrap();
+    let into = query.match_incoming(link, leaves).unwrap();
+    let root = query.group_or(vec![out, into]).unwrap();
+    query.set_root(root);
+    assert_eq!(rd.estimate_count(&query.compile().unwrap()).unwrap(), 4);
+}
+
+#[test]
+fn test_property_alias() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let email = wr.register_property("email", &()).unwrap();
+    let other = wr.register_property("other", &()).unwrap();
+    wr.add_property_alias(email, "e-mail").unwrap();
+    wr.add_property_alias(email, "mail").unwrap();
+    assert!(matches!(
+        wr.add_property_alias(other, "mail"),
+        Err(LatticeError::AliasAlreadyExists)
+    ));
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    for alias in ["email", "e-mail", "mail"] {
+        assert_eq!(rd.get_property_handle(alias).unwrap(), Some(email));
+    }
+}
+
+#[test]
+fn test_delete_property() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let email = wr.register_property("email", &()).unwrap();
+    let other = wr.register_property("other", &()).unwrap();
+    wr.add_property_alias(email, "mail").unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(email, "a@b.c").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut wr = db.begin_write().unwrap();
+    wr.delete_property(email).unwrap();
+    assert!(matches!(
+        wr.delete_property(email),
+        Err(LatticeError::PropertyNotFound)
+    ));
+    let again = wr.register_property("email", &()).unwrap();
+    wr.add_property_alias(again, "mail").unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_ne!(again, email);
+    assert_eq!(rd.get_property_handle("email").unwrap(), Some(again));
+    assert_eq!(rd.get_property_handle("other").unwrap(), Some(other));
+    assert!(matches!(
+        rd.get_property_meta::<()>(email),
+        Err(LatticeError::PropertyNotFound)
+    ));
+    assert!(rd.which_have_attribute(&[0], email).unwrap().is_empty());
+}
+
+#[test]
+fn test_delete_property_purges_edge_and_inline_attributes() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let road = wr.register_property(None, &()).unwrap();
+    let cost = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    graph
+        .build_edge(a, road, b)
+        .unwrap()
+        .new_attribute(cost, 5u64)
+        .unwrap();
+    let mut inline = GraphBuilder::new();
+    inline.set_inline_attributes(true);
+    inline.new_vertex().new_attribute(cost, 5u64).unwrap();
+    wr.save_graphs_parallel(vec![graph, inline]).unwrap();
+    wr.commit().unwrap();
+
+    let mut wr = db.begin_write().unwrap();
+    wr.delete_property(cost).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(cost, 5u64).unwrap();
+    query.set_root(root);
+    let rd = db.begin_read().unwrap();
+    assert!(rd.edges_by_weight(road, cost, ..).unwrap().is_empty());
+    assert!(rd.search(&query.compile().unwrap()).unwrap().is_empty());
+    assert!(rd.which_have_attribute(&[2], cost).unwrap().is_empty());
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_dump_scalar_index() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let size = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(size, 7u64).unwrap();
+    graph.new_vertex().new_attribute(size, 7u64).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let dump = rd.dump_scalar_index().unwrap();
+    assert_eq!(dump.len(), 1);
+    assert_eq!(dump[0].0.1, size.hash_value(&Primitive::UInt(7)));
+    assert_eq!(dump[0].1, vec![0, 1]);
+}
+
+#[test]
+fn test_include_saved_query() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let status = wr.register_property(None, &()).unwrap();
+    let role = wr.register_property(None, &()).unwrap();
+
+    let mut graph = GraphBuilder::new();
+    for (s, r) in [("active", "admin"), ("active", "user"), ("banned", "admin")] {
+        graph
+            .new_vertex()
+            .new_attribute(status, s)
+            .unwrap()
+            .new_attribute(role, r)
+            .unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+
+    let mut active = QueryBuilder::new();
+    let node = active.match_attr(status, "active").unwrap();
+    active.set_root(node);
+    let handle = wr.save_query(&active, "active", &()).unwrap();
+    wr.commit().unwrap();
+
+    // the saved query was never materialized, so it is evaluated inline
+    let mut query = QueryBuilder::new();
+    let saved = query.include_saved(handle.id());
+    let admin = query.match_attr(role, "admin").unwrap();
+    let both = query.group_and(vec![saved, admin]).unwrap();
+    query.set_root(both);
+
+    let rd = db.begin_read().unwrap();
+    let compiled = query.compile().unwrap();
+    assert_eq!(rd.search(&compiled).unwrap(), vec![0]);
+    assert_eq!(rd.estimate_count(&compiled).unwrap(), 1);
+    drop(rd);
+
+    // saved queries can reference other saved queries
+    let mut wr = db.begin_write().unwrap();
+    let nested = wr.save_query(&query, "active admins", &()).unwrap();
+    wr.commit().unwrap();
+    let mut query = QueryBuilder::new();
+    let outer = query.include_saved(nested.id());
+    let inner = query.include_saved(handle.id());
+    let either = query.group_or(vec![outer, inner]).unwrap();
+    query.set_root(either);
+
+    let rd = db.begin_read().unwrap();
+    let (ids, sizes) = rd.search_traced(&query.compile().unwrap()).unwrap();
+    assert_eq!(ids, vec![0, 1]);
+    assert_eq!(sizes, vec![2, 1, 2]);
+}
+
+#[test]
+fn test_saved_query_cycle() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+
+    // the first saved query gets id 0, so it references itself
+    let mut query = QueryBuilder::new();
+    let node = query.include_saved(0);
+    query.set_root(node);
+    let handle = wr.save_query(&query, None, &()).unwrap();
+    assert_eq!(handle.id(), 0);
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let query = query.compile().unwrap();
+    assert!(matches!(rd.search(&query), Err(LatticeError::QueryCycle)));
+}
+
+#[test]
+fn test_change_graph_id() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let mut graphs = vec![];
+    for n in ["a", "b"] {
+        let mut graph = GraphBuilder::new();
+        graph.new_vertex().new_attribute(name, n).unwrap();
+        graphs.push((graph, Some(n.to_string())));
+    }
+    wr.save_graphs_with_meta(graphs).unwrap();
+
+    assert!(matches!(
+        wr.change_graph_id(0, 1),
+        Err(LatticeError::GraphIdTaken)
+    ));
+    wr.change_graph_id(0, 10).unwrap();
+
+    // new graphs are assigned ids after the moved graph
+    wr.save_graphs_parallel(vec![GraphBuilder::new()]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert!(matches!(rd.load_graph(0), Err(LatticeError::GraphNotFound)));
+    assert_eq!(rd.dump_graph(10).unwrap().vertices[0].id, 0);
+    assert_eq!(
+        rd.get_graph_meta::<String>(10).unwrap(),
+        Some("a".to_string())
+    );
+    assert_eq!(
+        rd.get_graph_ids_from_vertices(&[0, 1]).unwrap(),
+        vec![Some(10), Some(1)]
+    );
+    assert!(rd.load_graph(11).is_ok());
+}
+
+#[test]
+fn test_search_page() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let kind = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    for _ in 0..5 {
+        graph.new_vertex().new_attribute(kind, "item").unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let items = query.match_attr(kind, "item").unwrap();
+    query.set_root(items);
+    let query = query.compile().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search_page(&query, 0, 2).unwrap(), (vec![0, 1], 5));
+    assert_eq!(rd.search_page(&query, 4, 2).unwrap(), (vec![4], 5));
+    assert_eq!(rd.search_page(&query, 9, 2).unwrap(), (vec![], 5));
+}
+
+#[test]
+fn test_count() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    // hub with many followers
+    let mut graph = GraphBuilder::new();
+    let hub = graph
+        .new_vertex()
+        .new_attribute(name, "hub")
+        .unwrap()
+        .handle();
+    for _ in 0..100 {
+        let follower = graph.new_vertex().handle();
+        graph.new_edge(follower, link, hub).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let mut query = QueryBuilder::new();
+    let hub = query.match_attr(name, "hub").unwrap();
+    let followers = query.match_incoming(link, hub).unwrap();
+    query.set_root(followers);
+    let compiled = query.compile().unwrap();
+    assert_eq!(rd.count(&compiled).unwrap(), 100);
+    assert_eq!(
+        rd.count(&compiled).unwrap(),
+        rd.search(&compiled).unwrap().len() as u64
+    );
+
+    // followers have no followers of their own
+    let root = query.match_incoming(link, followers).unwrap();
+    query.set_root(root);
+    assert_eq!(rd.count(&query.compile().unwrap()).unwrap(), 0);
+}
+
+#[test]
+fn test_overflow_store_as_bytes() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let size = wr.register_property(None, &()).unwrap();
+
+    let big = u64::MAX - 1;
+    let mut graph = GraphBuilder::new();
+    assert!(graph.new_vertex().new_attribute(size, big).is_err());
+    graph.set_overflow_policy(OnOverflow::StoreAsBytes);
+    graph.new_vertex().new_attribute(size, big).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let node = query.match_attr(size, big.to_be_bytes().to_vec()).unwrap();
+    query.set_root(node);
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![1]);
+    assert_eq!(
+        rd.dump_graph(0).unwrap().vertices[1].attributes,
+        vec![(size, Primitive::Bytes(big.to_be_bytes().to_vec()))]
+    );
+}
+
+#[test]
+fn test_relabel_edges() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let old = wr.register_property(None, &()).unwrap();
+    let new = wr.register_property(None, &()).unwrap();
+
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    graph.new_edge(a, old, b).unwrap();
+    graph.new_edge(b, old, a).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let mut graph = rd.load_graph(0).unwrap();
+    drop(rd);
+    assert_eq!(graph.relabel_edges(old, new), 2);
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // the diff moves the edges between index keys
+    let rd = db.begin_read().unwrap();
+    assert!(!rd.has_edge(0, old, 1).unwrap());
+    assert!(rd.has_edge(0, new, 1).unwrap());
+    assert!(rd.has_edge(1, new, 0).unwrap());
+}
+
+#[test]
+fn test_iter_committed_edges() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    graph.new_edge(a, link, b).unwrap();
+    graph.new_edge(b, link, a).unwrap();
+    assert_eq!(graph.iter_committed_edges().count(), 0);
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // edges to vertices that were not saved yet are skipped
+    let rd = db.begin_read().unwrap();
+    let mut graph = rd.load_graph(0).unwrap();
+    let c = graph.new_vertex().handle();
+    graph.new_edge(a, link, c).unwrap();
+    let edges: Vec<_> = graph.iter_committed_edges().collect();
+    assert_eq!(edges, vec![(0, link, 1), (1, link, 0)]);
+    assert_eq!(graph.iter_edges().count(), 3);
+}
+
+#[test]
+fn test_search_pending() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    let mut graph = GraphBuilder::new();
+    let a = graph
+        .new_vertex()
+        .new_attribute(name, "a")
+        .unwrap()
+        .handle();
+    let b = graph.new_vertex().handle();
+    graph.new_edge(a, link, b).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+
+    let mut query = QueryBuilder::new();
+    let a = query.match_attr(name, "a").unwrap();
+    let out = query.match_outgoing(link, a).unwrap();
+    query.set_root(out);
+    let query = query.compile().unwrap();
+
+    // the writer sees its own changes before they are committed
+    assert_eq!(wr.search_pending(&query).unwrap(), vec![1]);
+    assert!(db.begin_read().unwrap().search(&query).unwrap().is_empty());
+    wr.commit().unwrap();
+    assert_eq!(db.begin_read().unwrap().search(&query).unwrap(), vec![1]);
+}
+
+#[test]
+fn test_delete_graphs() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let mut graphs = vec![];
+    for _ in 0..3 {
+        let mut graph = GraphBuilder::new();
+        let a = graph
+            .new_vertex()
+            .new_attribute(name, "a")
+            .unwrap()
+            .handle();
+        let b = graph.new_vertex().handle();
+        graph.new_edge(a, link, b).unwrap();
+        graphs.push(graph);
+    }
+    wr.save_graphs_parallel(graphs).unwrap();
+    wr.commit().unwrap();
+
+    let mut wr = db.begin_write().unwrap();
+    wr.delete_graphs(&[0, 2]).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let a = query.match_attr(name, "a").unwrap();
+    let out = query.match_outgoing(link, a).unwrap();
+    query.set_root(out);
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![3]);
+    assert!(matches!(rd.load_graph(0), Err(LatticeError::GraphNotFound)));
+    assert_eq!(
+        rd.get_graph_ids_from_vertices(&[0, 2]).unwrap(),
+        vec![None, Some(1)]
+    );
+    drop(rd);
+
+    let mut wr = db.begin_write().unwrap();
+    assert!(matches!(
+        wr.delete_graphs(&[0]),
+        Err(LatticeError::GraphNotFound)
+    ));
+}
+
+#[test]
+fn test_delete_graph() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph
+        .new_vertex()
+        .new_attribute(name, "a")
+        .unwrap()
+        .handle();
+    let b = graph.new_vertex().handle();
+    graph.new_edge(a, link, b).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let a = query.match_attr(name, "a").unwrap();
+    let out = query.match_outgoing(link, a).unwrap();
+    query.set_root(out);
+    let query = query.compile().unwrap();
+    assert_eq!(db.begin_read().unwrap().search(&query).unwrap(), vec![1]);
+
+    let mut wr = db.begin_write().unwrap();
+    wr.delete_graph(0).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert!(rd.search(&query).unwrap().is_empty());
+    assert!(matches!(rd.load_graph(0), Err(LatticeError::GraphNotFound)));
+    assert_eq!(
+        rd.get_graph_ids_from_vertices(&[0, 1]).unwrap(),
+        vec![None, None]
+    );
+}
+
+#[test]
+fn test_match_degree() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    // a links to b and c, b links to c
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    let c = graph.new_vertex().handle();
+    graph.new_edge(a, link, b).unwrap();
+    graph.new_edge(a, link, c).unwrap();
+    graph.new_edge(b, link, c).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let search = |dir, predicate| {
+        let mut query = QueryBuilder::new();
+        let node = query.match_degree(link, dir, predicate);
+        query.set_root(node);
+        rd.search(&query.compile().unwrap()).unwrap()
+    };
+    assert_eq!(
+        search(EdgeDirection::Outgoing, DegreePredicate::Eq(2)),
+        vec![0]
+    );
+    assert_eq!(
+        search(EdgeDirection::Outgoing, DegreePredicate::Gt(0)),
+        vec![0, 1]
+    );
+    assert_eq!(
+        search(EdgeDirection::Incoming, DegreePredicate::Eq(2)),
+        vec![2]
+    );
+    // vertices without edges of the label have degree 0
+    assert_eq!(
+        search(EdgeDirection::Outgoing, DegreePredicate::Lt(1)),
+        vec![2]
+    );
+    assert_eq!(
+        search(EdgeDirection::Incoming, DegreePredicate::Eq(0)),
+        vec![0]
+    );
+}
+
+#[test]
+fn test_open_creates_missing_tables() {
+    // a redb file without any lattice tables
+    let file = tempfile::NamedTempFile::new().unwrap();
+    drop(redb::Database::create(file.path()).unwrap());
+
+    let db = LatticeDb::open(file.path()).unwrap();
+    let prop = db
+        .begin_read()
+        .unwrap()
+        .get_property_handle("name")
+        .unwrap();
+    assert!(prop.is_none());
+
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    drop(wr);
+    let mut query = QueryBuilder::new();
+    let node = query.match_attr(name, "a").unwrap();
+    query.set_root(node);
+    let rd = db.begin_read().unwrap();
+    assert!(rd.search(&query.compile().unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_interned_text_property() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let options = PropertyOptions {
+        intern_text: true,
+        ..Default::default()
+    };
+    let tag = wr
+        .register_property_with_options(None, &(), options)
+        .unwrap();
+    let mut graphs = vec![];
+    for _ in 0..2 {
+        let mut graph = GraphBuilder::new();
+        graph.new_vertex().new_attribute(tag, "shared").unwrap();
+        graph.new_vertex().new_attribute(tag, 7u64).unwrap();
+        graphs.push(graph);
+    }
+    wr.save_graphs_parallel(graphs).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(tag, "shared").unwrap();
+    query.set_root(root);
+    let query = query.compile().unwrap();
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&query).unwrap(), vec![0, 2]);
+
+    // loading resolves the text, and an unchanged resave keeps the index intact
+    let graph = rd.load_graph(0).unwrap();
+    drop(rd);
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&query).unwrap(), vec![0, 2]);
+    assert_eq!(
+        rd.dump_graph(1).unwrap().vertices[0].attributes,
+        vec![(tag, Primitive::Text("shared".to_string()))]
+    );
+    drop(rd);
+
+    // moving a graph keeps its text interned
+    let mut wr = db.begin_write().unwrap();
+    wr.change_graph_id(1, 5).unwrap();
+    wr.commit().unwrap();
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&query).unwrap(), vec![0, 2]);
+    assert_eq!(
+        rd.dump_graph(5).unwrap().vertices[0].attributes,
+        vec![(tag, Primitive::Text("shared".to_string()))]
+    );
+}
+
+#[test]
+fn test_mixed_direction_traversal() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let manages = wr.register_property(None, &()).unwrap();
+    let works_on = wr.register_property(None, &()).unwrap();
+
+    // alice manages bob and carol, who work on a project with dave
+    let mut graph = GraphBuilder::new();
+    let v: Vec<_> = ["alice", "bob", "carol", "project", "dave"]
+        .into_iter()
+        .map(|n| graph.new_vertex().new_attribute(name, n).unwrap().handle())
+        .collect();
+    graph.new_edge(v[0], manages, v[1]).unwrap();
+    graph.new_edge(v[0], manages, v[2]).unwrap();
+    for person in [v[1], v[2], v[4]] {
+        graph.new_edge(person, works_on, v[3]).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // managed vertices are reused at two depths of the expression
+    let mut query = QueryBuilder::new();
+    let alice = query.match_attr(name, "alice").unwrap();
+    let managed = query.match_outgoing(manages, alice).unwrap();
+    let projects = query.match_outgoing(works_on, managed).unwrap();
+    let coworkers = query.match_incoming(works_on, projects).unwrap();
+    let others = query.difference(coworkers, managed).unwrap();
+
+    let rd = db.begin_read().unwrap();
+    query.set_root(coworkers);
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![1, 2, 4]);
+    query.set_root(others);
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![4]);
+}
+
+#[test]
+fn test_unevaluated_query_nodes() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let config = lattice_db::bincode::config::standard();
+
+    // an intersect and a union, variants 1 and 0, each reading a node evaluated after them
+    let nodes = vec![(1u32, vec![1usize]), (0u32, vec![2]), (0u32, vec![])];
+    let bytes = lattice_db::bincode::encode_to_vec((nodes, 0usize), config).unwrap();
+    let (query, _): (PreparedQuery, usize) =
+        lattice_db::bincode::decode_from_slice(&bytes, config).unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert!(matches!(
+        rd.search(&query),
+        Err(LatticeError::QueryNodeNotFound)
+    ));
+    assert!(matches!(
+        rd.estimate_count(&query),
+        Err(LatticeError::QueryNodeNotFound)
+    ));
+}
+
+#[test]
+fn test_match_outgoing_where() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let rated = wr.register_property(None, &()).unwrap();
+    let score = wr.register_property(None, &()).unwrap();
+
+    // a user rating three items, one of them without a score
+    let mut graph = GraphBuilder::new();
+    let user = graph
+        .new_vertex()
+        .new_attribute(name, "user")
+        .unwrap()
+        .handle();
+    let items: Vec<_> = (0..3).map(|_| graph.new_vertex().handle()).collect();
+    for (item, value) in items[..2].iter().zip([5u64, 3]) {
+        graph
+            .build_edge(user, rated, *item)
+            .unwrap()
+            .new_attribute(score, value)
+            .unwrap();
+    }
+    graph.new_edge(user, rated, items[2]).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+
+    let mut query = QueryBuilder::new();
+    let subject = query.match_attr(name, "user").unwrap();
+    let top = query
+        .match_outgoing_where(rated, subject, score, 5u64)
+        .unwrap();
+    query.set_root(top);
+    let query = query.compile().unwrap();
+    assert_eq!(wr.search_pending(&query).unwrap(), vec![1]);
+    wr.commit().unwrap();
+    assert_eq!(db.begin_read().unwrap().search(&query).unwrap(), vec![1]);
+}
+
+#[test]
+fn test_checkpoint() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(name, "a").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    let mut wr = db.checkpoint(wr).unwrap();
+    assert!(db.try_begin_write().is_err());
+
+    // the batch is abandoned after the checkpoint
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(name, "a").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    drop(wr);
+
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(name, "a").unwrap();
+    query.set_root(root);
+    let query = query.compile().unwrap();
+    assert_eq!(db.begin_read().unwrap().search(&query).unwrap(), vec![0]);
+
+    // ids continue after the checkpointed sequences
+    let mut wr = db.begin_write().unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(name, "a").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    assert_eq!(db.begin_read().unwrap().search(&query).unwrap(), vec![0, 1]);
+}
+
+#[test]
+fn test_search_preloaded() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(name, "a").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(name, "a").unwrap();
+    query.set_root(root);
+    let query = query.compile().unwrap();
+    let rd = db.begin_read().unwrap();
+    let preloaded = rd
+        .preload(&[(name, name.hash_value(&Primitive::Text("a".to_string())))])
+        .unwrap();
+    assert_eq!(preloaded.len(), 1);
+    assert_eq!(rd.search_preloaded(&query, &preloaded).unwrap(), vec![0]);
+
+    // a newer reader sees a different snapshot
+    let mut wr = db.begin_write().unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(name, "a").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    let newer = db.begin_read().unwrap();
+    assert!(matches!(
+        newer.search_preloaded(&query, &preloaded),
+        Err(LatticeError::SnapshotMismatch)
+    ));
+    assert_eq!(newer.search(&query).unwrap(), vec![0, 1]);
+}
+
+#[test]
+fn test_checkpoint_metrics() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let reports = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+    let hook_reports = reports.clone();
+    wr.set_metrics_hook(move |metrics| hook_reports.lock().unwrap().push(metrics.graphs_saved));
+
+    // every report only covers the graphs saved since the previous one
+    for count in [2, 1] {
+        let graphs = (0..count)
+            .map(|_| {
+                let mut graph = GraphBuilder::new();
+                graph.new_vertex().new_attribute(name, "a").unwrap();
+                graph
+            })
+            .collect();
+        wr.save_graphs_parallel(graphs).unwrap();
+        wr = db.checkpoint(wr).unwrap();
+    }
+    wr.commit().unwrap();
+    assert_eq!(*reports.lock().unwrap(), vec![2, 1, 0]);
+}
+
+#[test]
+fn test_search_sample() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let kind = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    for i in 0..100u64 {
+        let kind_value = if i % 2 == 0 { "even" } else { "odd" };
+        graph.new_vertex().new_attribute(kind, kind_value).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let even = query.match_attr(kind, "even").unwrap();
+    query.set_root(even);
+    let query = query.compile().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let sample = rd.search_sample(&query, 10, 7).unwrap();
+    assert_eq!(sample.len(), 10);
+    assert!(sample.iter().all(|id| id % 2 == 0));
+    assert_eq!(rd.search_sample(&query, 10, 7).unwrap(), sample);
+    assert_eq!(rd.search_sample(&query, 80, 7).unwrap().len(), 50);
+}
+
+#[test]
+fn test_update_meta() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let weight = wr
+        .register_property("weight", &"grams".to_string())
+        .unwrap();
+    wr.update_property_meta(weight, &"kilograms".to_string())
+        .unwrap();
+
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(weight, 1u64).unwrap();
+    query.set_root(root);
+    let handle = wr.save_query(&query, "light", &1u32).unwrap();
+    wr.update_query_meta(handle, &2u32).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let meta: String = rd.get_property_meta(weight).unwrap();
+    assert_eq!(meta, "kilograms");
+    assert_eq!(rd.get_property_handle("weight").unwrap(), Some(weight));
+    let handle = rd.get_query_handle("light").unwrap().unwrap();
+    assert_eq!(rd.get_query_meta::<u32>(handle).unwrap(), 2);
+    drop(rd);
+
+    let mut wr = db.begin_write().unwrap();
+    let missing = wr.register_property(None, &()).unwrap();
+    drop(wr); // the property was never committed
+    let mut wr = db.begin_write().unwrap();
+    assert!(matches!(
+        wr.update_property_meta(missing, &()),
+        Err(LatticeError::PropertyNotFound)
+    ));
+}
+
+#[test]
+fn test_graphs_containing() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let term = wr.register_property(None, &()).unwrap();
+    let mut graphs = vec![];
+    for (i, terms) in [vec!["rust", "rust"], vec!["go"], vec!["rust"]]
+        .into_iter()
+        .enumerate()
+    {
+        let mut graph = GraphBuilder::new();
+        for t in terms {
+            graph.new_vertex().new_attribute(term, t).unwrap();
+        }
+        graph.set_inline_attributes(i == 2);
+        graphs.push(graph);
+    }
+    wr.save_graphs_parallel(graphs).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let found: Vec<u64> = rd
+        .graphs_containing(term, "rust")
+        .unwrap()
+        .into_iter()
+        .collect();
+    assert_eq!(found, vec![0, 2]);
+    assert_eq!(rd.graphs_containing(term, "go").unwrap().len(), 1);
+    assert!(rd.graphs_containing(term, "java").unwrap().is_empty());
+}
+
+#[test]
+fn test_signed_int_values() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let delta = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(delta, -5i64).unwrap();
+    graph.new_vertex().new_attribute(delta, 5u64).unwrap();
+    graph.new_vertex().new_attribute(delta, -5i8).unwrap();
+    assert!(matches!(
+        graph.new_vertex().new_attribute(delta, i64::MIN),
+        Err(LatticeError::NumberTooBig { .. })
+    ));
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // signed and unsigned values never share a hash
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(delta, -5i32).unwrap();
+    query.set_root(root);
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![0, 2]);
+    assert_eq!(
+        rd.dump_graph(0).unwrap().vertices[0].attributes,
+        vec![(delta, Primitive::Int(-5))]
+    );
+}
+
+#[test]
+fn test_float_values() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let reading = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(reading, 1.5f64).unwrap();
+    graph.new_vertex().new_attribute(reading, -0.0f64).unwrap();
+    graph.new_vertex().new_attribute(reading, 1.5f32).unwrap();
+    assert!(matches!(
+        graph.new_vertex().new_attribute(reading, f64::NAN),
+        Err(LatticeError::InvalidFloat)
+    ));
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let search = |value: f64| {
+        let mut query = QueryBuilder::new();
+        let root = query.match_attr(reading, value).unwrap();
+        query.set_root(root);
+        rd.search(&query.compile().unwrap()).unwrap()
+    };
+    assert_eq!(search(1.5), vec![0, 2]);
+    assert_eq!(search(0.0), vec![1]);
+    assert!(search(1.25).is_empty());
+
+    // floats can only be matched by value
+    let mut query = QueryBuilder::new();
+    assert!(matches!(
+        query.match_range(reading, 1.0f64..2.0),
+        Err(LatticeError::InvalidRange)
+    ));
+}
+
+#[test]
+fn test_match_range() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let age = wr.register_property(None, &()).unwrap();
+    let delta = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    for n in [5u64, 17, 18, 30, 64] {
+        let mut v = graph.new_vertex();
+        v.new_attribute(age, n).unwrap();
+        v.new_attribute(delta, 20 - n as i64).unwrap();
+    }
+    graph.new_vertex().new_attribute(age, "18").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let mut query = QueryBuilder::new();
+    let root = query.match_range(age, 17u64..31).unwrap();
+    query.set_root(root);
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![1, 2, 3]);
+
+    // signed ranges cross zero in order
+    let mut query = QueryBuilder::new();
+    let root = query.match_range(delta, -10i64..3).unwrap();
+    query.set_root(root);
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![2, 3]);
+
+    let mut query = QueryBuilder::new();
+    let root = query.match_range(age, 65u64..u64::MAX).unwrap();
+    query.set_root(root);
+    assert!(rd.search(&query.compile().unwrap()).unwrap().is_empty());
+    assert!(matches!(
+        QueryBuilder::new().match_range(age, "a".."z"),
+        Err(LatticeError::InvalidRange)
+    ));
+}
+
+#[test]
+fn test_match_prefix() {
+    fn prefix(city: PropertyHandle, prefix: &str) -> PreparedQuery {
+        let mut query = QueryBuilder::new();
+        let root = query.match_prefix(city, prefix);
+        query.set_root(root);
+        query.compile().unwrap()
+    }
+
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let city = wr.register_property(None, &()).unwrap();
+    let other = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    for name in ["San Diego", "San Jose", "Santa Fe", "Boston"] {
+        graph.new_vertex().new_attribute(city, name).unwrap();
+    }
+    graph.new_vertex().new_attribute(city, 7u64).unwrap();
+    graph
+        .new_vertex()
+        .new_attribute(other, "San Mateo")
+        .unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    assert_eq!(
+        wr.search_pending(&prefix(city, "San ")).unwrap(),
+        vec![0, 1]
+    );
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&prefix(city, "San")).unwrap(), vec![0, 1, 2]);
+    assert_eq!(rd.search(&prefix(city, "")).unwrap(), vec![0, 1, 2, 3]);
+    assert!(
+        rd.search(&prefix(city, "San Diego, CA"))
+            .unwrap()
+            .is_empty()
+    );
+
+    // removed values leave the text index
+    let mut graph = rd.load_graph(0).unwrap();
+    drop(rd);
+    let (handle, _) = graph.iter_vertices().nth(1).unwrap();
+    graph.remove_vertex(VertexHandle::from_raw(handle)).unwrap();
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&prefix(city, "San")).unwrap(), vec![0, 2]);
+}
+
+#[test]
+fn test_complement() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let color = wr.register_property(None, &()).unwrap();
+    let mut graphs = vec![];
+    for _ in 0..2 {
+        let mut graph = GraphBuilder::new();
+        for name in ["red", "blue", "red", "green"] {
+            graph.new_vertex().new_attribute(color, name).unwrap();
+        }
+        graphs.push(graph);
+    }
+    wr.save_graphs_parallel(graphs).unwrap();
+    wr.commit().unwrap();
+
+    // the other graph's vertices are outside the scope
+    let mut query = QueryBuilder::new();
+    let red = query.match_attr(color, "red").unwrap();
+    let root = query.complement(red, 1).unwrap();
+    query.set_root(root);
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![5, 7]);
+
+    let root = query.complement(root, 1).unwrap();
+    query.set_root(root);
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![4, 6]);
+
+    let missing = query.match_attr(color, "purple").unwrap();
+    let root = query.complement(missing, 2).unwrap();
+    query.set_root(root);
+    assert!(rd.search(&query.compile().unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_search_with_attrs() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let kind = wr.register_property(None, &()).unwrap();
+    let size = wr.register_property(None, &()).unwrap();
+    let mut graphs = vec![];
+    for n in 0..2u64 {
+        let mut graph = GraphBuilder::new();
+        graph.new_vertex().new_attribute(kind, "other").unwrap();
+        for i in 0..2 {
+            let mut v = graph.new_vertex();
+            v.new_attribute(kind, "box").unwrap();
+            v.new_attribute(size, n * 10 + i).unwrap();
+        }
+        graphs.push(graph);
+    }
+    wr.save_graphs_parallel(graphs).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(kind, "box").unwrap();
+    query.set_root(root);
+    let rd = db.begin_read().unwrap();
+    let results = rd.search_with_attrs(&query.compile().unwrap()).unwrap();
+    let sizes: Vec<(u64, Vec<Primitive>)> = results
+        .into_iter()
+        .map(|(id, attrs)| {
+            let values = attrs
+                .into_iter()
+                .filter(|(a, _)| *a == size)
+                .map(|(_, v)| v)
+                .collect();
+            (id, values)
+        })
+        .collect();
+    assert_eq!(
+        sizes,
+        vec![
+            (1, vec![Primitive::UInt(0)]),
+            (2, vec![Primitive::UInt(1)]),
+            (4, vec![Primitive::UInt(10)]),
+            (5, vec![Primitive::UInt(11)]),
+        ]
+    );
+}
+
+#[test]
+fn test_search_paginated() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let kind = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    for i in 0..6 {
+        let value = if i % 2 == 0 { "even" } else { "odd" };
+        graph.new_vertex().new_attribute(kind, value).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let even = query.match_attr(kind, "even").unwrap();
+    query.set_root(even);
+    let query = query.compile().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search_paginated(&query, 1, 1).unwrap(), vec![2]);
+    assert_eq!(rd.search_paginated(&query, 0, 10).unwrap(), vec![0, 2, 4]);
+    assert!(rd.search_paginated(&query, 0, 0).unwrap().is_empty());
+    assert!(rd.search_paginated(&query, 3, 10).unwrap().is_empty());
+    assert!(
+        rd.search_paginated(&query, u64::MAX, usize::MAX)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_match_depth() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let next = wr.register_property(None, &()).unwrap();
+    let pos = wr.register_property(None, &()).unwrap();
+
+    // chain 0 -> 1 -> 2 -> 3 -> 4
+    let mut graph = GraphBuilder::new();
+    let mut handles = vec![];
+    for i in 0..5u64 {
+        handles.push(graph.new_vertex().new_attribute(pos, i).unwrap().handle());
+    }
+    for pair in handles.windows(2) {
+        graph.new_edge(pair[0], next, pair[1]).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let search = |start: u64, dir: EdgeDirection, depth: u64| {
+        let mut query = QueryBuilder::new();
+        let subject = query.match_attr(pos, start).unwrap();
+        let root = match dir {
+            EdgeDirection::Outgoing => query.match_outgoing_depth(next, subject, depth),
+            EdgeDirection::Incoming => query.match_incoming_depth(next, subject, depth),
+        }
+        .unwrap();
+        query.set_root(root);
+        rd.search(&query.compile().unwrap()).unwrap()
+    };
+    assert_eq!(search(0, EdgeDirection::Outgoing, 2), vec![1, 2]);
+    assert_eq!(search(0, EdgeDirection::Outgoing, 100), vec![1, 2, 3, 4]);
+    assert_eq!(search(4, EdgeDirection::Incoming, 2), vec![2, 3]);
+    assert!(search(0, EdgeDirection::Outgoing, 0).is_empty());
+}
+
+#[test]
+fn test_match_reachable() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let next = wr.register_property(None, &()).unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+
+    // cycle a -> b -> c -> a, with d hanging off c
+    let mut graph = GraphBuilder::new();
+    let mut handles = vec![];
+    for n in ["a", "b", "c", "d"] {
+        handles.push(graph.new_vertex().new_attribute(name, n).unwrap().handle());
+    }
+    for (from, to) in [(0, 1), (1, 2), (2, 0), (2, 3)] {
+        graph.new_edge(handles[from], next, handles[to]).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let search = |start: &str, dir: EdgeDirection| {
+        let mut query = QueryBuilder::new();
+        let subject = query.match_attr(name, start).unwrap();
+        let root = query.match_reachable(next, subject, dir).unwrap();
+        query.set_root(root);
+        rd.search(&query.compile().unwrap()).unwrap()
+    };
+    assert_eq!(search("a", EdgeDirection::Outgoing), vec![0, 1, 2, 3]);
+    assert_eq!(search("a", EdgeDirection::Incoming), vec![0, 1, 2]);
+    assert!(search("d", EdgeDirection::Outgoing).is_empty());
+
+    // a constant fan-out over unbounded depth is capped by the vertex count
+    let mut query = QueryBuilder::new();
+    let subject = query.match_attr(name, "a").unwrap();
+    let root = query
+        .match_reachable(next, subject, EdgeDirection::Outgoing)
+        .unwrap();
+    query.set_root(root);
+    assert_eq!(rd.estimate_count(&query.compile().unwrap()).unwrap(), 4);
+}
+
+#[test]
+fn test_shortest_path() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let road = wr.register_property(None, &()).unwrap();
+
+    // 0 -> 1 -> 2 -> 3 with a shortcut 0 -> 2, and 4 on its own
+    let mut graph = GraphBuilder::new();
+    let v: Vec<_> = (0..5).map(|_| graph.new_vertex().handle()).collect();
+    for (from, to) in [(0, 1), (1, 2), (2, 3), (0, 2)] {
+        graph.new_edge(v[from], road, v[to]).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let out = EdgeDirection::Outgoing;
+    assert_eq!(
+        rd.shortest_path(0, 3, road, out, None).unwrap(),
+        Some(vec![0, 2, 3])
+    );
+    assert_eq!(
+        rd.shortest_path(3, 0, road, EdgeDirection::Incoming, None)
+            .unwrap(),
+        Some(vec![3, 2, 0])
+    );
+    assert_eq!(
+        rd.shortest_path(1, 1, road, out, None).unwrap(),
+        Some(vec![1])
+    );
+    assert_eq!(rd.shortest_path(0, 4, road, out, None).unwrap(), None);
+    assert_eq!(rd.shortest_path(3, 0, road, out, None).unwrap(), None);
+    assert_eq!(rd.shortest_path(0, 3, road, out, Some(1)).unwrap(), None);
+}
+
+#[test]
+fn test_remove_attribute() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let tag = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph
+        .new_vertex()
+        .new_multi_attribute(tag, ["red", "blue", "green"])
+        .unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let search = |value: &str| {
+        let mut query = QueryBuilder::new();
+        let root = query.match_attr(tag, value).unwrap();
+        query.set_root(root);
+        let rd = db.begin_read().unwrap();
+        rd.search(&query.compile().unwrap()).unwrap()
+    };
+
+    // remove one value, then the whole attribute
+    let mut graph = db.begin_read().unwrap().load_graph(0).unwrap();
+    let (handle, _) = graph.iter_vertices().next().unwrap();
+    let handle = VertexHandle::from_raw(handle);
+    graph
+        .edit_vertex(handle)
+        .unwrap()
+        .remove_attribute_value(tag, "blue")
+        .unwrap();
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    assert!(search("blue").is_empty());
+    assert_eq!(search("red"), vec![0]);
+
+    let mut graph = db.begin_read().unwrap().load_graph(0).unwrap();
+    graph
+        .edit_vertex(handle)
+        .unwrap()
+        .remove_attribute(tag)
+        .unwrap();
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    assert!(search("red").is_empty());
+    assert!(search("green").is_empty());
+    let rd = db.begin_read().unwrap();
+    assert!(rd.which_have_attribute(&[0], tag).unwrap().is_empty());
+}
+
+#[test]
+fn test_set_attribute() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let age = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph
+        .new_vertex()
+        .new_attribute(age, 30u64)
+        .unwrap()
+        .new_attribute(age, 31u64)
+        .unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut graph = db.begin_read().unwrap().load_graph(0).unwrap();
+    let (handle, _) = graph.iter_vertices().next().unwrap();
+    let handle = VertexHandle::from_raw(handle);
+    let mut vertex = graph.edit_vertex(handle).unwrap();
+    vertex.set_attribute(age, 32u64).unwrap();
+    assert!(vertex.set_attribute(age, u64::MAX).is_err());
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let mut values = vec![];
+    for entry in rd.iter_property_index(age).unwrap() {
+        let (value_hash, bitmap) = entry.unwrap();
+        values.push((value_hash, bitmap.into_iter().collect::<Vec<_>>()));
+    }
+    assert_eq!(values, vec![(Primitive::UInt(32).hash(), vec![0])]);
+}
+
+#[test]
+fn test_new_edge_unique() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let knows = wr.register_property(None, &()).unwrap();
+    let likes = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+
+    let first = graph.new_edge_unique(a, knows, b).unwrap();
+    let second = graph.new_edge_unique(a, knows, b).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(graph.iter_edges().count(), 1);
+
+    // another label or direction is a different edge
+    assert_ne!(graph.new_edge_unique(a, likes, b).unwrap(), first);
+    assert_ne!(graph.new_edge_unique(b, knows, a).unwrap(), first);
+    assert_eq!(graph.iter_edges().count(), 3);
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.edge_count_by_label().unwrap()[&knows], 2);
+}
+
+#[test]
+fn test_edges_between() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let knows = wr.register_property(None, &()).unwrap();
+    let likes = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    let c = graph.new_vertex().handle();
+    let ab = graph.build_edge(a, knows, b).unwrap().handle();
+    let ab2 = graph.build_edge(a, likes, b).unwrap().handle();
+    let ba = graph.build_edge(b, knows, a).unwrap().handle();
+    let aa = graph.build_edge(a, knows, a).unwrap().handle();
+    graph.new_edge(b, knows, c).unwrap();
+
+    assert_eq!(graph.edges_between(a, b), vec![ab, ab2]);
+    assert_eq!(graph.edges_between(b, a), vec![ba]);
+    assert_eq!(graph.edges_connecting(a, b), vec![ab, ab2, ba]);
+    assert!(graph.edges_between(a, c).is_empty());
+
+    // self-loops
+    assert_eq!(graph.edges_between(a, a), vec![aa]);
+    assert_eq!(graph.edges_connecting(a, a), vec![aa]);
+    assert!(graph.edges_connecting(c, c).is_empty());
+}
+
+#[test]
+fn test_neighbors() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    // star with the hub pointing to every leaf
+    let mut graph = GraphBuilder::new();
+    let hub = graph.new_vertex().handle();
+    let leaves: Vec<_> = (0..3).map(|_| graph.new_vertex().handle()).collect();
+    for leaf in &leaves {
+        graph.new_edge(hub, link, *leaf).unwrap();
+    }
+
+    let out = EdgeDirection::Outgoing;
+    let inc = EdgeDirection::Incoming;
+    assert_eq!(graph.neighbors(hub, out).unwrap(), leaves);
+    assert!(graph.neighbors(hub, inc).unwrap().is_empty());
+    assert_eq!(graph.neighbors(leaves[1], inc).unwrap(), vec![hub]);
+    assert_eq!(graph.degree(hub, out).unwrap(), 3);
+    assert_eq!(graph.degree(leaves[0], inc).unwrap(), 1);
+
+    // removing a leaf removes its edge from the hub
+    graph.remove_vertex(leaves[0]).unwrap();
+    let remaining = graph.neighbors(hub, out).unwrap();
+    assert_eq!(remaining.len(), 2);
+    assert!(leaves[1..].iter().all(|leaf| remaining.contains(leaf)));
+    assert_eq!(graph.degree(hub, out).unwrap(), 2);
+    assert!(matches!(
+        graph.neighbors(leaves[0], out),
+        Err(LatticeError::VertexNotFound)
+    ));
+}
+
+#[test]
+fn test_graph_from_parts() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    let vertices = vec![
+        vec![(name, Primitive::Text("a".to_string()))],
+        vec![(name, Primitive::Text("b".to_string()))],
+    ];
+    let graph = GraphBuilder::from_parts(vertices.clone(), vec![(0, link, 1)]).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // the edge points from "a" to "b" and not the other way around
+    let rd = db.begin_read().unwrap();
+    for (from, to) in [("a", vec![1]), ("b", vec![])] {
+        let mut query = QueryBuilder::new();
+        let from = query.match_attr(name, from).unwrap();
+        let root = query.match_outgoing(link, from).unwrap();
+        query.set_root(root);
+        assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), to);
+    }
+
+    // edges must reference one of the given vertices
+    assert!(GraphBuilder::from_parts(vertices, vec![(0, link, 2)]).is_err());
+    assert!(
+        GraphBuilder::from_parts(vec![vec![(name, Primitive::UInt(u64::MAX))]], vec![]).is_err()
+    );
+}
+
+#[test]
+fn test_search_records() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let kind = wr.register_property(None, &()).unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let mut graphs = vec![];
+    for names in [["ann", "bob"], ["cat", "dan"]] {
+        let mut graph = GraphBuilder::new();
+        for n in names {
+            graph
+                .new_vertex()
+                .new_attribute(kind, "user")
+                .unwrap()
+                .new_attribute(name, n)
+                .unwrap();
+        }
+        graphs.push(graph);
+    }
+    wr.save_graphs_parallel(graphs).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let users = query.match_attr(kind, "user").unwrap();
+    query.set_root(users);
+    let rd = db.begin_read().unwrap();
+    let records = rd.search_records(&query.compile().unwrap()).unwrap();
+
+    // records keep the order of the results, with the graph of each vertex
+    let ids: Vec<_> = records.iter().map(|r| (r.id, r.graph_id)).collect();
+    assert_eq!(ids, vec![(0, 0), (1, 0), (2, 1), (3, 1)]);
+    assert_eq!(
+        records[2].attributes,
+        vec![
+            (kind, Primitive::Text("user".to_string())),
+            (name, Primitive::Text("cat".to_string()))
+        ]
+    );
+}
+
+#[test]
+fn test_limited_fan_out() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let kind = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    // three hubs, each pointing to its own leaf
+    let mut graph = GraphBuilder::new();
+    for _ in 0..3 {
+        let hub = graph
+            .new_vertex()
+            .new_attribute(kind, "hub")
+            .unwrap()
+            .handle();
+        let leaf = graph.new_vertex().handle();
+        graph.new_edge(hub, link, leaf).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let search = |max_targets: u64| {
+        let mut query = QueryBuilder::new();
+        let hubs = query.match_attr(kind, "hub").unwrap();
+        let root = query
+            .match_outgoing_limited(link, hubs, max_targets)
+            .unwrap();
+        query.set_root(root);
+        rd.search_partial(&query.compile().unwrap()).unwrap()
+    };
+    assert_eq!(search(2), (vec![1, 3], true));
+    assert_eq!(search(3), (vec![1, 3, 5], false));
+
+    // only the first targets are expanded
+    let mut query = QueryBuilder::new();
+    let hubs = query.match_attr(kind, "hub").unwrap();
+    let leaves = query.match_outgoing(link, hubs).unwrap();
+    let root = query.match_incoming_limited(link, leaves, 1).unwrap();
+    query.set_root(root);
+    assert_eq!(
+        rd.search_partial(&query.compile().unwrap()).unwrap(),
+        (vec![0], true)
+    );
+}
+
+#[test]
+fn test_register_property_with_id() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let options = PropertyOptions::default();
+    let ten = wr
+        .register_property_with_id(10, "ten", &(), options)
+        .unwrap();
+    assert!(matches!(
+        wr.register_property_with_id(10, "other", &(), options),
+        Err(LatticeError::PropertyIdTaken)
+    ));
+    assert!(matches!(
+        wr.register_property_with_id(20, "ten", &(), options),
+        Err(LatticeError::AliasAlreadyExists)
+    ));
+
+    // auto assigned ids continue after the given id, also in later writers
+    let next = wr.register_property(None, &()).unwrap();
+    assert!(matches!(
+        wr.register_property_with_id(11, None, &(), options),
+        Err(LatticeError::PropertyIdTaken)
+    ));
+    wr.commit().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    wr.register_property(None, &()).unwrap();
+    assert!(matches!(
+        wr.register_property_with_id(12, None, &(), options),
+        Err(LatticeError::PropertyIdTaken)
+    ));
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.get_property_handle("ten").unwrap(), Some(ten));
+    assert_ne!(next, ten);
+}
+
+#[test]
+fn test_dump_graph() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph
+        .new_vertex()
+        .new_attribute(name, "a")
+        .unwrap()
+        .handle();
+    let b = graph.new_vertex().handle();
+    graph.new_edge(a, link, b).unwrap();
+    wr.save_graphs_parallel(vec![GraphBuilder::new(), graph])
+        .unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let expected = GraphDump {
+        id: 1,
+        vertices: vec![
+            VertexDump {
+                id: 0,
+                attributes: vec![(name, Primitive::Text("a".to_string()))],
+            },
+            VertexDump {
+                id: 1,
+                attributes: vec![],
+            },
+        ],
+        edges: vec![EdgeDump {
+            from: 0,
+            label: link,
+            to: 1,
+        }],
+    };
+    assert_eq!(rd.dump_graph(1).unwrap(), expected);
+    assert!(rd.dump_graph(0).unwrap().vertices.is_empty());
+    assert!(rd.dump_graph(2).is_err());
+}
+
+#[test]
+fn test_match_attr_hash() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let color = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    for c in ["red", "blue", "red"] {
+        graph.new_vertex().new_attribute(color, c).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let search = |value_hash: u64| {
+        let mut query = QueryBuilder::new();
+        let root = query.match_attr_hash(color, value_hash);
+        query.set_root(root);
+        rd.search(&query.compile().unwrap()).unwrap()
+    };
+    let hash = |text: &str| color.hash_value(&Primitive::Text(text.to_string()));
+    assert_eq!(search(hash("red")), vec![0, 2]);
+    assert_eq!(search(hash("blue")), vec![1]);
+    assert!(search(hash("green")).is_empty());
+}
+
+#[test]
+fn test_handle_round_trip() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    graph.new_edge(a, link, b).unwrap();
+    let edge = EdgeHandle::from_raw(graph.iter_edges().next().unwrap().0);
+
+    // handles survive encoding and their raw parts
+    let config = lattice_db::bincode::config::standard();
+    let bytes = lattice_db::bincode::encode_to_vec(b, config).unwrap();
+    let (decoded, _): (VertexHandle, usize) =
+        lattice_db::bincode::decode_from_slice(&bytes, config).unwrap();
+    assert_eq!(decoded, b);
+    let raw = edge.raw();
+    let rebuilt = EdgeHandle::from_raw(Handle::from_parts(raw.generation(), raw.index()));
+    assert_eq!(rebuilt, edge);
+    assert!(graph.get_vertex(decoded).is_some());
+
+    // a reused slot does not match the old handle
+    graph.remove_vertex(b).unwrap();
+    let c = graph.new_vertex().handle();
+    assert_eq!(c.raw().index(), b.raw().index());
+    assert!(graph.get_vertex(decoded).is_none());
+    assert!(graph.remove_edge(rebuilt).is_err());
+}
+
+#[test]
+fn test_commit_metrics() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let reports = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+    let hook_reports = reports.clone();
+    wr.set_metrics_hook(move |metrics| hook_reports.lock().unwrap().push(metrics));
+    let graphs = (0..3)
+        .map(|_| {
+            let mut graph = GraphBuilder::new();
+            let a = graph
+                .new_vertex()
+                .new_attribute(name, "a")
+                .unwrap()
+                .handle();
+            let b = graph.new_vertex().handle();
+            graph.new_edge(a, link, b).unwrap();
+            graph
+        })
+        .collect();
+    wr.save_graphs_parallel(graphs).unwrap();
+    assert!(reports.lock().unwrap().is_empty());
+    wr.commit().unwrap();
+
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 1);
+    let metrics = reports[0];
+    assert_eq!(metrics.graphs_saved, 3);
+    assert!(metrics.scalar_bytes > 0);
+    assert!(metrics.forward_bytes > 0);
+    assert!(metrics.reverse_bytes > 0);
+}
+
+#[test]
+fn test_search_with_deadline() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(name, "a").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(name, "a").unwrap();
+    query.set_root(root);
+    let query = query.compile().unwrap();
+    let rd = db.begin_read().unwrap();
+    let now = std::time::Instant::now();
+    let later = now + std::time::Duration::from_secs(60);
+    assert_eq!(rd.search_with_deadline(&query, later).unwrap(), vec![0]);
+    let err = rd.search_with_deadline(&query, now).unwrap_err();
+    assert_eq!(err.to_string(), "Search deadline exceeded");
+}
+
+#[test]
+fn test_search_cancellable() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let hub = graph
+        .new_vertex()
+        .new_attribute(name, "hub")
+        .unwrap()
+        .handle();
+    for _ in 0..3 {
+        let leaf = graph.new_vertex().handle();
+        graph.new_edge(leaf, link, hub).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let hub = query.match_attr(name, "hub").unwrap();
+    let leaves = query.match_incoming(link, hub).unwrap();
+    query.set_root(leaves);
+    let query = query.compile().unwrap();
+    let rd = db.begin_read().unwrap();
+    let cancel = std::sync::atomic::AtomicBool::new(false);
+    assert_eq!(
+        rd.search_cancellable(&query, &cancel).unwrap(),
+        vec![1, 2, 3]
+    );
+    cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    let err = rd.search_cancellable(&query, &cancel).unwrap_err();
+    assert_eq!(err.to_string(), "Search cancelled");
+}
+
+#[test]
+fn test_dedup_edges() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let other = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    for _ in 0..3 {
+        graph.new_edge(a, link, b).unwrap();
+    }
+    graph.new_edge(b, link, a).unwrap();
+    graph.new_edge(a, other, b).unwrap();
+
+    // only exact repeats of source, label, and destination are removed
+    assert_eq!(graph.dedup_edges(), 2);
+    assert_eq!(graph.dedup_edges(), 0);
+    assert_eq!(graph.iter_edges().count(), 3);
+    assert_eq!(graph.get_vertex(a).unwrap().outgoing_edges.len(), 2);
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.dump_graph(0).unwrap().edges.len(), 3);
+}
+
+#[test]
+fn test_top_degree_vertices() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let other = wr.register_property(None, &()).unwrap();
+
+    // every vertex points to all later vertices
+    let mut graph = GraphBuilder::new();
+    let handles: Vec<_> = (0..4).map(|_| graph.new_vertex().handle()).collect();
+    for (i, from) in handles.iter().enumerate() {
+        for to in &handles[i + 1..] {
+            graph.new_edge(*from, link, *to).unwrap();
+        }
+    }
+    graph.new_edge(handles[3], other, handles[0]).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let out = EdgeDirection::Outgoing;
+    let inc = EdgeDirection::Incoming;
+    assert_eq!(
+        rd.top_degree_vertices(link, out, 2).unwrap(),
+        vec![(0, 3), (1, 2)]
+    );
+    assert_eq!(
+        rd.top_degree_vertices(link, inc, 10).unwrap(),
+        vec![(3, 3), (2, 2), (1, 1)]
+    );
+    assert_eq!(rd.top_degree_vertices(other, out, 5).unwrap(), vec![(3, 1)]);
+    assert!(rd.top_degree_vertices(link, out, 0).unwrap().is_empty());
+}
+
+#[test]
+fn test_null_values() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let email = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph
+        .new_vertex()
+        .new_attribute(email, Some("a@example.com"))
+        .unwrap();
+    graph
+        .new_vertex()
+        .new_attribute(email, None::<&str>)
+        .unwrap();
+    graph.new_vertex();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // an explicit null is matched, a missing attribute is not
+    let rd = db.begin_read().unwrap();
+    let search = |value: Option<&str>| {
+        let mut query = QueryBuilder::new();
+        let root = query.match_attr(email, value).unwrap();
+        query.set_root(root);
+        rd.search(&query.compile().unwrap()).unwrap()
+    };
+    assert_eq!(search(None), vec![1]);
+    assert_eq!(search(Some("a@example.com")), vec![0]);
+    assert_ne!(
+        Primitive::Null.hash(),
+        Primitive::Text(String::new()).hash()
+    );
+    assert_eq!(
+        rd.dump_graph(0).unwrap().vertices[1].attributes,
+        vec![(email, Primitive::Null)]
+    );
+}
+
+#[test]
+fn test_edge_labels() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let follows = wr.register_property(None, &()).unwrap();
+    let blocks = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    let c = graph.new_vertex().handle();
+    graph.new_edge(a, follows, b).unwrap();
+    graph.new_edge(a, follows, c).unwrap();
+    graph.new_edge(a, blocks, c).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // each label is listed once, in id order
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.outgoing_labels(0).unwrap(), vec![follows, blocks]);
+    assert!(rd.incoming_labels(0).unwrap().is_empty());
+    assert_eq!(rd.incoming_labels(1).unwrap(), vec![follows]);
+    assert_eq!(rd.incoming_labels(2).unwrap(), vec![follows, blocks]);
+    assert!(rd.outgoing_labels(2).unwrap().is_empty());
+    assert!(rd.outgoing_labels(99).unwrap().is_empty());
+}
+
+#[test]
+fn test_parallel_threshold() {
+    // batches below and above the threshold save the same data
+    let save = |threshold: usize| {
+        let (db, _file) = LatticeDb::create_temporary().unwrap();
+        let mut wr = db.begin_write().unwrap();
+        wr.set_parallel_threshold(threshold);
+        let name = wr.register_property(None, &()).unwrap();
+        let link = wr.register_property(None, &()).unwrap();
+        let graphs = (0..5)
+            .map(|i| {
+                let mut graph = GraphBuilder::new();
+                let a = graph
+                    .new_vertex()
+                    .new_attribute(name, i as u64)
+                    .unwrap()
+                    .handle();
+                let b = graph.new_vertex().handle();
+                graph.new_edge(a, link, b).unwrap();
+                graph
+            })
+            .collect();
+        wr.save_graphs_parallel(graphs).unwrap();
+        wr.commit().unwrap();
+        let rd = db.begin_read().unwrap();
+        (0..5)
+            .map(|id| rd.dump_graph(id).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let sequential = save(usize::MAX);
+    assert_eq!(sequential, save(0));
+    assert_eq!(sequential[4].vertices[0].id, 8);
+}
+
+#[test]
+fn test_edge_count_by_label() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let follows = wr.register_property(None, &()).unwrap();
+    let blocks = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let handles: Vec<_> = (0..3).map(|_| graph.new_vertex().handle()).collect();
+    graph.new_edge(handles[0], follows, handles[1]).unwrap();
+    graph.new_edge(handles[1], follows, handles[2]).unwrap();
+    graph.new_edge(handles[2], follows, handles[0]).unwrap();
+    graph.new_edge(handles[0], blocks, handles[2]).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    let counts = rd.edge_count_by_label().unwrap();
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts[&follows], 3);
+    assert_eq!(counts[&blocks], 1);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_round_trip() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let options = PropertyOptions {
+        case_insensitive: true,
+        ..Default::default()
+    };
+    let name = wr
+        .register_property_with_options("name", &(), options)
+        .unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph
+        .new_vertex()
+        .new_attribute(name, "a")
+        .unwrap()
+        .handle();
+    let b = graph
+        .new_vertex()
+        .new_attribute(name, None::<u64>)
+        .unwrap()
+        .handle();
+    graph.new_edge(a, link, b).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // handles keep their options, dumps keep their values
+    let json = serde_json::to_string(&name).unwrap();
+    let decoded: PropertyHandle = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, name);
+    assert_eq!(decoded.options(), options);
+    let json = serde_json::to_string(&a).unwrap();
+    assert_eq!(serde_json::from_str::<VertexHandle>(&json).unwrap(), a);
+    let dump = db.begin_read().unwrap().dump_graph(0).unwrap();
+    let json = serde_json::to_string(&dump).unwrap();
+    assert_eq!(serde_json::from_str::<GraphDump>(&json).unwrap(), dump);
+    let direction = serde_json::to_string(&EdgeDirection::Incoming).unwrap();
+    assert!(matches!(
+        serde_json::from_str(&direction).unwrap(),
+        EdgeDirection::Incoming
+    ));
+}
+
+#[test]
+fn test_search_traced() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let kind = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let user = graph
+        .new_vertex()
+        .new_attribute(kind, "user")
+        .unwrap()
+        .handle();
+    for _ in 0..3 {
+        let post = graph
+            .new_vertex()
+            .new_attribute(kind, "post")
+            .unwrap()
+            .handle();
+        graph.new_edge(user, link, post).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // one size per node, in the order they were evaluated
+    let mut query = QueryBuilder::new();
+    let users = query.match_attr(kind, "user").unwrap();
+    let posts = query.match_outgoing(link, users).unwrap();
+    let all_posts = query.match_attr(kind, "post").unwrap();
+    let root = query.group_and(vec![posts, all_posts]).unwrap();
+    query.set_root(root);
+    let query = query.compile().unwrap();
+    let rd = db.begin_read().unwrap();
+    let (ids, sizes) = rd.search_traced(&query).unwrap();
+    assert_eq!(ids, rd.search(&query).unwrap());
+    assert_eq!(ids, vec![1, 2, 3]);
+    assert_eq!(sizes.last(), Some(&3));
+    let mut sorted = sizes.clone();
+    sorted.sort();
+    assert_eq!(sorted, vec![1, 3, 3, 3]);
+}
+
+#[test]
+fn test_has_edge() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let follows = wr.register_property(None, &()).unwrap();
+    let blocks = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph.new_vertex().handle();
+    let b = graph.new_vertex().handle();
+    graph.new_edge(a, follows, b).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // direction and label both have to match
+    let rd = db.begin_read().unwrap();
+    assert!(rd.has_edge(0, follows, 1).unwrap());
+    assert!(!rd.has_edge(1, follows, 0).unwrap());
+    assert!(!rd.has_edge(0, blocks, 1).unwrap());
+    assert!(!rd.has_edge(0, follows, 7).unwrap());
+    drop(rd);
+
+    let mut graph = db.begin_read().unwrap().load_graph(0).unwrap();
+    let edge = EdgeHandle::from_raw(graph.iter_edges().next().unwrap().0);
+    graph.remove_edge(edge).unwrap();
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    assert!(!db.begin_read().unwrap().has_edge(0, follows, 1).unwrap());
+}
+
+#[test]
+fn test_which_have_attribute() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let email = wr.register_property(None, &()).unwrap();
+    let phone = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(email, "a@x").unwrap();
+    graph.new_vertex().new_attribute(phone, 5u64).unwrap();
+    graph.new_vertex().new_attribute(email, "c@x").unwrap();
+    graph
+        .new_vertex()
+        .new_attribute(email, None::<&str>)
+        .unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // any value counts, including null, ids outside the set are ignored
+    let rd = db.begin_read().unwrap();
+    let present = rd.which_have_attribute(&[0, 1, 3, 9], email).unwrap();
+    assert_eq!(present.into_iter().collect::<Vec<_>>(), vec![0, 3]);
+    let present = rd.which_have_attribute(&[0, 1, 2], phone).unwrap();
+    assert_eq!(present.into_iter().collect::<Vec<_>>(), vec![1]);
+    assert!(rd.which_have_attribute(&[], email).unwrap().is_empty());
+}
+
+#[test]
+fn test_skip_vertex_graph_mapping() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let vertex = |n: &str| {
+        let mut graph = GraphBuilder::new();
+        graph.new_vertex().new_attribute(name, n).unwrap();
+        graph
+    };
+    wr.save_graphs_parallel(vec![vertex("mapped")]).unwrap();
+    wr.set_vertex_graph_mapping(false);
+    wr.save_graphs_parallel(vec![vertex("ephemeral")]).unwrap();
+    wr.commit().unwrap();
+
+    // unmapped vertices are still searchable, but have no graph
+    let rd = db.begin_read().unwrap();
+    let mut query = QueryBuilder::new();
+    let mapped = query.match_attr(name, "mapped").unwrap();
+    let ephemeral = query.match_attr(name, "ephemeral").unwrap();
+    let root = query.group_or(vec![mapped, ephemeral]).unwrap();
+    query.set_root(root);
+    let query = query.compile().unwrap();
+    assert_eq!(rd.search(&query).unwrap(), vec![0, 1]);
+    assert_eq!(
+        rd.get_graph_ids_from_vertices(&[0, 1]).unwrap(),
+        vec![Some(0), None]
+    );
+    let records = rd.search_records(&query).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].id, 0);
+}
+
+#[test]
+fn test_group_results_by() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let kind = wr.register_property(None, &()).unwrap();
+    let color = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    for c in ["red", "blue", "red"] {
+        graph
+            .new_vertex()
+            .new_attribute(kind, "car")
+            .unwrap()
+            .new_attribute(color, c)
+            .unwrap();
+    }
+    graph.new_vertex().new_attribute(kind, "car").unwrap();
+    graph.new_vertex().new_attribute(color, "red").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // only results are grouped, results without a color are left out
+    let mut query = QueryBuilder::new();
+    let cars = query.match_attr(kind, "car").unwrap();
+    query.set_root(cars);
+    let rd = db.begin_read().unwrap();
+    let groups = rd
+        .group_results_by(&query.compile().unwrap(), color)
+        .unwrap();
+    assert_eq!(groups.len(), 2);
+    let red = color.hash_value(&Primitive::Text("red".to_string()));
+    let blue = color.hash_value(&Primitive::Text("blue".to_string()));
+    assert_eq!(groups[&red], vec![0, 2]);
+    assert_eq!(groups[&blue], vec![1]);
+}
+
+#[test]
+fn test_id_space_exhausted() {
+    let (db, file) = LatticeDb::create_temporary().unwrap();
+    drop(db);
+
+    // vertex ids near the end of the sequence
+    let sequences: redb::TableDefinition<u8, u64> = redb::TableDefinition::new("_lattice_seq");
+    let raw = redb::Database::open(file.path()).unwrap();
+    let wt = raw.begin_write().unwrap();
+    wt.open_table(sequences)
+        .unwrap()
+        .insert(2, u64::MAX - 1)
+        .unwrap();
+    wt.commit().unwrap();
+    drop(raw);
+
+    let db = LatticeDb::open(file.path()).unwrap();
+    let vertices = |count: usize| {
+        let mut graph = GraphBuilder::new();
+        for _ in 0..count {
+            graph.new_vertex();
+        }
+        graph
+    };
+    let mut wr = db.begin_write().unwrap();
+    assert!(matches!(
+        wr.save_graphs_parallel(vec![vertices(2)]),
+        Err(LatticeError::IdSpaceExhausted)
+    ));
+    drop(wr);
+
+    // the last id can still be handed out, once
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![vertices(1)]).unwrap();
+    assert!(matches!(
+        wr.save_graphs_parallel(vec![vertices(1)]),
+        Err(LatticeError::IdSpaceExhausted)
+    ));
+    wr.commit().unwrap();
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.dump_graph(0).unwrap().vertices[0].id, u64::MAX - 1);
+}
+
+#[test]
+fn test_vertex_attribute_hash() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let options = PropertyOptions {
+        case_insensitive: true,
+        ..Default::default()
+    };
+    let name = wr
+        .register_property_with_options("name", &(), options)
+        .unwrap();
+    let age = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(name, "Ann").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // the hash follows the property options, so it matches the index
+    let rd = db.begin_read().unwrap();
+    let hash = rd.vertex_attribute_hash(0, name).unwrap().unwrap();
+    assert_eq!(hash, name.hash_value(&Primitive::Text("ann".to_string())));
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr_hash(name, hash);
+    query.set_root(root);
+    assert_eq!(rd.search(&query.compile().unwrap()).unwrap(), vec![0]);
+
+    assert_eq!(rd.vertex_attribute_hash(0, age).unwrap(), None);
+    assert!(matches!(
+        rd.vertex_attribute_hash(5, name),
+        Err(LatticeError::VertexNotFound)
+    ));
+}
+
+#[test]
+fn test_remove_vertex_returns_data() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    let a = graph
+        .new_vertex()
+        .new_attribute(name, "a")
+        .unwrap()
+        .handle();
+    let b = graph.new_vertex().handle();
+    graph.new_edge(a, link, b).unwrap();
+    graph.new_edge(b, link, a).unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // the removed data keeps its global id and attributes, its edges are gone from the graph
+    let mut graph = db.begin_read().unwrap().load_graph(0).unwrap();
+    let removed = graph.remove_vertex(a).unwrap();
+    assert_eq!(removed.global_id, Some(0));
+    assert_eq!(
+        removed.attributes,
+        vec![(name, Primitive::Text("a".to_string()))]
+    );
+    assert_eq!(removed.outgoing_edges.len(), 1);
+    assert_eq!(removed.incoming_edges.len(), 1);
+    assert_eq!(graph.iter_edges().count(), 0);
+    assert!(matches!(
+        graph.remove_vertex(a),
+        Err(LatticeError::VertexNotFound)
+    ));
+
+    let mut wr = db.begin_write().unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+    let rd = db.begin_read().unwrap();
+    assert!(!rd.has_edge(1, link, 0).unwrap());
+    assert_eq!(rd.dump_graph(0).unwrap().vertices.len(), 1);
+}
+
+#[test]
+fn test_incident_edges() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let link = wr.register_property(None, &()).unwrap();
+
+    // chain 0 -> 1 -> 2 -> 3 -> 4
+    let mut graph = GraphBuilder::new();
+    let handles: Vec<_> = (0..5).map(|_| graph.new_vertex().handle()).collect();
+    for pair in handles.windows(2) {
+        graph.new_edge(pair[0], link, pair[1]).unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // edges inside the set are listed once, edges crossing it in either direction are included
+    let rd = db.begin_read().unwrap();
+    let set: lattice_db::roaring::RoaringTreemap = [1, 2].into_iter().collect();
+    let edges = rd.incident_edges(&set).unwrap();
+    let pairs: Vec<_> = edges.iter().map(|(from, _, to)| (*from, *to)).collect();
+    assert_eq!(pairs, vec![(0, 1), (1, 2), (2, 3)]);
+    assert!(edges.iter().all(|(_, label, _)| *label == edges[0].1));
+    let empty = lattice_db::roaring::RoaringTreemap::new();
+    assert!(rd.incident_edges(&empty).unwrap().is_empty());
+}
+
+#[test]
+fn test_search_with_graph_ids() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let kind = wr.register_property(None, &()).unwrap();
+    let mut graphs = vec![];
+    for kinds in [vec!["user", "post"], vec!["post", "user", "user"]] {
+        let mut graph = GraphBuilder::new();
+        for k in kinds {
+            graph.new_vertex().new_attribute(kind, k).unwrap();
+        }
+        graphs.push(graph);
+    }
+    wr.save_graphs_parallel(graphs).unwrap();
+    wr.set_vertex_graph_mapping(false);
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(kind, "user").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+    wr.commit().unwrap();
+
+    // the unmapped user is left out
+    let mut query = QueryBuilder::new();
+    let users = query.match_attr(kind, "user").unwrap();
+    query.set_root(users);
+    let rd = db.begin_read().unwrap();
+    assert_eq!(
+        rd.search_with_graph_ids(&query.compile().unwrap()).unwrap(),
+        vec![(0, 0), (3, 1), (4, 1)]
+    );
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_matching_queries() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let kind = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    for _ in 0..3 {
+        graph.new_vertex().new_attribute(kind, "user").unwrap();
+    }
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+
+    // materialize the matches of saved queries 2 and 5
+    for (query_id, vertex) in [(2, 0), (2, 1), (5, 1)] {
+        wr.insert_query_match(query_id, vertex).unwrap();
+    }
+    wr.commit().unwrap();
+
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.matching_queries(0).unwrap(), vec![2]);
+    assert_eq!(rd.matching_queries(1).unwrap(), vec![2, 5]);
+    assert!(rd.matching_queries(2).unwrap().is_empty());
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_verify_values_drops_collisions() {
+    let (db, _file) = LatticeDb::create_temporary().unwrap();
+    let mut wr = db.begin_write().unwrap();
+    let name = wr.register_property(None, &()).unwrap();
+    let mut graph = GraphBuilder::new();
+    graph.new_vertex().new_attribute(name, "alice").unwrap();
+    graph.new_vertex().new_attribute(name, "bob").unwrap();
+    wr.save_graphs_parallel(vec![graph]).unwrap();
+
+    // index bob under the hash of alice, as if the two values collided
+    let alice_hash = name.hash_value(&Primitive::Text("alice".to_string()));
+    wr.insert_scalar_entry(name, alice_hash, 1).unwrap();
+    wr.commit().unwrap();
+
+    let mut query = QueryBuilder::new();
+    let root = query.match_attr(name, "alice").unwrap();
+    query.set_root(root);
+    let mut query = query.compile().unwrap();
+    let rd = db.begin_read().unwrap();
+    assert_eq!(rd.search(&query).unwrap(), vec![0, 1]);
+    query.set_verify_values(true);
+    assert_eq!(rd.search(&query).unwrap(), vec![0]);
+}